@@ -19,6 +19,15 @@ fn main() {
     )
     .unwrap();
 
+    // Crashes due to running out of file descriptors are common, and the
+    // minidump must still be written in that case
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let _fds = if std::env::args().any(|a| a == "--exhaust-fds") {
+        exhaust_fds()
+    } else {
+        Vec::new()
+    };
+
     #[allow(unsafe_code)]
     unsafe {
         if std::env::args().any(|a| a == "--crash") {
@@ -27,3 +36,25 @@ fn main() {
         }
     }
 }
+
+/// Lowers the file descriptor limit and opens files until it is reached
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn exhaust_fds() -> Vec<std::fs::File> {
+    #[allow(unsafe_code)]
+    // SAFETY: syscall with a valid rlimit
+    unsafe {
+        let limit = libc::rlimit {
+            rlim_cur: 64,
+            rlim_max: 64,
+        };
+        libc::setrlimit(libc::RLIMIT_NOFILE, &limit);
+    }
+
+    let mut files = Vec::new();
+    while let Ok(file) = std::fs::File::open("/dev/null") {
+        files.push(file);
+    }
+
+    println!("Opened {} files until the limit was reached", files.len());
+    files
+}
@@ -0,0 +1,22 @@
+// C API of breakpad-handler, available when it is built with the `c-api`
+// feature. The handler itself is attached from Rust.
+#pragma once
+
+#include <stdbool.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+// Sets an annotation that is attached to the crash event if the process
+// crashes, or removes it if value is NULL. Both strings must be nul
+// terminated UTF-8. Returns false if key is NULL or either string is invalid.
+bool sentry_breakpad_set_annotation(const char* key, const char* value);
+
+// Writes a minidump of the current process with the attached handler, without
+// crashing. Returns false if no handler is attached or it failed.
+bool sentry_breakpad_generate_minidump(void);
+
+#ifdef __cplusplus
+}
+#endif
@@ -0,0 +1,34 @@
+//! Key/value pairs, aka crash keys, that describe the state of the process and
+//! are attached to the crash event if it crashes
+
+use std::collections::BTreeMap;
+
+static ANNOTATIONS: std::sync::Mutex<BTreeMap<String, String>> =
+    std::sync::Mutex::new(BTreeMap::new());
+
+/// Sets an annotation, or removes it if `value` is `None`. Annotations are
+/// process wide, so they can be set before a handler is attached, and from
+/// C/C++ with the `c-api` feature.
+pub fn set_annotation(key: &str, value: Option<&str>) {
+    let mut annotations = ANNOTATIONS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+    match value {
+        Some(value) => {
+            annotations.insert(key.to_owned(), value.to_owned());
+        }
+        None => {
+            annotations.remove(key);
+        }
+    }
+}
+
+/// Gets a snapshot of the annotations that are currently set. This locks, so
+/// it must only be called once the minidump has been written.
+pub fn annotations() -> BTreeMap<String, String> {
+    ANNOTATIONS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .clone()
+}
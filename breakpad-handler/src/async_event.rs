@@ -0,0 +1,196 @@
+use crate::CrashEvent;
+use std::{
+    collections::VecDeque,
+    future::Future,
+    path::PathBuf,
+    pin::Pin,
+    sync::{atomic, Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<PathBuf>,
+    waker: Option<Waker>,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    senders: atomic::AtomicUsize,
+}
+
+impl Shared {
+    fn push(&self, minidump_path: PathBuf) {
+        // If the lock is poisoned we still want the notification to go through,
+        // there is nothing in the state that can be left inconsistent
+        let mut state = self
+            .state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        state.queue.push_back(minidump_path);
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Adapter that forwards crash notifications into a channel, so that they can
+/// be handled by the application's async runtime, eg. tokio or async-std.
+///
+/// The fatal path is kept synchronous, the wrapped [`CrashEvent`] is always
+/// called first, directly from the crash handler, as the process is about to
+/// terminate and the runtime will most likely never get to poll the
+/// [`CrashReceiver`]. The channel is mainly useful for notifications that
+/// don't terminate the process, which are sent via a [`CrashSender`].
+pub struct AsyncCrashEvent {
+    fatal: Box<dyn CrashEvent>,
+    sender: CrashSender,
+}
+
+impl AsyncCrashEvent {
+    /// Creates the adapter, calling `fatal` synchronously for every crash
+    /// before forwarding it to the returned receiver
+    pub fn new<E: CrashEvent + 'static>(fatal: E) -> (Self, CrashReceiver) {
+        let shared = Arc::new(Shared::default());
+
+        let receiver = CrashReceiver {
+            shared: shared.clone(),
+        };
+
+        (
+            Self {
+                fatal: Box::new(fatal),
+                sender: CrashSender::new(shared),
+            },
+            receiver,
+        )
+    }
+
+    /// Gets a sender for the same channel, eg. to notify the application of
+    /// dumps that were written on demand, or that have finished uploading
+    pub fn sender(&self) -> CrashSender {
+        self.sender.clone()
+    }
+}
+
+impl CrashEvent for AsyncCrashEvent {
+    fn on_crash(&self, minidump_path: PathBuf) {
+        self.fatal.on_crash(minidump_path.clone());
+        self.sender.send(minidump_path);
+    }
+
+    fn on_crash_with_info(&self, minidump_path: PathBuf, info: &crate::CrashInfo) {
+        self.fatal.on_crash_with_info(minidump_path.clone(), info);
+        self.sender.send(minidump_path);
+    }
+
+    fn on_crash_with_result(
+        &self,
+        result: Result<crate::MinidumpInfo, crate::DumpError>,
+        info: &crate::CrashInfo,
+    ) {
+        let minidump_path = match &result {
+            Ok(written) => written.minidump_path.clone(),
+            Err(err) => err.minidump_path().to_owned(),
+        };
+        self.fatal.on_crash_with_result(result, info);
+        self.sender.send(minidump_path);
+    }
+}
+
+/// Sends minidump paths to the [`CrashReceiver`] of an [`AsyncCrashEvent`]
+pub struct CrashSender {
+    shared: Arc<Shared>,
+}
+
+impl CrashSender {
+    fn new(shared: Arc<Shared>) -> Self {
+        shared.senders.fetch_add(1, atomic::Ordering::Relaxed);
+        Self { shared }
+    }
+
+    /// Queues the path, waking the task waiting on the receiver if there is one
+    pub fn send(&self, minidump_path: PathBuf) {
+        self.shared.push(minidump_path);
+    }
+}
+
+impl Clone for CrashSender {
+    fn clone(&self) -> Self {
+        Self::new(self.shared.clone())
+    }
+}
+
+impl Drop for CrashSender {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, atomic::Ordering::AcqRel) == 1 {
+            // Wake the receiver so that it can see the channel is closed
+            let mut state = self
+                .shared
+                .state
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Receives the minidump paths forwarded by an [`AsyncCrashEvent`] and its
+/// [`CrashSender`]s. This doesn't depend on any particular runtime, the future
+/// returned by [`CrashReceiver::recv`] can be awaited on any executor.
+pub struct CrashReceiver {
+    shared: Arc<Shared>,
+}
+
+impl CrashReceiver {
+    /// Waits for the next minidump path, resolving to `None` once the channel
+    /// is empty and every sender, including the [`AsyncCrashEvent`], has been
+    /// dropped
+    pub fn recv(&mut self) -> Recv<'_> {
+        Recv { receiver: self }
+    }
+
+    /// Gets the next minidump path if one is queued, without waiting
+    pub fn try_recv(&mut self) -> Option<PathBuf> {
+        self.shared
+            .state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .queue
+            .pop_front()
+    }
+}
+
+/// Future returned by [`CrashReceiver::recv`]
+pub struct Recv<'r> {
+    receiver: &'r mut CrashReceiver,
+}
+
+impl Future for Recv<'_> {
+    type Output = Option<PathBuf>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let shared = &self.receiver.shared;
+        let mut state = shared
+            .state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        if let Some(minidump_path) = state.queue.pop_front() {
+            return Poll::Ready(Some(minidump_path));
+        }
+
+        // The last sender takes the lock before waking us, so checking the
+        // count while holding it means we can't miss the channel closing
+        if shared.senders.load(atomic::Ordering::Acquire) == 0 {
+            return Poll::Ready(None);
+        }
+
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
@@ -0,0 +1,164 @@
+//! Out of process crash handling, where a crash generation server writes the
+//! minidumps for the processes that crash, so that a process whose heap or
+//! stack is corrupted doesn't have to write its own minidump
+
+use crate::{CrashEvent, Error};
+
+/// Identifies the crash generation server that clients send their crashes to
+#[derive(Debug)]
+pub enum CrashServerChannel {
+    /// One end of the socket pair created with [`create_report_channel`]. The
+    /// server listens on the server end, and the client, which usually
+    /// inherits it from the server process, sends its crashes over the client
+    /// end.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    Socket(std::os::fd::OwnedFd),
+    /// The name of the pipe on Windows, eg. `\\.\pipe\my-app-crash-server`, or
+    /// of the mach port on macOS, that the server listens on
+    #[cfg(any(windows, target_os = "macos"))]
+    Named(String),
+}
+
+impl CrashServerChannel {
+    /// Invokes `f` with the channel as passed to breakpad
+    pub(crate) fn with_sys<R>(&self, f: impl FnOnce(&breakpad_sys::CrashServerChannel) -> R) -> R {
+        match self {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::Socket(fd) => {
+                use std::os::fd::AsRawFd;
+
+                f(&breakpad_sys::CrashServerChannel {
+                    name: std::ptr::null(),
+                    name_len: 0,
+                    fd: fd.as_raw_fd(),
+                })
+            }
+            #[cfg(any(windows, target_os = "macos"))]
+            Self::Named(name) => {
+                let name = crate::path_chars(std::ffi::OsStr::new(name));
+
+                f(&breakpad_sys::CrashServerChannel {
+                    name: name.as_ptr(),
+                    name_len: name.len(),
+                    fd: -1,
+                })
+            }
+        }
+    }
+}
+
+/// Creates the socket pair that a [`CrashGenerationServer`] and its client
+/// communicate over, returning the server end and the client end. Only the
+/// client end is inherited by child processes.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn create_report_channel() -> Result<(CrashServerChannel, CrashServerChannel), Error> {
+    use std::os::fd::FromRawFd;
+
+    let mut server_fd = -1;
+    let mut client_fd = -1;
+
+    #[allow(unsafe_code)]
+    // SAFETY: Calling into C code, the descriptors are owned by us on success
+    unsafe {
+        if !breakpad_sys::create_report_channel(&mut server_fd, &mut client_fd) {
+            return Err(Error::CrashServerFailed);
+        }
+
+        Ok((
+            CrashServerChannel::Socket(std::os::fd::OwnedFd::from_raw_fd(server_fd)),
+            CrashServerChannel::Socket(std::os::fd::OwnedFd::from_raw_fd(client_fd)),
+        ))
+    }
+}
+
+/// A server that writes minidumps for the clients that crash, which attach
+/// their handler with [`crate::BreakpadHandler::attach_out_of_process`]. The
+/// server is stopped when this is dropped.
+///
+/// On Linux/Android the server writes the minidump by ptracing the crashed
+/// client, so it must be allowed to, eg. by being its parent process if
+/// Yama's `ptrace_scope` is 1.
+pub struct CrashGenerationServer {
+    server: *mut breakpad_sys::CrashGenerationServer,
+    on_dump: *mut Box<dyn CrashEvent>,
+    /// Kept open until the server has been stopped
+    _channel: CrashServerChannel,
+}
+
+#[allow(unsafe_code)]
+unsafe impl Send for CrashGenerationServer {}
+#[allow(unsafe_code)]
+unsafe impl Sync for CrashGenerationServer {}
+
+impl CrashGenerationServer {
+    /// Starts listening for crashes on the channel, writing a minidump to the
+    /// designated directory for every client that crashes. `on_dump` is
+    /// invoked on a thread owned by the server once the minidump has been
+    /// written.
+    pub fn listen<P: AsRef<std::path::Path>>(
+        channel: CrashServerChannel,
+        crash_dir: P,
+        on_dump: Box<dyn CrashEvent>,
+    ) -> Result<Self, Error> {
+        extern "C" fn dump_callback(
+            path: *const breakpad_sys::PathChar,
+            path_len: usize,
+            ctx: *mut std::ffi::c_void,
+        ) {
+            #[allow(unsafe_code)]
+            // SAFETY: The path is valid for the duration of the callback, and
+            // the context lives until the server is stopped
+            let (path, on_dump) = unsafe {
+                (
+                    std::slice::from_raw_parts(path, path_len),
+                    &*ctx.cast::<Box<dyn CrashEvent>>(),
+                )
+            };
+
+            on_dump.on_crash(crate::path_from_chars(path));
+        }
+
+        let path = crate::path_chars(crash_dir.as_ref().as_os_str());
+        let on_dump = Box::into_raw(Box::new(on_dump));
+
+        #[allow(unsafe_code)]
+        // SAFETY: Calling into C code, the path and channel only need to be
+        // valid for the duration of the call
+        let server = unsafe {
+            channel.with_sys(|sys_channel| {
+                breakpad_sys::start_crash_generation_server(
+                    path.as_ptr(),
+                    path.len(),
+                    sys_channel,
+                    dump_callback,
+                    on_dump.cast(),
+                )
+            })
+        };
+
+        if server.is_null() {
+            #[allow(unsafe_code)]
+            // SAFETY: The server was never started, so nothing else refers to it
+            let _ = unsafe { Box::from_raw(on_dump) };
+            return Err(Error::CrashServerFailed);
+        }
+
+        Ok(Self {
+            server,
+            on_dump,
+            _channel: channel,
+        })
+    }
+}
+
+impl Drop for CrashGenerationServer {
+    fn drop(&mut self) {
+        #[allow(unsafe_code)]
+        // SAFETY: Calling into C code, the dump callback can't be invoked once
+        // the server has been stopped
+        unsafe {
+            breakpad_sys::stop_crash_generation_server(self.server);
+            let _ = Box::from_raw(self.on_dump);
+        }
+    }
+}
@@ -1,8 +1,32 @@
 use std::fmt;
 
+/// The component that has registered the crash handler
+#[derive(Clone, Debug)]
+pub struct HandlerOwner {
+    /// The location of the call that attached the handler, eg.
+    /// `src/main.rs:10:5`
+    pub tag: String,
+    /// When the handler was attached
+    pub registered_at: std::time::SystemTime,
+}
+
 #[derive(Debug)]
 pub enum Error {
-    HandlerAlreadyRegistered,
+    HandlerAlreadyRegistered {
+        /// The owner of the handler that is already registered. This is only
+        /// `None` if the other handler is still in the process of being
+        /// attached.
+        owner: Option<HandlerOwner>,
+    },
+    /// A path could not be passed to breakpad as it contains a nul byte
+    InvalidPath(std::path::PathBuf),
+    /// Breakpad failed to write a minidump
+    MinidumpWriteFailed,
+    /// The crash generation server, or the channel to it, could not be created
+    CrashServerFailed,
+    /// No [`BreakpadHandler`](crate::BreakpadHandler) is attached, see
+    /// [`generate_current_minidump`](crate::generate_current_minidump)
+    NotAttached,
 }
 
 impl std::error::Error for Error {}
@@ -10,9 +34,60 @@ impl std::error::Error for Error {}
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::HandlerAlreadyRegistered => {
-                f.write_str("Unable to register crash handler, only one is allowed at a time")
+            Self::HandlerAlreadyRegistered { owner } => {
+                f.write_str("Unable to register crash handler, only one is allowed at a time")?;
+
+                if let Some(owner) = owner {
+                    let elapsed = owner.registered_at.elapsed().unwrap_or_default();
+                    write!(
+                        f,
+                        ", it was registered by '{}' {}s ago",
+                        owner.tag,
+                        elapsed.as_secs()
+                    )?;
+                }
+
+                Ok(())
             }
+            Self::InvalidPath(path) => write!(f, "'{}' contains a nul byte", path.display()),
+            Self::MinidumpWriteFailed => f.write_str("Failed to write minidump"),
+            Self::CrashServerFailed => f.write_str("Failed to create crash generation server"),
+            Self::NotAttached => f.write_str("No crash handler is attached"),
+        }
+    }
+}
+
+/// Why a minidump couldn't be written for a crash, see
+/// [`CrashEvent::on_crash_with_result`](crate::CrashEvent::on_crash_with_result)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DumpError {
+    /// Breakpad failed to write the minidump, eg. because the disk is full,
+    /// the file is missing or empty if so
+    WriteFailed {
+        /// The path the minidump was meant to be written to
+        minidump_path: std::path::PathBuf,
+    },
+}
+
+impl DumpError {
+    /// The path the minidump was meant to be written to
+    pub fn minidump_path(&self) -> &std::path::Path {
+        match self {
+            Self::WriteFailed { minidump_path } => minidump_path,
+        }
+    }
+}
+
+impl std::error::Error for DumpError {}
+
+impl fmt::Display for DumpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WriteFailed { minidump_path } => write!(
+                f,
+                "failed to write minidump to '{}'",
+                minidump_path.display()
+            ),
         }
     }
 }
@@ -0,0 +1,99 @@
+//! Human readable names for the structured exception codes that crash handlers
+//! are notified of on Windows
+
+pub(crate) const EXCEPTION_ACCESS_VIOLATION: u32 = 0xc000_0005;
+pub(crate) const EXCEPTION_IN_PAGE_ERROR: u32 = 0xc000_0006;
+pub(crate) const EXCEPTION_STACK_OVERFLOW: u32 = 0xc000_00fd;
+pub(crate) const STATUS_HEAP_CORRUPTION: u32 = 0xc000_0374;
+pub(crate) const STATUS_STACK_BUFFER_OVERRUN: u32 = 0xc000_0409;
+
+/// Gets the name and description of an exception code
+pub(crate) fn code_info(code: u32) -> Option<(&'static str, &'static str)> {
+    Some(match code {
+        EXCEPTION_ACCESS_VIOLATION => ("EXCEPTION_ACCESS_VIOLATION", "invalid access to memory"),
+        EXCEPTION_IN_PAGE_ERROR => (
+            "EXCEPTION_IN_PAGE_ERROR",
+            "page could not be loaded into memory",
+        ),
+        0x8000_0001 => ("STATUS_GUARD_PAGE_VIOLATION", "guard page was accessed"),
+        0x8000_0002 => ("EXCEPTION_DATATYPE_MISALIGNMENT", "misaligned data access"),
+        0x8000_0003 => ("EXCEPTION_BREAKPOINT", "breakpoint was hit"),
+        0x8000_0004 => ("EXCEPTION_SINGLE_STEP", "single step trap"),
+        0xc000_0008 => ("STATUS_INVALID_HANDLE", "invalid handle was used"),
+        0xc000_0017 => ("STATUS_NO_MEMORY", "out of memory"),
+        0xc000_001d => ("EXCEPTION_ILLEGAL_INSTRUCTION", "illegal instruction"),
+        0xc000_0025 => (
+            "EXCEPTION_NONCONTINUABLE_EXCEPTION",
+            "execution continued after a noncontinuable exception",
+        ),
+        0xc000_0026 => (
+            "EXCEPTION_INVALID_DISPOSITION",
+            "exception handler returned an invalid disposition",
+        ),
+        0xc000_008c => (
+            "EXCEPTION_ARRAY_BOUNDS_EXCEEDED",
+            "array index out of bounds",
+        ),
+        0xc000_008d => (
+            "EXCEPTION_FLT_DENORMAL_OPERAND",
+            "denormal floating point operand",
+        ),
+        0xc000_008e => (
+            "EXCEPTION_FLT_DIVIDE_BY_ZERO",
+            "floating point divide by zero",
+        ),
+        0xc000_008f => (
+            "EXCEPTION_FLT_INEXACT_RESULT",
+            "floating point inexact result",
+        ),
+        0xc000_0090 => (
+            "EXCEPTION_FLT_INVALID_OPERATION",
+            "floating point invalid operation",
+        ),
+        0xc000_0091 => ("EXCEPTION_FLT_OVERFLOW", "floating point overflow"),
+        0xc000_0092 => (
+            "EXCEPTION_FLT_STACK_CHECK",
+            "floating point stack overflow or underflow",
+        ),
+        0xc000_0093 => ("EXCEPTION_FLT_UNDERFLOW", "floating point underflow"),
+        0xc000_0094 => ("EXCEPTION_INT_DIVIDE_BY_ZERO", "integer divide by zero"),
+        0xc000_0095 => ("EXCEPTION_INT_OVERFLOW", "integer overflow"),
+        0xc000_0096 => ("EXCEPTION_PRIV_INSTRUCTION", "privileged instruction"),
+        EXCEPTION_STACK_OVERFLOW => ("EXCEPTION_STACK_OVERFLOW", "stack overflow"),
+        STATUS_HEAP_CORRUPTION => ("STATUS_HEAP_CORRUPTION", "heap corruption was detected"),
+        STATUS_STACK_BUFFER_OVERRUN => (
+            "STATUS_STACK_BUFFER_OVERRUN",
+            "fast fail or stack buffer overrun",
+        ),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_exception_codes() {
+        assert_eq!(
+            code_info(EXCEPTION_ACCESS_VIOLATION),
+            Some(("EXCEPTION_ACCESS_VIOLATION", "invalid access to memory"))
+        );
+        assert_eq!(
+            code_info(EXCEPTION_STACK_OVERFLOW),
+            Some(("EXCEPTION_STACK_OVERFLOW", "stack overflow"))
+        );
+        assert_eq!(
+            code_info(0x8000_0003).map(|ci| ci.0),
+            Some("EXCEPTION_BREAKPOINT")
+        );
+    }
+
+    #[test]
+    fn ignores_unknown_codes() {
+        assert_eq!(code_info(0), None);
+        assert_eq!(code_info(0xe06d_7363), None);
+        // A signal code, which is only meaningful on Linux/Android
+        assert_eq!(code_info(1), None);
+    }
+}
@@ -0,0 +1,40 @@
+//! A tiny C API so that the C/C++ parts of a mixed codebase can contribute
+//! annotations and write minidumps through the handler attached from Rust, see
+//! `include/sentry_breakpad.h`
+
+use std::ffi::{c_char, CStr};
+
+/// Sets an annotation, or removes it if `value` is null. Returns `false` if
+/// `key` is null or either string is not valid UTF-8.
+#[allow(unsafe_code)]
+#[no_mangle]
+pub extern "C" fn sentry_breakpad_set_annotation(key: *const c_char, value: *const c_char) -> bool {
+    if key.is_null() {
+        return false;
+    }
+
+    // SAFETY: The caller guarantees non-null strings are nul terminated
+    let (key, value) = unsafe {
+        (
+            CStr::from_ptr(key),
+            (!value.is_null()).then(|| CStr::from_ptr(value)),
+        )
+    };
+
+    match (key.to_str(), value.map(CStr::to_str).transpose()) {
+        (Ok(key), Ok(value)) => {
+            crate::set_annotation(key, value);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Writes a minidump of the current process with the attached handler, without
+/// crashing. Returns `false` if no handler is attached or the minidump could
+/// not be written.
+#[allow(unsafe_code)]
+#[no_mangle]
+pub extern "C" fn sentry_breakpad_generate_minidump() -> bool {
+    crate::generate_current_minidump().is_ok()
+}
@@ -0,0 +1,140 @@
+//! Detection of a thread that stopped responding, eg. the main thread of an
+//! application being stuck in a deadlock, see [`HangMonitor`]
+
+use std::{
+    sync::{atomic, Arc, Condvar, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// Set while a [`HangMonitor`] is writing a minidump, so that the callbacks
+/// can tell it apart from a crash, see [`CrashInfo::hang`](crate::CrashInfo::hang)
+static DUMP_PENDING: atomic::AtomicBool = atomic::AtomicBool::new(false);
+
+/// Whether the minidump that is being written is for a hang
+pub(crate) fn is_pending() -> bool {
+    DUMP_PENDING.load(atomic::Ordering::Acquire)
+}
+
+/// State shared between the monitor and its watchdog thread
+struct Shared {
+    /// The point `last_heartbeat_ms` is relative to
+    epoch: Instant,
+    last_heartbeat_ms: atomic::AtomicU64,
+    stopped: Mutex<bool>,
+    wakeup: Condvar,
+}
+
+impl Shared {
+    fn elapsed_ms(&self) -> u64 {
+        self.epoch.elapsed().as_millis() as u64
+    }
+}
+
+/// Watches a thread that is expected to call [`HangMonitor::heartbeat`]
+/// regularly, eg. once per frame or every iteration of an event loop, and
+/// writes a minidump with the attached [`BreakpadHandler`](crate::BreakpadHandler)
+/// if it doesn't within the deadline. The process keeps running.
+///
+/// The [`CrashEvent`](crate::CrashEvent) is notified the same as for a crash,
+/// with [`CrashInfo::hang`](crate::CrashInfo::hang) set. Only one minidump is
+/// written per hang, the next one can only be written after the thread has
+/// called [`HangMonitor::heartbeat`] again. Nothing is written while no
+/// handler is attached.
+///
+/// The watchdog thread is stopped when the monitor is dropped.
+pub struct HangMonitor {
+    shared: Arc<Shared>,
+    watchdog: Option<std::thread::JoinHandle<()>>,
+}
+
+impl HangMonitor {
+    /// Starts the watchdog thread, which considers the monitored thread hung
+    /// if it hasn't called [`HangMonitor::heartbeat`] for `deadline`,
+    /// starting from now
+    pub fn start(deadline: Duration) -> std::io::Result<Self> {
+        let shared = Arc::new(Shared {
+            epoch: Instant::now(),
+            last_heartbeat_ms: atomic::AtomicU64::new(0),
+            stopped: Mutex::new(false),
+            wakeup: Condvar::new(),
+        });
+
+        let watchdog = {
+            let shared = shared.clone();
+            std::thread::Builder::new()
+                .name("breakpad-hang-monitor".to_owned())
+                .spawn(move || watch(&shared, deadline))?
+        };
+
+        Ok(Self {
+            shared,
+            watchdog: Some(watchdog),
+        })
+    }
+
+    /// Signals that the monitored thread is still responding. This is a
+    /// single atomic store, so it can be called as often as needed.
+    pub fn heartbeat(&self) {
+        self.shared
+            .last_heartbeat_ms
+            .store(self.shared.elapsed_ms(), atomic::Ordering::Relaxed);
+    }
+}
+
+impl Drop for HangMonitor {
+    fn drop(&mut self) {
+        *self
+            .shared
+            .stopped
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = true;
+        self.shared.wakeup.notify_all();
+
+        if let Some(watchdog) = self.watchdog.take() {
+            let _ = watchdog.join();
+        }
+    }
+}
+
+fn watch(shared: &Shared, deadline: Duration) {
+    let deadline_ms = deadline.as_millis() as u64;
+    // Checked a few times per deadline, so that a hang is detected within a
+    // fraction of the deadline of it being exceeded
+    let interval = (deadline / 4).max(Duration::from_millis(10));
+    // The heartbeat a minidump was last written for, so that a hang is only
+    // reported once
+    let mut reported = None;
+
+    let mut stopped = shared
+        .stopped
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    while !*stopped {
+        stopped = shared
+            .wakeup
+            .wait_timeout(stopped, interval)
+            .unwrap_or_else(PoisonError::into_inner)
+            .0;
+        if *stopped {
+            break;
+        }
+
+        let last_heartbeat = shared.last_heartbeat_ms.load(atomic::Ordering::Relaxed);
+        if shared.elapsed_ms().saturating_sub(last_heartbeat) < deadline_ms
+            || reported == Some(last_heartbeat)
+        {
+            continue;
+        }
+        reported = Some(last_heartbeat);
+
+        DUMP_PENDING.store(true, atomic::Ordering::Release);
+        crate::with_current_handler(|handler| {
+            #[allow(unsafe_code)]
+            // SAFETY: The handler can't be detached while we hold it
+            unsafe {
+                breakpad_sys::generate_minidump(handler)
+            }
+        });
+        DUMP_PENDING.store(false, atomic::Ordering::Release);
+    }
+}
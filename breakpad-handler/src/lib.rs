@@ -1,12 +1,335 @@
 mod error;
-pub use error::Error;
+pub use error::{DumpError, Error, HandlerOwner};
+mod annotations;
+pub use annotations::{annotations, set_annotation};
+mod task_names;
+pub use task_names::{register_task_names, task_names, TaskNames, MAX_TASK_LABEL_LEN};
+#[cfg(feature = "async")]
+mod async_event;
+mod crash_generation;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use crash_generation::create_report_channel;
+pub use crash_generation::{CrashGenerationServer, CrashServerChannel};
+#[cfg(windows)]
+mod exception;
+#[cfg(feature = "c-api")]
+mod ffi;
+mod hang;
+pub use hang::HangMonitor;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod redact;
+mod reporter;
+pub use reporter::CrashReporter;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod signal;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod stack;
+pub mod syms;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod truncated;
+#[cfg(feature = "async")]
+pub use async_event::{AsyncCrashEvent, CrashReceiver, CrashSender, Recv};
 
 use std::sync::atomic;
 
+/// The version of this crate, which determines the version of breakpad that
+/// writes the minidumps
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Details about the crash a minidump was written for, what is available
+/// depends on the platform
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CrashInfo {
+    /// The signal that caused the crash, only available on Linux/Android
+    pub signal: Option<i32>,
+    /// The `si_code` of the signal on Linux/Android, or the exception code on
+    /// Windows
+    pub code: Option<u32>,
+    /// The faulting address on Linux/Android, or the address of the exception
+    /// on Windows
+    pub address: Option<u64>,
+    /// The value of `GetLastError` on the crashing thread, only available on
+    /// Windows
+    pub last_error: Option<u32>,
+    /// The memory access that caused an access violation or in-page error,
+    /// only available on Windows
+    pub access: Option<MemoryAccess>,
+    /// The id of the crashing thread, only available on Linux/Android. On
+    /// other platforms it is only recorded in the exception stream of the
+    /// minidump.
+    pub thread_id: Option<u32>,
+    /// The stack pointer of the crashing thread when it crashed, only
+    /// available on Linux/Android
+    pub stack_pointer: Option<u64>,
+    /// Whether the minidump was written by a [`HangMonitor`] because the
+    /// thread it watches stopped responding, rather than for a crash
+    pub hang: bool,
+    /// Whether the minidump was requested with
+    /// [`BreakpadHandler::generate_minidump`],
+    /// [`BreakpadHandler::generate_minidump_with_exception`] or
+    /// [`generate_current_minidump`] while the process keeps running, rather
+    /// than written for a crash
+    pub requested: bool,
+    /// Whether breakpad truncated the stacks of some of the threads in the
+    /// minidump to stay within [`MinidumpSettings::with_size_limit`], only
+    /// available on Linux/Android
+    pub truncated: bool,
+    /// Whether removing the command line and environment from the minidump
+    /// for [`MinidumpSettings::with_redacted_process_info`] failed, in which
+    /// case they may still be in it, only on Linux/Android
+    pub redaction_failed: bool,
+}
+
+impl From<&breakpad_sys::CrashInfo> for CrashInfo {
+    fn from(ci: &breakpad_sys::CrashInfo) -> Self {
+        if ci.signal != 0 {
+            Self {
+                signal: Some(ci.signal),
+                code: Some(ci.code),
+                address: Some(ci.address),
+                thread_id: (ci.thread_id != 0).then_some(ci.thread_id),
+                stack_pointer: (ci.stack_pointer != 0).then_some(ci.stack_pointer),
+                ..Default::default()
+            }
+        } else if ci.code != 0 {
+            Self {
+                signal: None,
+                code: Some(ci.code),
+                address: Some(ci.address),
+                #[cfg(windows)]
+                last_error: Some(ci.last_error),
+                #[cfg(windows)]
+                access: MemoryAccess::from_parameters(ci),
+                ..Default::default()
+            }
+        } else {
+            Self::default()
+        }
+    }
+}
+
+/// The kind of memory access that caused an access violation
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+    /// Executing memory that is not executable, ie. a DEP violation
+    Execute,
+    Unknown(u64),
+}
+
+impl AccessKind {
+    /// Gets a short description of the access, eg. `write`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::Execute => "execute",
+            Self::Unknown(_) => "unknown access",
+        }
+    }
+}
+
+/// The memory access that caused an access violation or in-page error
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub kind: AccessKind,
+    /// The address that was accessed
+    pub address: u64,
+    /// The `NTSTATUS` of the failure to load the page for an in-page error
+    pub status: Option<u32>,
+}
+
+#[cfg(windows)]
+impl MemoryAccess {
+    fn from_parameters(ci: &breakpad_sys::CrashInfo) -> Option<Self> {
+        let status = match ci.code {
+            exception::EXCEPTION_ACCESS_VIOLATION if ci.num_parameters >= 2 => None,
+            exception::EXCEPTION_IN_PAGE_ERROR if ci.num_parameters >= 3 => {
+                Some(ci.parameters[2] as u32)
+            }
+            _ => return None,
+        };
+
+        let kind = match ci.parameters[0] {
+            0 => AccessKind::Read,
+            1 => AccessKind::Write,
+            8 => AccessKind::Execute,
+            other => AccessKind::Unknown(other),
+        };
+
+        Some(Self {
+            kind,
+            address: ci.parameters[1],
+            status,
+        })
+    }
+}
+
+impl CrashInfo {
+    /// Whether the crash was caused by a `SIGBUS`, eg. from accessing a memory
+    /// mapped file beyond its end after it was truncated. Only on Linux/Android
+    pub fn is_bus_error(&self) -> bool {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            self.signal == Some(libc::SIGBUS)
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            false
+        }
+    }
+
+    /// Whether the crash was a fail fast exception, ie. a stack cookie check
+    /// failure, a `__fastfail`, or heap corruption detected by the heap
+    /// manager. These are the most security relevant crashes, as they are
+    /// raised when memory corruption was detected. Only on Windows.
+    ///
+    /// Note that fail fast exceptions bypass the unhandled exception filter
+    /// breakpad installs, so they are only seen by the handler if they are
+    /// raised in a way that doesn't terminate the process immediately.
+    pub fn is_fail_fast(&self) -> bool {
+        #[cfg(windows)]
+        {
+            matches!(
+                self.code,
+                Some(exception::STATUS_STACK_BUFFER_OVERRUN | exception::STATUS_HEAP_CORRUPTION)
+            )
+        }
+        #[cfg(not(windows))]
+        {
+            false
+        }
+    }
+
+    /// Whether the crash was caused by the crashing thread overflowing its
+    /// stack. On Linux/Android, this is a `SIGSEGV` within 64KiB below the
+    /// stack of the thread, ie. in or just past its guard page, which is
+    /// looked up in `/proc/self/task/<tid>/maps`, so this should only be
+    /// called after the minidump has been written, ie. from a [`CrashEvent`].
+    /// On Windows, this is an `EXCEPTION_STACK_OVERFLOW`.
+    pub fn is_stack_overflow(&self) -> bool {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            match (self.signal, self.stack_pointer, self.address) {
+                (Some(libc::SIGSEGV), Some(stack_pointer), Some(address)) => {
+                    stack::is_overflow(self.thread_id, stack_pointer, address)
+                }
+                _ => false,
+            }
+        }
+        #[cfg(windows)]
+        {
+            self.code == Some(exception::EXCEPTION_STACK_OVERFLOW)
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+        {
+            false
+        }
+    }
+
+    /// Gets the name of the signal that caused the crash, eg. `SIGSEGV`. Only
+    /// on Linux/Android
+    pub fn signal_name(&self) -> Option<&'static str> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            signal::signal_name(self.signal?)
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            None
+        }
+    }
+
+    /// Gets the name and a short description of the `si_code` of the signal
+    /// that caused the crash on Linux/Android, eg. `SEGV_MAPERR` and "address
+    /// not mapped to object", or of the exception code on Windows, eg.
+    /// `EXCEPTION_ACCESS_VIOLATION` and "invalid access to memory"
+    pub fn code_info(&self) -> Option<(&'static str, &'static str)> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            signal::code_info(self.signal?, self.code? as i32)
+        }
+        #[cfg(windows)]
+        {
+            exception::code_info(self.code?)
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+        {
+            None
+        }
+    }
+
+    /// Finds the file that is mapped at the faulting address, if any, by
+    /// looking it up in `/proc/self/maps`. This should only be called after
+    /// the minidump has been written, ie. from a [`CrashEvent`]. Only on
+    /// Linux/Android
+    pub fn fault_mapping(&self) -> Option<FaultMapping> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let address = self.address?;
+            let maps = std::fs::read_to_string("/proc/self/maps").ok()?;
+
+            maps.lines().find_map(|line| {
+                // start-end perms offset dev inode path
+                let mut parts = line.split_whitespace();
+                let (start, end) = parts.next()?.split_once('-')?;
+                let start = u64::from_str_radix(start, 16).ok()?;
+                let end = u64::from_str_radix(end, 16).ok()?;
+
+                if address < start || address >= end {
+                    return None;
+                }
+
+                let offset = u64::from_str_radix(parts.nth(1)?, 16).ok()?;
+                // The path is the remainder of the line, which can contain spaces
+                let path = line.find(" /").map(|i| line[i + 1..].to_owned())?;
+
+                Some(FaultMapping {
+                    path: path.into(),
+                    offset: offset + (address - start),
+                })
+            })
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            None
+        }
+    }
+}
+
+/// The file that was mapped at the faulting address of a crash
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FaultMapping {
+    /// The path of the mapped file, which is suffixed with ` (deleted)` if the
+    /// file was deleted after it was mapped
+    pub path: std::path::PathBuf,
+    /// The offset in the file that was being accessed
+    pub offset: u64,
+}
+
 /// Trait used by the crash handler to notify the implementor that a crash was
 /// captured, providing the full path on disk to that minidump.
 pub trait CrashEvent: Sync + Send {
     fn on_crash(&self, minidump_path: std::path::PathBuf);
+
+    /// Same as [`CrashEvent::on_crash`], but also provides the details of the
+    /// crash, the default implementation ignores the details.
+    fn on_crash_with_info(&self, minidump_path: std::path::PathBuf, _info: &CrashInfo) {
+        self.on_crash(minidump_path);
+    }
+
+    /// Same as [`CrashEvent::on_crash_with_info`], but also provides whether
+    /// the minidump was actually written. This is what the handler actually
+    /// calls, the default implementation passes the path of the minidump on
+    /// regardless.
+    fn on_crash_with_result(&self, result: Result<MinidumpInfo, DumpError>, info: &CrashInfo) {
+        let minidump_path = match result {
+            Ok(written) => written.minidump_path,
+            Err(err) => err.minidump_path().to_owned(),
+        };
+        self.on_crash_with_info(minidump_path, info);
+    }
 }
 
 impl<F> CrashEvent for F
@@ -18,11 +341,82 @@ where
     }
 }
 
+/// A minidump that was written for a crash, see
+/// [`CrashEvent::on_crash_with_result`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MinidumpInfo {
+    /// The path of the minidump
+    pub minidump_path: std::path::PathBuf,
+    /// The size of the minidump in bytes
+    pub size: u64,
+}
+
+/// Trait used by the crash handler to decide if a minidump should be written
+/// for a crash, eg. to ignore the crashes that are expected and handled by
+/// something else, such as the `SIGSEGV`s a WebAssembly runtime uses to
+/// detect out of bounds accesses.
+///
+/// This is invoked in the signal handler on Linux/Android, so it must only
+/// do things that are safe to do there, ie. no allocations or locks. On macOS
+/// the [`CrashInfo`] is always empty.
+pub trait CrashFilter: Sync + Send {
+    /// Returns `false` to skip writing a minidump, the crash is then passed on
+    /// as if no handler was attached
+    fn should_dump(&self, info: &CrashInfo) -> bool;
+}
+
+impl<F> CrashFilter for F
+where
+    F: Fn(&CrashInfo) -> bool + Send + Sync,
+{
+    fn should_dump(&self, info: &CrashInfo) -> bool {
+        self(info)
+    }
+}
+
 static HANDLER_ATTACHED: atomic::AtomicBool = atomic::AtomicBool::new(false);
+/// Who attached the current handler, so that conflicts between components
+/// that each try to attach one can be diagnosed
+static HANDLER_OWNER: std::sync::Mutex<Option<HandlerOwner>> = std::sync::Mutex::new(None);
+/// The attached handler, for the C API and the [`HangMonitor`], which have no
+/// way to refer to it
+static CURRENT_HANDLER: std::sync::Mutex<Option<CurrentHandler>> = std::sync::Mutex::new(None);
+
+/// Set while a minidump is written on request, see [`CrashInfo::requested`]
+static DUMP_REQUESTED: atomic::AtomicBool = atomic::AtomicBool::new(false);
+
+/// Writes a minidump on request, marking it as such for the callbacks
+fn requested_dump(generate: impl FnOnce() -> bool) -> bool {
+    DUMP_REQUESTED.store(true, atomic::Ordering::Release);
+    let written = generate();
+    DUMP_REQUESTED.store(false, atomic::Ordering::Release);
+    written
+}
+
+struct CurrentHandler(*mut breakpad_sys::ExceptionHandler);
+
+#[allow(unsafe_code)]
+// SAFETY: The handler is only used while the lock is held, and it is removed
+// before the handler is detached
+unsafe impl Send for CurrentHandler {}
+
+/// Invokes `f` with the attached handler, if there is one, which can't be
+/// detached until `f` returns
+pub(crate) fn with_current_handler<R>(
+    f: impl FnOnce(*mut breakpad_sys::ExceptionHandler) -> R,
+) -> Option<R> {
+    CURRENT_HANDLER
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .as_ref()
+        .map(|current| f(current.0))
+}
 
 /// Determines which handlers are installed to catch errors. These options are
 /// only used when targetting MacOS/iOS, all other platforms use the only
-/// error handler they support
+/// error handler they support. On Linux/Android, the signals that are hooked
+/// can be chosen with [`MinidumpSettings::with_signal_mask`] instead.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum InstallOptions {
     /// No handlers are registered. This means you won't actually catch any
     /// errors at all.
@@ -45,9 +439,369 @@ pub enum InstallOptions {
     BothHandlers,
 }
 
+/// The mach exception types that breakpad handles on macOS
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MachExceptionType {
+    /// `EXC_BAD_ACCESS`, eg. a segmentation fault
+    BadAccess,
+    /// `EXC_BAD_INSTRUCTION`, an illegal or undefined instruction
+    BadInstruction,
+    /// `EXC_ARITHMETIC`, eg. an integer division by zero
+    Arithmetic,
+    /// `EXC_BREAKPOINT`, which debuggers and profilers use for breakpoints and
+    /// single stepping, but which is also raised by trap instructions
+    Breakpoint,
+}
+
+impl MachExceptionType {
+    /// The `exception_mask_t` of the exception type
+    fn mask(self) -> u32 {
+        match self {
+            Self::BadAccess => 1 << 1,
+            Self::BadInstruction => 1 << 2,
+            Self::Arithmetic => 1 << 3,
+            Self::Breakpoint => 1 << 6,
+        }
+    }
+}
+
+/// The signals that the handler is installed for on Linux/Android, see
+/// [`MinidumpSettings::with_signal_mask`]. By default all of the signals
+/// breakpad handles are hooked, ie. `SIGSEGV`, `SIGABRT`, `SIGFPE`, `SIGILL`,
+/// `SIGBUS` and `SIGTRAP`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SignalMask {
+    /// The signals that aren't hooked, as a mask of `1 << signal`
+    unhooked: u32,
+}
+
+impl SignalMask {
+    /// Hooks all of the signals breakpad handles
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Hooks the signal, or leaves it to the handler that was installed
+    /// before, eg. `SIGTRAP` for debugger tooling, or `SIGABRT` so that
+    /// panics abort normally. Signals breakpad doesn't handle are ignored.
+    pub fn with_signal(mut self, signal: i32, hook: bool) -> Self {
+        if let Some(bit) = Self::bit(signal) {
+            if hook {
+                self.unhooked &= !bit;
+            } else {
+                self.unhooked |= bit;
+            }
+        }
+        self
+    }
+
+    /// Whether the signal is hooked, which is always `false` for signals
+    /// breakpad doesn't handle
+    pub fn hooks(&self, signal: i32) -> bool {
+        Self::bit(signal).is_some_and(|bit| self.unhooked & bit == 0)
+    }
+
+    fn bit(signal: i32) -> Option<u32> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            matches!(
+                signal,
+                libc::SIGSEGV
+                    | libc::SIGABRT
+                    | libc::SIGFPE
+                    | libc::SIGILL
+                    | libc::SIGBUS
+                    | libc::SIGTRAP
+            )
+            .then(|| 1 << signal)
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            let _ = signal;
+            None
+        }
+    }
+}
+
+/// Settings that control the contents of the minidumps written by a
+/// [`BreakpadHandler`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MinidumpSettings {
+    capture_register_memory: bool,
+    respect_non_dumpable: bool,
+    forward_exception_mask: u32,
+    dumper_nice: Option<i32>,
+    size_limit: Option<u64>,
+    sanitize_stacks: bool,
+    redact_process_info: bool,
+    signal_mask: SignalMask,
+    skip_under_debugger: bool,
+}
+
+impl MinidumpSettings {
+    /// Captures a small window of memory around every register of the crashing
+    /// thread that points to readable memory, eg. the object being accessed
+    /// in a use-after-free, which helps a lot with postmortem analysis.
+    ///
+    /// On Linux/Android the memory is registered with breakpad from the signal
+    /// handler, which allocates. On Windows this uses dbghelp's
+    /// `MiniDumpWithIndirectlyReferencedMemory`, which also captures memory
+    /// referenced from the stack. It is not supported on macOS.
+    ///
+    /// Disabled by default.
+    pub fn with_register_memory(mut self, capture: bool) -> Self {
+        self.capture_register_memory = capture;
+        self
+    }
+
+    /// Skips writing a minidump if the process has been made non-dumpable,
+    /// eg. via `PR_SET_DUMPABLE`, or by the kernel because it changed
+    /// credentials, as the memory of such processes is meant to be protected.
+    ///
+    /// To write the minidump, breakpad makes the process dumpable and allows
+    /// the child process that writes the minidump to ptrace it. Both are
+    /// restored to what they were before once the minidump has been written,
+    /// regardless of this setting. This also applies to the minidumps that are
+    /// requested, eg. with [`BreakpadHandler::generate_minidump`]. Only
+    /// applies on Linux/Android.
+    ///
+    /// Disabled by default.
+    pub fn with_respect_non_dumpable(mut self, respect: bool) -> Self {
+        self.respect_non_dumpable = respect;
+        self
+    }
+
+    /// Leaves the exception type to the exception ports that were registered
+    /// before the handler was attached, eg. by a debugger or profiler, rather
+    /// than the handler taking them over and swallowing exceptions they would
+    /// have resumed from.
+    ///
+    /// If the prior ports don't handle an exception, or there weren't any, the
+    /// kernel turns it into a signal, so a minidump is still written for
+    /// crashes with [`InstallOptions::BothHandlers`]. Only applies on macOS,
+    /// with [`InstallOptions::ExceptionHandler`] or
+    /// [`InstallOptions::BothHandlers`].
+    ///
+    /// No exception types are forwarded by default.
+    pub fn with_forwarded_exception(mut self, kind: MachExceptionType, forward: bool) -> Self {
+        if forward {
+            self.forward_exception_mask |= kind.mask();
+        } else {
+            self.forward_exception_mask &= !kind.mask();
+        }
+        self
+    }
+
+    /// Sets the nice value of the crashing thread right before the minidump
+    /// is written, as the child process that breakpad clones to write it
+    /// inherits it, so that it isn't starved of CPU if that is what caused
+    /// the crash. The nice value is restored once the minidump has been
+    /// written.
+    ///
+    /// Lowering the nice value below the current one requires
+    /// `CAP_SYS_NICE` or a high enough `RLIMIT_NICE`, the minidump is written
+    /// with the current one if it's not permitted. Only applies on
+    /// Linux/Android.
+    ///
+    /// The nice value isn't changed by default.
+    pub fn with_dumper_priority(mut self, nice: Option<i32>) -> Self {
+        self.dumper_nice = nice;
+        self
+    }
+
+    /// Limits the size of the minidump in bytes. If the minidump would be
+    /// larger, breakpad only captures the start of the stacks of the threads
+    /// beyond the first 20, so the limit can still be exceeded, eg. if the
+    /// registered app memory alone is larger. Only applies on Linux/Android.
+    ///
+    /// There is no limit by default.
+    pub fn with_size_limit(mut self, size_limit: Option<u64>) -> Self {
+        self.size_limit = size_limit;
+        self
+    }
+
+    /// Replaces everything on the stacks of the threads that doesn't look like
+    /// a pointer into a mapped module with a placeholder, so that secrets on
+    /// the stack aren't included in the minidump, at the cost of local
+    /// variables no longer being available. Only applies on Linux/Android.
+    ///
+    /// Disabled by default.
+    pub fn with_sanitized_stacks(mut self, sanitize: bool) -> Self {
+        self.sanitize_stacks = sanitize;
+        self
+    }
+
+    /// Whether the stacks are sanitized on the current platform, see
+    /// [`MinidumpSettings::with_sanitized_stacks`]
+    pub fn sanitizes_stacks(&self) -> bool {
+        cfg!(any(target_os = "linux", target_os = "android")) && self.sanitize_stacks
+    }
+
+    /// Removes the copies of `/proc/<pid>/cmdline` and `/proc/<pid>/environ`
+    /// from the minidump, as the arguments and environment variables of the
+    /// process may contain secrets. The other streams breakpad copies from
+    /// procfs, ie. `maps`, `status`, `auxv` and `/proc/cpuinfo`, are kept as
+    /// they are needed when symbolication fails. Only applies on
+    /// Linux/Android.
+    ///
+    /// Disabled by default.
+    pub fn with_redacted_process_info(mut self, redact: bool) -> Self {
+        self.redact_process_info = redact;
+        self
+    }
+
+    /// Whether the command line and environment are removed on the current
+    /// platform, see [`MinidumpSettings::with_redacted_process_info`]
+    pub fn redacts_process_info(&self) -> bool {
+        cfg!(any(target_os = "linux", target_os = "android")) && self.redact_process_info
+    }
+
+    /// Chooses the signals the handler is installed for, the handlers that
+    /// were installed before the handler was attached are left in place for
+    /// the rest, so no minidump is written for them. Only applies on
+    /// Linux/Android, where breakpad installs its handler for all of the
+    /// signals in the mask of the first handler that is attached.
+    ///
+    /// All signals are hooked by default.
+    pub fn with_signal_mask(mut self, mask: SignalMask) -> Self {
+        self.signal_mask = mask;
+        self
+    }
+
+    /// Skips writing a minidump if a debugger is attached when the crash
+    /// occurs, so that breakpoints and intentional faults during development
+    /// don't produce minidumps and crash events. The crash is passed on as if
+    /// no handler was installed, so the debugger sees it right away.
+    ///
+    /// A debugger is detected by `TracerPid` in `/proc/self/status` on
+    /// Linux/Android, which is also set by other tools that ptrace the
+    /// process, eg. `strace`, `IsDebuggerPresent` on Windows, and `P_TRACED`
+    /// on macOS.
+    ///
+    /// Disabled by default.
+    pub fn with_skip_under_debugger(mut self, skip: bool) -> Self {
+        self.skip_under_debugger = skip;
+        self
+    }
+}
+
+/// Counters of the crashes seen by a [`BreakpadHandler`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct HandlerStats {
+    /// The number of crashes the handler was notified of
+    pub crashes: u64,
+    /// The number of crashes a minidump was written for
+    pub dumps_written: u64,
+    /// The number of crashes a minidump was not written for because the
+    /// handler was disabled via [`BreakpadHandler::set_enabled`]
+    pub dumps_skipped_disabled: u64,
+    /// The number of crashes a minidump was not written for because the
+    /// [`RateLimit`] was exceeded
+    pub dumps_skipped_rate_limited: u64,
+    /// The number of crashes a minidump was not written for because the
+    /// [`CrashFilter`] rejected them
+    pub dumps_skipped_filtered: u64,
+    /// The number of crashes a minidump was not written for because a
+    /// debugger was attached, see [`MinidumpSettings::with_skip_under_debugger`]
+    pub dumps_skipped_debugger: u64,
+}
+
+/// Limits how many minidumps are written in a window of time, so that a
+/// process that crashes over and over, eg. from threads whose crashes are
+/// handled, doesn't fill up the disk or exhaust the quota
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    /// The maximum number of minidumps written in a window
+    pub max_dumps: u32,
+    /// The length of a window, which starts at the first crash after the
+    /// previous window has elapsed
+    pub window: std::time::Duration,
+}
+
+/// What happens to a crash once its minidump has been written, eg. so that
+/// another crash reporter in the same process, that was installed before the
+/// handler, can still handle it. See [`BreakpadHandler::set_after_dump`].
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AfterDump {
+    /// The signal handlers that were installed before the handler are
+    /// restored and the signal is passed on to them, so later crashes are no
+    /// longer handled
+    RestorePrevious,
+    /// The signal handler that was installed before the handler is called
+    /// directly, after which the process is terminated by the signal if it
+    /// returns
+    CallPrevious,
+    /// The process exits with the code, rather than being terminated by the
+    /// signal
+    Exit(i32),
+}
+
+/// State shared between the handler and the callbacks invoked by breakpad.
+/// Everything that is accessed before the minidump is written must be safe
+/// to use in a signal handler, ie only atomics.
+struct HandlerContext {
+    on_crash: Box<dyn CrashEvent>,
+    /// The file minidumps are written to instead of the crash directory,
+    /// either the one passed to [`BreakpadHandler::attach_to_fd`], or an
+    /// in-memory file if the crash directory is not writable
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    minidump_file: Option<std::os::fd::OwnedFd>,
+    /// Whether `minidump_file` is an in-memory file we created
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    in_memory: bool,
+    /// See [`MinidumpSettings::with_redacted_process_info`]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    redact_process_info: bool,
+    /// Whether [`MinidumpSettings::with_size_limit`] was set
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    size_limited: bool,
+    /// The `fn(i32, u64)` set with [`BreakpadHandler::set_signal_observer`],
+    /// null if there is none
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    signal_observer: atomic::AtomicPtr<()>,
+    /// The crash generation server crashes are sent to, kept open until the
+    /// handler is detached
+    channel: Option<CrashServerChannel>,
+    /// The `cgroup.procs` file of the cgroup the process is moved to before a
+    /// minidump is written
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    dumper_cgroup: std::sync::Mutex<Option<std::os::fd::OwnedFd>>,
+    enabled: atomic::AtomicBool,
+    /// See [`MinidumpSettings::with_skip_under_debugger`]
+    skip_under_debugger: bool,
+    crashes: atomic::AtomicU64,
+    dumps_written: atomic::AtomicU64,
+    dumps_skipped_disabled: atomic::AtomicU64,
+    dumps_skipped_rate_limited: atomic::AtomicU64,
+    dumps_skipped_filtered: atomic::AtomicU64,
+    dumps_skipped_debugger: atomic::AtomicU64,
+    /// Only ever locked with `try_lock` from the filter callback, which is a
+    /// single atomic operation that can't block in a signal handler
+    filter: std::sync::Mutex<Option<Box<dyn CrashFilter>>>,
+    /// Only ever locked with `try_lock` from the crash callback, so a crash
+    /// while it is being replaced doesn't deadlock
+    crash_reporter: std::sync::Mutex<Option<CrashReporter>>,
+    /// The point the rate limit window start is relative to
+    epoch: std::time::Instant,
+    /// The maximum number of dumps per window, 0 if there is no rate limit
+    rate_limit_max: atomic::AtomicU32,
+    rate_limit_window_ms: atomic::AtomicU64,
+    /// When the current window started, in milliseconds since `epoch`
+    window_start_ms: atomic::AtomicU64,
+    window_dumps: atomic::AtomicU32,
+}
+
+/// A file that minidumps are written to instead of the crash directory, which
+/// is only supported on Linux/Android
+#[cfg(any(target_os = "linux", target_os = "android"))]
+type MinidumpFile = std::os::fd::OwnedFd;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+type MinidumpFile = std::convert::Infallible;
+
 pub struct BreakpadHandler {
     handler: *mut breakpad_sys::ExceptionHandler,
-    on_crash: *mut std::ffi::c_void,
+    context: *mut HandlerContext,
 }
 
 #[allow(unsafe_code)]
@@ -58,11 +812,108 @@ unsafe impl Sync for BreakpadHandler {}
 impl BreakpadHandler {
     /// Sets up a breakpad handler to catch exceptions/signals, writing out
     /// a minidump to the designated directory if a crash occurs. Only one
-    /// handler can be attached at a time
+    /// handler can be attached at a time, the location of the caller is
+    /// recorded so that it can be reported if another attach is attempted.
+    ///
+    /// On Linux/Android the handler keeps a few file descriptors open, which
+    /// it closes right before the minidump is written, so that the minidump
+    /// can still be written if the crash is due to the process running out of
+    /// file descriptors.
+    #[track_caller]
     pub fn attach<P: AsRef<std::path::Path>>(
         crash_dir: P,
         install_opts: InstallOptions,
         on_crash: Box<dyn CrashEvent>,
+    ) -> Result<Self, Error> {
+        Self::attach_with_settings(
+            crash_dir,
+            install_opts,
+            MinidumpSettings::default(),
+            on_crash,
+        )
+    }
+
+    /// Same as [`BreakpadHandler::attach`], but with settings that control the
+    /// contents of the minidumps
+    #[track_caller]
+    pub fn attach_with_settings<P: AsRef<std::path::Path>>(
+        crash_dir: P,
+        install_opts: InstallOptions,
+        settings: MinidumpSettings,
+        on_crash: Box<dyn CrashEvent>,
+    ) -> Result<Self, Error> {
+        Self::attach_inner(
+            crash_dir.as_ref(),
+            install_opts,
+            settings,
+            on_crash,
+            None,
+            None,
+        )
+    }
+
+    /// Same as [`BreakpadHandler::attach_with_settings`], but minidumps are
+    /// written to the file instead of a crash directory, eg. a memfd that a
+    /// supervisor passed to a sandboxed process that can't write to disk. The
+    /// path passed to the [`CrashEvent`] is the `/proc/self/fd` path of the
+    /// file. Only supported on Linux/Android.
+    ///
+    /// Breakpad writes the minidump at absolute offsets from the start of the
+    /// file, regardless of its current offset, so every minidump overwrites
+    /// the previous one in the file. The file isn't truncated, so it keeps
+    /// the end of a previous minidump that was larger. The descriptor is
+    /// never closed by breakpad, it is closed when the handler is dropped.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[track_caller]
+    pub fn attach_to_fd(
+        fd: std::os::fd::OwnedFd,
+        install_opts: InstallOptions,
+        settings: MinidumpSettings,
+        on_crash: Box<dyn CrashEvent>,
+    ) -> Result<Self, Error> {
+        Self::attach_inner(
+            std::path::Path::new(""),
+            install_opts,
+            settings,
+            on_crash,
+            None,
+            Some(fd),
+        )
+    }
+
+    /// Same as [`BreakpadHandler::attach`], but crashes are sent to a
+    /// [`CrashGenerationServer`], usually running in another process, which
+    /// writes the minidump instead of the crashing process.
+    ///
+    /// `on_crash` is only invoked for minidumps that are written in process,
+    /// which happens on Windows if the server could not be reached when the
+    /// handler was attached, see [`BreakpadHandler::is_out_of_process`]. The
+    /// [`HandlerStats::dumps_written`] likewise only counts those.
+    #[track_caller]
+    pub fn attach_out_of_process<P: AsRef<std::path::Path>>(
+        crash_dir: P,
+        channel: CrashServerChannel,
+        install_opts: InstallOptions,
+        on_crash: Box<dyn CrashEvent>,
+    ) -> Result<Self, Error> {
+        Self::attach_inner(
+            crash_dir.as_ref(),
+            install_opts,
+            MinidumpSettings::default(),
+            on_crash,
+            Some(channel),
+            None,
+        )
+    }
+
+    #[track_caller]
+    fn attach_inner(
+        crash_dir: &std::path::Path,
+        install_opts: InstallOptions,
+        settings: MinidumpSettings,
+        on_crash: Box<dyn CrashEvent>,
+        channel: Option<CrashServerChannel>,
+        minidump_file: Option<MinidumpFile>,
     ) -> Result<Self, Error> {
         match HANDLER_ATTACHED.compare_exchange(
             false,
@@ -70,53 +921,229 @@ impl BreakpadHandler {
             atomic::Ordering::Relaxed,
             atomic::Ordering::Relaxed,
         ) {
-            Ok(true) | Err(true) => return Err(Error::HandlerAlreadyRegistered),
+            Ok(true) | Err(true) => {
+                let owner = HANDLER_OWNER
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .clone();
+                return Err(Error::HandlerAlreadyRegistered { owner });
+            }
             _ => {}
         }
 
-        let on_crash = Box::into_raw(Box::new(on_crash)).cast();
+        *HANDLER_OWNER
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(HandlerOwner {
+            tag: std::panic::Location::caller().to_string(),
+            registered_at: std::time::SystemTime::now(),
+        });
+
+        // There's no need for an in-memory fallback if the server writes the
+        // minidumps, or we've been given a file to write them to
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let (minidump_file, in_memory) = match minidump_file {
+            Some(file) => (Some(file), false),
+            None if channel.is_some() || is_writable(crash_dir) => (None, false),
+            None => {
+                let memfd = create_memfd();
+                let in_memory = memfd.is_some();
+                (memfd, in_memory)
+            }
+        };
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let _ = minidump_file;
+
+        let context = Box::into_raw(Box::new(HandlerContext {
+            on_crash,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            minidump_file,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            in_memory,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            redact_process_info: settings.redact_process_info,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            size_limited: settings.size_limit.is_some_and(|limit| limit > 0),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            signal_observer: atomic::AtomicPtr::new(std::ptr::null_mut()),
+            channel,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            dumper_cgroup: std::sync::Mutex::new(None),
+            enabled: atomic::AtomicBool::new(true),
+            skip_under_debugger: settings.skip_under_debugger,
+            crashes: atomic::AtomicU64::new(0),
+            dumps_written: atomic::AtomicU64::new(0),
+            dumps_skipped_disabled: atomic::AtomicU64::new(0),
+            dumps_skipped_rate_limited: atomic::AtomicU64::new(0),
+            dumps_skipped_filtered: atomic::AtomicU64::new(0),
+            dumps_skipped_debugger: atomic::AtomicU64::new(0),
+            filter: std::sync::Mutex::new(None),
+            crash_reporter: std::sync::Mutex::new(None),
+            epoch: std::time::Instant::now(),
+            rate_limit_max: atomic::AtomicU32::new(0),
+            rate_limit_window_ms: atomic::AtomicU64::new(0),
+            window_start_ms: atomic::AtomicU64::new(0),
+            window_dumps: atomic::AtomicU32::new(0),
+        }));
 
         #[allow(unsafe_code)]
         // SAFETY: Calling into C code :shrug:
         unsafe {
-            let os_str = crash_dir.as_ref().as_os_str();
-
-            let path: Vec<breakpad_sys::PathChar> = {
-                #[cfg(windows)]
-                {
-                    use std::os::windows::ffi::OsStrExt;
-                    os_str.encode_wide().collect()
-                }
-                #[cfg(unix)]
-                {
-                    use std::os::unix::ffi::OsStrExt;
-                    Vec::from(os_str.as_bytes())
-                }
-            };
+            let path = path_chars(crash_dir.as_os_str());
 
             extern "C" fn crash_callback(
                 path: *const breakpad_sys::PathChar,
                 path_len: usize,
+                succeeded: bool,
+                crash_info: *const breakpad_sys::CrashInfo,
                 ctx: *mut std::ffi::c_void,
             ) {
-                let path_slice = unsafe { std::slice::from_raw_parts(path, path_len) };
+                let path = path_from_chars(unsafe { std::slice::from_raw_parts(path, path_len) });
 
-                let path = {
-                    #[cfg(windows)]
-                    {
-                        use std::os::windows::ffi::OsStringExt;
-                        std::path::PathBuf::from(std::ffi::OsString::from_wide(path_slice))
+                let context: &HandlerContext = unsafe { &*ctx.cast() };
+
+                // Breakpad doesn't know the path of a file it was given a
+                // descriptor for, but it can still be opened via procfs
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                let path = match &context.minidump_file {
+                    Some(file) if path.as_os_str().is_empty() => {
+                        use std::os::fd::AsRawFd;
+                        std::path::PathBuf::from(format!("/proc/self/fd/{}", file.as_raw_fd()))
                     }
-                    #[cfg(unix)]
+                    _ => path,
+                };
+
+                // Breakpad reports success as long as the dumper ran, so an
+                // empty or missing file is also a failure
+                let size = std::fs::metadata(&path).map_or(0, |md| md.len());
+                let written = succeeded && size > 0;
+
+                // The streams are removed before anyone gets to see the
+                // minidump, if that fails it is still reported as written,
+                // but flagged so that it can be handled with care
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                let redaction_failed = written
+                    && context.redact_process_info
+                    && redact::redact_process_info(&path).is_err();
+                #[cfg(not(any(target_os = "linux", target_os = "android")))]
+                let redaction_failed = false;
+
+                // Breakpad only ever truncates stacks if there is a size limit
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                let truncated = written
+                    && context.size_limited
+                    && truncated::is_truncated(&path).unwrap_or_default();
+                #[cfg(not(any(target_os = "linux", target_os = "android")))]
+                let truncated = false;
+
+                let info = CrashInfo {
+                    hang: hang::is_pending(),
+                    requested: DUMP_REQUESTED.load(atomic::Ordering::Acquire),
+                    truncated,
+                    redaction_failed,
+                    ..unsafe { crash_info.as_ref() }
+                        .map(CrashInfo::from)
+                        .unwrap_or_default()
+                };
+
+                let result = if written {
+                    context
+                        .dumps_written
+                        .fetch_add(1, atomic::Ordering::Relaxed);
+                    Ok(MinidumpInfo {
+                        minidump_path: path.clone(),
+                        size,
+                    })
+                } else {
+                    Err(DumpError::WriteFailed {
+                        minidump_path: path.clone(),
+                    })
+                };
+                context.on_crash.on_crash_with_result(result, &info);
+
+                // The reporter is only launched for minidumps in the crash
+                // directory, as the path of a descriptor is only valid in
+                // the crashing process
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                let written = written && context.minidump_file.is_none();
+                if written {
+                    if let Ok(reporter) = context.crash_reporter.try_lock() {
+                        if let Some(reporter) = reporter.as_ref() {
+                            reporter.launch(&path);
+                        }
+                    }
+                }
+            }
+
+            extern "C" fn filter_callback(
+                crash_info: *const breakpad_sys::CrashInfo,
+                ctx: *mut std::ffi::c_void,
+            ) -> bool {
+                let context: &HandlerContext = unsafe { &*ctx.cast() };
+                context.crashes.fetch_add(1, atomic::Ordering::Relaxed);
+
+                if !context.enabled.load(atomic::Ordering::Relaxed) {
+                    context
+                        .dumps_skipped_disabled
+                        .fetch_add(1, atomic::Ordering::Relaxed);
+                    return false;
+                }
+
+                // Checked before the filter, so that breakpoints hit during
+                // development aren't counted against the rate limit either
+                if context.skip_under_debugger && unsafe { breakpad_sys::is_debugger_attached() } {
+                    context
+                        .dumps_skipped_debugger
+                        .fetch_add(1, atomic::Ordering::Relaxed);
+                    return false;
+                }
+
+                // If the filter is being replaced while we crash, the dump is
+                // written rather than risking losing it
+                if let Ok(filter) = context.filter.try_lock() {
+                    if let Some(filter) = filter.as_ref() {
+                        let info = CrashInfo {
+                            hang: hang::is_pending(),
+                            requested: DUMP_REQUESTED.load(atomic::Ordering::Acquire),
+                            ..unsafe { crash_info.as_ref() }
+                                .map(CrashInfo::from)
+                                .unwrap_or_default()
+                        };
+
+                        if !filter.should_dump(&info) {
+                            context
+                                .dumps_skipped_filtered
+                                .fetch_add(1, atomic::Ordering::Relaxed);
+                            return false;
+                        }
+                    }
+                }
+
+                // Instant uses clock_gettime, which is async signal safe
+                let max_dumps = context.rate_limit_max.load(atomic::Ordering::Relaxed);
+                if max_dumps != 0 {
+                    let now = context.epoch.elapsed().as_millis() as u64;
+                    let window = context.rate_limit_window_ms.load(atomic::Ordering::Relaxed);
+
+                    if context.window_dumps.load(atomic::Ordering::Relaxed) == 0
+                        || now
+                            .saturating_sub(context.window_start_ms.load(atomic::Ordering::Relaxed))
+                            >= window
                     {
-                        use std::os::unix::ffi::OsStrExt;
-                        std::path::PathBuf::from(std::ffi::OsStr::from_bytes(path_slice).to_owned())
+                        context
+                            .window_start_ms
+                            .store(now, atomic::Ordering::Relaxed);
+                        context.window_dumps.store(0, atomic::Ordering::Relaxed);
                     }
-                };
 
-                let context: Box<Box<dyn CrashEvent>> = unsafe { Box::from_raw(ctx.cast()) };
-                context.on_crash(path);
-                Box::leak(context);
+                    if context.window_dumps.fetch_add(1, atomic::Ordering::Relaxed) >= max_dumps {
+                        context
+                            .dumps_skipped_rate_limited
+                            .fetch_add(1, atomic::Ordering::Relaxed);
+                        return false;
+                    }
+                }
+
+                true
             }
 
             let install_opts = match install_opts {
@@ -126,26 +1153,566 @@ impl BreakpadHandler {
                 InstallOptions::BothHandlers => breakpad_sys::INSTALL_BOTH_HANDLERS,
             };
 
-            let handler = breakpad_sys::attach_exception_handler(
-                path.as_ptr(),
-                path.len(),
-                crash_callback,
-                on_crash,
-                install_opts,
+            // Breakpad seeks to every offset it writes at and never closes
+            // the descriptor, so it can use ours directly, which stays open
+            // until the handler has been detached
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            let minidump_fd = match &(*context).minidump_file {
+                Some(file) => {
+                    use std::os::fd::AsRawFd;
+                    file.as_raw_fd()
+                }
+                None => -1,
+            };
+            #[cfg(not(any(target_os = "linux", target_os = "android")))]
+            let minidump_fd = -1;
+
+            let settings = breakpad_sys::MinidumpSettings {
+                capture_register_memory: settings.capture_register_memory,
+                respect_non_dumpable: settings.respect_non_dumpable,
+                forward_exception_mask: settings.forward_exception_mask,
+                set_dumper_nice: settings.dumper_nice.is_some(),
+                dumper_nice: settings.dumper_nice.unwrap_or_default(),
+                size_limit: settings.size_limit.unwrap_or_default(),
+                sanitize_stacks: settings.sanitize_stacks,
+                unhooked_signals: settings.signal_mask.unhooked,
+            };
+
+            let attach = |channel: *const breakpad_sys::CrashServerChannel| {
+                breakpad_sys::attach_exception_handler(
+                    path.as_ptr(),
+                    path.len(),
+                    crash_callback,
+                    Some(filter_callback),
+                    context.cast(),
+                    install_opts,
+                    minidump_fd,
+                    &settings,
+                    channel,
+                )
+            };
+
+            let handler = match &(*context).channel {
+                Some(channel) => channel.with_sys(|channel| attach(channel)),
+                None => attach(std::ptr::null()),
+            };
+
+            *CURRENT_HANDLER
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(CurrentHandler(handler));
+
+            Ok(Self { handler, context })
+        }
+    }
+
+    #[inline]
+    fn context(&self) -> &HandlerContext {
+        #[allow(unsafe_code)]
+        // SAFETY: The context lives until the handler is dropped
+        unsafe {
+            &*self.context
+        }
+    }
+
+    /// Enables or disables writing of minidumps, without detaching the
+    /// handler. While disabled, crashes are passed on as if no handler was
+    /// attached, but are still counted in the [`HandlerStats`].
+    pub fn set_enabled(&self, enabled: bool) {
+        self.context()
+            .enabled
+            .store(enabled, atomic::Ordering::Relaxed);
+    }
+
+    /// Sets the filter that decides if a minidump is written for a crash,
+    /// `None` removes it. Crashes that are rejected are passed on as if no
+    /// handler was attached, but are still counted in the [`HandlerStats`].
+    pub fn set_filter(&self, filter: Option<Box<dyn CrashFilter>>) {
+        *self
+            .context()
+            .filter
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = filter;
+    }
+
+    /// Sets the program that is launched once a minidump has been written and
+    /// the [`CrashEvent`] has handled the crash, eg. a crash dialog, `None`
+    /// removes it. The program isn't launched if the minidump couldn't be
+    /// written, or was written to a descriptor rather than the crash
+    /// directory.
+    pub fn set_crash_reporter(&self, reporter: Option<CrashReporter>) {
+        *self
+            .context()
+            .crash_reporter
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = reporter;
+    }
+
+    /// Limits how many minidumps are written in a window of time, crashes
+    /// beyond the limit are passed on as if no handler was attached, but are
+    /// still counted in the [`HandlerStats`]. `None` removes the limit, which
+    /// is the default.
+    pub fn set_rate_limit(&self, limit: Option<RateLimit>) {
+        let context = self.context();
+        let (max_dumps, window) = limit.map_or((0, 0), |limit| {
+            (limit.max_dumps, limit.window.as_millis() as u64)
+        });
+
+        context
+            .rate_limit_window_ms
+            .store(window, atomic::Ordering::Relaxed);
+        context
+            .rate_limit_max
+            .store(max_dumps, atomic::Ordering::Relaxed);
+    }
+
+    /// Includes a region of memory in every minidump that is written, eg. the
+    /// state of an allocator or the headers of a lock-free queue, which helps
+    /// with postmortem debugging. The memory is only read when a minidump is
+    /// written, and regions that are no longer mapped by then are skipped.
+    ///
+    /// Returns `false` on macOS, where this is not supported. Regions should
+    /// be registered up front rather than while other threads might crash, as
+    /// breakpad doesn't synchronize access to them.
+    pub fn register_app_memory(&self, ptr: *const u8, len: usize) -> bool {
+        #[allow(unsafe_code)]
+        // SAFETY: The handler lives until we are dropped, and breakpad never
+        // dereferences the pointer itself
+        unsafe {
+            breakpad_sys::register_app_memory(self.handler, ptr.cast(), len)
+        }
+    }
+
+    /// Stops including the region of memory starting at `ptr`, that was
+    /// registered with [`BreakpadHandler::register_app_memory`], in minidumps
+    pub fn unregister_app_memory(&self, ptr: *const u8) {
+        #[allow(unsafe_code)]
+        // SAFETY: The handler lives until we are dropped
+        unsafe {
+            breakpad_sys::unregister_app_memory(self.handler, ptr.cast());
+        }
+    }
+
+    /// Adds a module to the module list of every minidump that is written, for
+    /// code that doesn't show up in `/proc/self/maps` with a file name, eg.
+    /// because it was mapped by a custom loader or a JIT, so that its frames
+    /// can still be symbolicated.
+    ///
+    /// The `identifier` is the debug identifier the symbols were uploaded
+    /// with, ie. the first 16 bytes of the GNU build id. The name is what the
+    /// module is reported as, usually the path of the file it was loaded from.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn add_mapping_info(
+        &self,
+        name: &str,
+        identifier: &[u8; 16],
+        start_address: usize,
+        mapping_size: usize,
+        file_offset: usize,
+    ) {
+        #[allow(unsafe_code)]
+        // SAFETY: The handler lives until we are dropped, and breakpad copies
+        // the name and identifier
+        unsafe {
+            breakpad_sys::add_mapping_info(
+                self.handler,
+                name.as_ptr().cast(),
+                name.len(),
+                identifier,
+                start_address,
+                mapping_size,
+                file_offset,
+            );
+        }
+    }
+
+    /// Writes a minidump on demand of the current state of the process,
+    /// without an exception. The process keeps running.
+    ///
+    /// The [`CrashEvent`] is notified the same as for an actual crash.
+    pub fn generate_minidump(&self) -> Result<(), Error> {
+        #[allow(unsafe_code)]
+        // SAFETY: The handler lives until we are dropped
+        let written = requested_dump(|| unsafe { breakpad_sys::generate_minidump(self.handler) });
+
+        if written {
+            Ok(())
+        } else {
+            Err(Error::MinidumpWriteFailed)
+        }
+    }
+
+    /// Writes a minidump on demand with a synthetic exception, eg. for a
+    /// watchdog timeout or a failed assertion that shouldn't abort the
+    /// process, so that it appears in Sentry with a meaningful exception
+    /// rather than as a generic dump request. The process keeps running.
+    ///
+    /// On Linux/Android the minidump is written as if [`CrashInfo::signal`]
+    /// had been delivered, defaulting to `SIGABRT`, with [`CrashInfo::code`]
+    /// as the `si_code` and [`CrashInfo::address`] as the faulting address. On
+    /// Windows the code and address are the exception code and address. This
+    /// is not supported on macOS.
+    ///
+    /// The [`CrashEvent`] is notified the same as for an actual crash.
+    pub fn generate_minidump_with_exception(&self, info: &CrashInfo) -> Result<(), Error> {
+        #[allow(unsafe_code)]
+        // SAFETY: The handler lives until we are dropped
+        let written = requested_dump(|| unsafe {
+            breakpad_sys::generate_minidump_with_exception(
+                self.handler,
+                info.signal.unwrap_or_default(),
+                info.code.unwrap_or_default(),
+                info.address.unwrap_or_default(),
+            )
+        });
+
+        if written {
+            Ok(())
+        } else {
+            Err(Error::MinidumpWriteFailed)
+        }
+    }
+
+    /// Moves the process to the cgroup at the path, eg. one with a higher
+    /// `cpu.weight` that is reserved for crash handling, right before a
+    /// minidump is written, as the child process that breakpad clones to
+    /// write it inherits it. The process isn't moved back. `None` leaves the
+    /// process in its cgroup, which is the default.
+    ///
+    /// The `cgroup.procs` file of the cgroup is opened right away, as that
+    /// can't be done in the crashing process, but the process must still be
+    /// allowed to move to the cgroup when it crashes for it to have any
+    /// effect. Only available on Linux/Android.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_dumper_cgroup(&self, cgroup: Option<&std::path::Path>) -> std::io::Result<()> {
+        use std::os::fd::AsRawFd;
+
+        let procs = match cgroup {
+            Some(cgroup) => Some(std::os::fd::OwnedFd::from(
+                std::fs::OpenOptions::new()
+                    .write(true)
+                    .open(cgroup.join("cgroup.procs"))?,
+            )),
+            None => None,
+        };
+
+        let mut dumper_cgroup = self
+            .context()
+            .dumper_cgroup
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        #[allow(unsafe_code)]
+        // SAFETY: Calling into C code, the descriptor is kept open in the
+        // context until it is replaced or the handler is dropped
+        unsafe {
+            breakpad_sys::set_dumper_cgroup(
+                self.handler,
+                procs.as_ref().map_or(-1, |procs| procs.as_raw_fd()),
             );
+        }
+        // The previous descriptor is only closed once breakpad no longer uses it
+        *dumper_cgroup = procs;
+
+        Ok(())
+    }
+
+    /// Sets a function that is called with the signal and the faulting address
+    /// first thing when the process crashes, before the [`CrashFilter`] or
+    /// any of the handler's own state is consulted, and before the minidump
+    /// is written, eg. to flip a byte in a memory mapped file so a launcher
+    /// can tell the process crashed even if writing the minidump fails. It is
+    /// also called for crashes no minidump is written for, eg. because the
+    /// handler is disabled, rate limited, or the process is non-dumpable.
+    /// `None` removes it, which is the default.
+    ///
+    /// The function runs in the signal handler, so it must be async signal
+    /// safe, ie. it must not allocate, lock, or panic. Only available on
+    /// Linux/Android.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_signal_observer(&self, observer: Option<fn(signal: i32, address: u64)>) {
+        extern "C" fn observe_signal(signal: i32, address: u64, ctx: *mut std::ffi::c_void) {
+            #[allow(unsafe_code)]
+            // SAFETY: The context is the one the handler was attached with,
+            // and the observer is only ever set to a `fn(i32, u64)`
+            unsafe {
+                let context: &HandlerContext = &*ctx.cast();
+                let observer = context.signal_observer.load(atomic::Ordering::Acquire);
+                if !observer.is_null() {
+                    let observer: fn(i32, u64) = std::mem::transmute(observer);
+                    observer(signal, address);
+                }
+            }
+        }
+
+        self.context().signal_observer.store(
+            observer.map_or(std::ptr::null_mut(), |observer| observer as *mut ()),
+            atomic::Ordering::Release,
+        );
+
+        #[allow(unsafe_code)]
+        // SAFETY: Calling into C code, the handler and the context the
+        // observer is passed live until we are dropped
+        unsafe {
+            breakpad_sys::set_signal_observer(
+                self.handler,
+                observer.map(|_| observe_signal as breakpad_sys::SignalObserver),
+            );
+        }
+    }
+
+    /// Sets what happens to a crash once its minidump has been written and the
+    /// [`CrashEvent`] has handled it. `None` restores breakpad's behavior,
+    /// which is the default, where the process is terminated by the signal if
+    /// the minidump was written, and the signal is passed on to the previous
+    /// handlers otherwise. Minidumps written with
+    /// [`BreakpadHandler::generate_minidump`] or
+    /// [`BreakpadHandler::generate_minidump_with_exception`] aren't affected.
+    /// Only available on Linux/Android.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_after_dump(&self, after_dump: Option<AfterDump>) {
+        let (after_dump, exit_code) = match after_dump {
+            None => (breakpad_sys::AFTER_DUMP_DEFAULT, 0),
+            Some(AfterDump::RestorePrevious) => (breakpad_sys::AFTER_DUMP_RESTORE_PREVIOUS, 0),
+            Some(AfterDump::CallPrevious) => (breakpad_sys::AFTER_DUMP_CALL_PREVIOUS, 0),
+            Some(AfterDump::Exit(code)) => (breakpad_sys::AFTER_DUMP_EXIT, code),
+        };
+
+        #[allow(unsafe_code)]
+        // SAFETY: Calling into C code, the handler lives until we are dropped
+        unsafe {
+            breakpad_sys::set_after_dump(self.handler, after_dump, exit_code);
+        }
+    }
+
+    /// Resets the state of the handler in the child of a `fork`, which inherits
+    /// the handler, so that its crashes aren't attributed to the parent. The
+    /// [`HandlerStats`] and the [`RateLimit`] window start over, and if
+    /// minidumps are written to an in-memory file the child gets its own,
+    /// rather than sharing it with the parent.
+    ///
+    /// Must be called in the child before it spawns any threads.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn reinit_after_fork(&mut self) {
+        #[allow(unsafe_code)]
+        // SAFETY: We have exclusive access to the context, and it lives until
+        // the handler is dropped
+        let context = unsafe { &mut *self.context };
+
+        for counter in [
+            &context.crashes,
+            &context.dumps_written,
+            &context.dumps_skipped_disabled,
+            &context.dumps_skipped_rate_limited,
+            &context.dumps_skipped_filtered,
+            &context.dumps_skipped_debugger,
+            &context.window_start_ms,
+        ] {
+            counter.store(0, atomic::Ordering::Relaxed);
+        }
+        context.window_dumps.store(0, atomic::Ordering::Relaxed);
+
+        if context.in_memory {
+            if let Some(memfd) = create_memfd() {
+                use std::os::fd::AsRawFd;
+
+                #[allow(unsafe_code)]
+                // SAFETY: Calling into C code, the file stays open until it is
+                // replaced again or the handler is dropped
+                unsafe {
+                    breakpad_sys::set_minidump_fd(self.handler, memfd.as_raw_fd());
+                }
+                // Closes the file shared with the parent, now that breakpad
+                // no longer writes to it
+                context.minidump_file = Some(memfd);
+            }
+        }
+    }
+
+    /// Whether minidumps are written to memory rather than the crash directory,
+    /// which happens on Linux/Android if the crash directory isn't writable,
+    /// eg. because it is on a read-only filesystem. In that case the path
+    /// passed to the [`CrashEvent`] is only valid until the process exits.
+    pub fn is_in_memory(&self) -> bool {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            self.context().in_memory
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            false
+        }
+    }
 
-            Ok(Self { handler, on_crash })
+    /// Whether crashes are sent to a [`CrashGenerationServer`] rather than
+    /// the minidump being written in process
+    pub fn is_out_of_process(&self) -> bool {
+        #[allow(unsafe_code)]
+        // SAFETY: The handler lives until we are dropped
+        unsafe {
+            breakpad_sys::is_out_of_process(self.handler)
+        }
+    }
+
+    /// Whether minidumps are currently written when a crash occurs
+    pub fn is_enabled(&self) -> bool {
+        self.context().enabled.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Gets a snapshot of the counters for the crashes seen by this handler
+    pub fn stats(&self) -> HandlerStats {
+        let context = self.context();
+        HandlerStats {
+            crashes: context.crashes.load(atomic::Ordering::Relaxed),
+            dumps_written: context.dumps_written.load(atomic::Ordering::Relaxed),
+            dumps_skipped_disabled: context
+                .dumps_skipped_disabled
+                .load(atomic::Ordering::Relaxed),
+            dumps_skipped_rate_limited: context
+                .dumps_skipped_rate_limited
+                .load(atomic::Ordering::Relaxed),
+            dumps_skipped_filtered: context
+                .dumps_skipped_filtered
+                .load(atomic::Ordering::Relaxed),
+            dumps_skipped_debugger: context
+                .dumps_skipped_debugger
+                .load(atomic::Ordering::Relaxed),
         }
     }
 }
 
+/// Converts a path to the representation breakpad expects
+pub(crate) fn path_chars(os_str: &std::ffi::OsStr) -> Vec<breakpad_sys::PathChar> {
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+        os_str.encode_wide().collect()
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Vec::from(os_str.as_bytes())
+    }
+}
+
+/// Converts a path from the representation breakpad uses
+pub(crate) fn path_from_chars(path: &[breakpad_sys::PathChar]) -> std::path::PathBuf {
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStringExt;
+        std::path::PathBuf::from(std::ffi::OsString::from_wide(path))
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        std::path::PathBuf::from(std::ffi::OsStr::from_bytes(path).to_owned())
+    }
+}
+
+/// Checks if files can be created in the directory, ie. that it is not on a
+/// read-only filesystem and that we have permission to write to it
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn is_writable(dir: &std::path::Path) -> bool {
+    let probe = dir.join(format!(".breakpad-probe-{}", std::process::id()));
+
+    match std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+    {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            true
+        }
+        Err(e) => !matches!(
+            e.kind(),
+            std::io::ErrorKind::ReadOnlyFilesystem | std::io::ErrorKind::PermissionDenied
+        ),
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn create_memfd() -> Option<std::os::fd::OwnedFd> {
+    use std::os::fd::FromRawFd;
+
+    #[allow(unsafe_code)]
+    // SAFETY: syscall, the name is a valid nul terminated string
+    unsafe {
+        let fd = libc::memfd_create(c"breakpad-minidump".as_ptr(), libc::MFD_CLOEXEC);
+
+        (fd >= 0).then(|| std::os::fd::OwnedFd::from_raw_fd(fd))
+    }
+}
+
+/// Same as [`BreakpadHandler::generate_minidump`], but with the handler that
+/// is currently attached, for code that has no way to refer to it, eg. a
+/// panic hook
+pub fn generate_current_minidump() -> Result<(), Error> {
+    let written = with_current_handler(|handler| {
+        #[allow(unsafe_code)]
+        // SAFETY: The handler can't be detached while we hold it
+        requested_dump(|| unsafe { breakpad_sys::generate_minidump(handler) })
+    })
+    .ok_or(Error::NotAttached)?;
+
+    if written {
+        Ok(())
+    } else {
+        Err(Error::MinidumpWriteFailed)
+    }
+}
+
+/// Writes a minidump from a core dump of a crashed process, eg. one collected
+/// via the kernel's `core_pattern`. `proc_snapshot` is a directory with copies
+/// of the `auxv`, `cmdline`, `environ`, `maps`, and `status` files from
+/// `/proc/<pid>` of the process, taken before it exited.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn write_minidump_from_core(
+    core_path: &std::path::Path,
+    proc_snapshot: &std::path::Path,
+    minidump_path: &std::path::Path,
+) -> Result<(), Error> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let to_cstr = |path: &std::path::Path| {
+        std::ffi::CString::new(path.as_os_str().as_bytes())
+            .map_err(|_e| Error::InvalidPath(path.to_owned()))
+    };
+
+    let core_path = to_cstr(core_path)?;
+    let proc_snapshot = to_cstr(proc_snapshot)?;
+    let minidump_path = to_cstr(minidump_path)?;
+
+    #[allow(unsafe_code)]
+    // SAFETY: Calling into C code, all of the paths are valid nul terminated strings
+    let written = unsafe {
+        breakpad_sys::write_minidump_from_core(
+            core_path.as_ptr(),
+            proc_snapshot.as_ptr(),
+            minidump_path.as_ptr(),
+        )
+    };
+
+    if written {
+        Ok(())
+    } else {
+        Err(Error::MinidumpWriteFailed)
+    }
+}
+
 impl Drop for BreakpadHandler {
     fn drop(&mut self) {
         #[allow(unsafe_code)]
         // SAFETY: Calling into C code
         unsafe {
+            *CURRENT_HANDLER
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner) = None;
+
             breakpad_sys::detach_exception_handler(self.handler);
-            let _: Box<Box<dyn CrashEvent>> = Box::from_raw(self.on_crash.cast());
+            let _: Box<HandlerContext> = Box::from_raw(self.context);
+            *HANDLER_OWNER
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner) = None;
             HANDLER_ATTACHED.swap(false, atomic::Ordering::Relaxed);
         }
     }
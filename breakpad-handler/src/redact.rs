@@ -0,0 +1,84 @@
+//! Removal of the streams that breakpad copies from procfs which may contain
+//! secrets, see [`MinidumpSettings::with_redacted_process_info`](crate::MinidumpSettings::with_redacted_process_info)
+
+use std::os::unix::fs::FileExt;
+
+const MINIDUMP_SIGNATURE: u32 = 0x504d_444d;
+/// The size of a `MINIDUMP_DIRECTORY` entry, ie. the stream type, and the size
+/// and RVA of the stream
+const DIRECTORY_ENTRY_SIZE: u64 = 12;
+const UNUSED_STREAM: u32 = 0;
+const LINUX_CMD_LINE: u32 = 0x4767_0006;
+const LINUX_ENVIRON: u32 = 0x4767_0007;
+/// Breakpad writes about 20 streams, so a minidump claiming more than this is
+/// corrupt, and isn't walked any further in the crashing process
+const MAX_STREAMS: u32 = 256;
+/// The combined size of the arguments and environment of a process is limited
+/// by the kernel to a few MiB, so larger streams are corrupt
+const MAX_STREAM_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Zeroes the contents of the `/proc/<pid>/cmdline` and `/proc/<pid>/environ`
+/// streams of the minidump, and marks them as unused so that readers skip
+/// them. This is done in place with a fixed size buffer and a bounded amount
+/// of I/O, as it runs in the crashing process.
+pub(crate) fn redact_process_info(path: &std::path::Path) -> std::io::Result<()> {
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)?;
+
+    let mut header = [0u8; 16];
+    file.read_exact_at(&mut header, 0)?;
+    let u32_at = |bytes: &[u8], offset: usize| {
+        u32::from_le_bytes([
+            bytes[offset],
+            bytes[offset + 1],
+            bytes[offset + 2],
+            bytes[offset + 3],
+        ])
+    };
+    if u32_at(&header, 0) != MINIDUMP_SIGNATURE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "not a minidump",
+        ));
+    }
+
+    let stream_count = u32_at(&header, 8);
+    let directory = u64::from(u32_at(&header, 12));
+    if stream_count > MAX_STREAMS {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "too many streams",
+        ));
+    }
+
+    let zeroes = [0u8; 4096];
+    for i in 0..u64::from(stream_count) {
+        let offset = directory + i * DIRECTORY_ENTRY_SIZE;
+        let mut entry = [0u8; DIRECTORY_ENTRY_SIZE as usize];
+        file.read_exact_at(&mut entry, offset)?;
+        if !matches!(u32_at(&entry, 0), LINUX_CMD_LINE | LINUX_ENVIRON) {
+            continue;
+        }
+
+        let size = u64::from(u32_at(&entry, 4));
+        let rva = u64::from(u32_at(&entry, 8));
+        if size > MAX_STREAM_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "stream is too large",
+            ));
+        }
+        let mut written = 0;
+        while written < size {
+            let len = (size - written).min(zeroes.len() as u64) as usize;
+            file.write_all_at(&zeroes[..len], rva + written)?;
+            written += len as u64;
+        }
+
+        file.write_all_at(&UNUSED_STREAM.to_le_bytes(), offset)?;
+    }
+
+    Ok(())
+}
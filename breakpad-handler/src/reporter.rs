@@ -0,0 +1,155 @@
+//! Launching of an external program once a minidump has been written, eg. a
+//! crash dialog that lets the user send feedback along with the crash
+
+use crate::Error;
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+/// An external program that is launched once a minidump has been written and
+/// the [`CrashEvent`](crate::CrashEvent) has handled the crash, see
+/// [`BreakpadHandler::set_crash_reporter`](crate::BreakpadHandler::set_crash_reporter)
+///
+/// The program is run with its arguments, followed by the path of the
+/// minidump and the path of its metadata, ie. the minidump path with a
+/// `.metadata` extension, which is where `sentry-contrib-breakpad` writes the
+/// crash event. The metadata file doesn't exist if the crash is sent
+/// immediately instead.
+#[derive(Debug)]
+pub struct CrashReporter {
+    program: PathBuf,
+    args: Vec<OsString>,
+    /// The program and its arguments, prepared ahead of time as they can't
+    /// safely be converted in the crashing process
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    argv: Vec<std::ffi::CString>,
+}
+
+impl CrashReporter {
+    /// Creates the reporter for the program, which is looked up in `PATH` if
+    /// it isn't a path. Fails if the program or one of its arguments contains
+    /// a nul byte.
+    pub fn new<I, A>(program: impl Into<PathBuf>, args: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = A>,
+        A: Into<OsString>,
+    {
+        let program = program.into();
+        let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let argv = {
+            use std::os::unix::ffi::OsStrExt;
+
+            std::iter::once(program.as_os_str())
+                .chain(args.iter().map(OsString::as_os_str))
+                .map(|arg| {
+                    std::ffi::CString::new(arg.as_bytes())
+                        .map_err(|_e| Error::InvalidPath(PathBuf::from(arg)))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        if let Some(arg) = std::iter::once(program.as_os_str())
+            .chain(args.iter().map(OsString::as_os_str))
+            .find(|arg| arg.to_string_lossy().contains('\0'))
+        {
+            return Err(Error::InvalidPath(PathBuf::from(arg)));
+        }
+
+        Ok(Self {
+            program,
+            args,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            argv,
+        })
+    }
+
+    /// The program that is launched
+    pub fn program(&self) -> &Path {
+        &self.program
+    }
+
+    /// The arguments the program is launched with, before the minidump and
+    /// metadata paths
+    pub fn args(&self) -> &[OsString] {
+        &self.args
+    }
+
+    /// Launches the program for the minidump, without waiting for it to exit,
+    /// as the crashing process is about to be terminated
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) fn launch(&self, minidump_path: &Path) {
+        use std::os::unix::ffi::OsStrExt;
+
+        extern "C" {
+            static environ: *const *mut libc::c_char;
+        }
+
+        let metadata_path = minidump_path.with_extension("metadata");
+        let (minidump_path, metadata_path) = match (
+            std::ffi::CString::new(minidump_path.as_os_str().as_bytes()),
+            std::ffi::CString::new(metadata_path.as_os_str().as_bytes()),
+        ) {
+            (Ok(minidump_path), Ok(metadata_path)) => (minidump_path, metadata_path),
+            _ => return,
+        };
+
+        let mut argv: Vec<*mut libc::c_char> = self
+            .argv
+            .iter()
+            .chain([&minidump_path, &metadata_path])
+            .map(|arg| arg.as_ptr().cast_mut())
+            .chain(std::iter::once(std::ptr::null_mut()))
+            .collect();
+
+        #[allow(unsafe_code)]
+        // SAFETY: posix_spawn is implemented with vfork, or clone with
+        // CLONE_VFORK, and exec, which unlike fork doesn't run the atfork
+        // handlers or copy the address space of the crashing process. The
+        // child starts with the default signal handlers and no signals
+        // blocked, as the crash signal is blocked in the signal handler.
+        unsafe {
+            let mut attr: libc::posix_spawnattr_t = std::mem::zeroed();
+            if libc::posix_spawnattr_init(&mut attr) != 0 {
+                return;
+            }
+
+            let mut no_signals: libc::sigset_t = std::mem::zeroed();
+            let mut all_signals: libc::sigset_t = std::mem::zeroed();
+            libc::sigemptyset(&mut no_signals);
+            libc::sigfillset(&mut all_signals);
+            libc::posix_spawnattr_setsigmask(&mut attr, &no_signals);
+            libc::posix_spawnattr_setsigdefault(&mut attr, &all_signals);
+            libc::posix_spawnattr_setflags(
+                &mut attr,
+                (libc::POSIX_SPAWN_SETSIGMASK | libc::POSIX_SPAWN_SETSIGDEF) as _,
+            );
+
+            let mut pid: libc::pid_t = 0;
+            libc::posix_spawnp(
+                &mut pid,
+                argv[0],
+                std::ptr::null(),
+                &attr,
+                argv.as_mut_ptr(),
+                environ,
+            );
+            libc::posix_spawnattr_destroy(&mut attr);
+        }
+    }
+
+    /// Launches the program for the minidump, without waiting for it to exit,
+    /// as the crashing process is about to be terminated
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub(crate) fn launch(&self, minidump_path: &Path) {
+        // Breakpad handles crashes on a thread of its own on Windows and
+        // macOS, so the program can be spawned normally
+        let _ = std::process::Command::new(&self.program)
+            .args(&self.args)
+            .arg(minidump_path)
+            .arg(minidump_path.with_extension("metadata"))
+            .spawn();
+    }
+}
@@ -0,0 +1,155 @@
+//! Human readable names for the signals and signal codes that crash handlers
+//! are notified of on Linux/Android
+
+/// Gets the name of a signal that causes a crash
+pub(crate) fn signal_name(signal: i32) -> Option<&'static str> {
+    Some(match signal {
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGILL => "SIGILL",
+        libc::SIGTRAP => "SIGTRAP",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGSYS => "SIGSYS",
+        _ => return None,
+    })
+}
+
+/// Gets the name and description of the `si_code` of a signal. Positive codes
+/// are specific to the signal, while the others are the same for all signals
+/// and indicate where the signal was sent from.
+pub(crate) fn code_info(signal: i32, code: i32) -> Option<(&'static str, &'static str)> {
+    Some(match (signal, code) {
+        (libc::SIGSEGV, 1) => ("SEGV_MAPERR", "address not mapped to object"),
+        (libc::SIGSEGV, 2) => ("SEGV_ACCERR", "invalid permissions for mapped object"),
+        (libc::SIGSEGV, 3) => ("SEGV_BNDERR", "failed address bound checks"),
+        (libc::SIGSEGV, 4) => ("SEGV_PKUERR", "access was denied by memory protection keys"),
+        (libc::SIGSEGV, 5) => ("SEGV_ACCADI", "ADI not enabled for mapped object"),
+        (libc::SIGSEGV, 6) => ("SEGV_ADIDERR", "disrupting MCD error"),
+        (libc::SIGSEGV, 7) => ("SEGV_ADIPERR", "precise MCD exception"),
+        (libc::SIGSEGV, 8) => ("SEGV_MTEAERR", "asynchronous ARM MTE error"),
+        (libc::SIGSEGV, 9) => ("SEGV_MTESERR", "synchronous ARM MTE exception"),
+        (libc::SIGSEGV, 10) => ("SEGV_CPERR", "control protection fault"),
+
+        (libc::SIGBUS, 1) => ("BUS_ADRALN", "invalid address alignment"),
+        (libc::SIGBUS, 2) => ("BUS_ADRERR", "nonexistent physical address"),
+        (libc::SIGBUS, 3) => ("BUS_OBJERR", "object specific hardware error"),
+        (libc::SIGBUS, 4) => (
+            "BUS_MCEERR_AR",
+            "hardware memory error consumed on a machine check",
+        ),
+        (libc::SIGBUS, 5) => (
+            "BUS_MCEERR_AO",
+            "hardware memory error detected in process but not consumed",
+        ),
+
+        (libc::SIGFPE, 1) => ("FPE_INTDIV", "integer divide by zero"),
+        (libc::SIGFPE, 2) => ("FPE_INTOVF", "integer overflow"),
+        (libc::SIGFPE, 3) => ("FPE_FLTDIV", "floating point divide by zero"),
+        (libc::SIGFPE, 4) => ("FPE_FLTOVF", "floating point overflow"),
+        (libc::SIGFPE, 5) => ("FPE_FLTUND", "floating point underflow"),
+        (libc::SIGFPE, 6) => ("FPE_FLTRES", "floating point inexact result"),
+        (libc::SIGFPE, 7) => ("FPE_FLTINV", "floating point invalid operation"),
+        (libc::SIGFPE, 8) => ("FPE_FLTSUB", "subscript out of range"),
+        (libc::SIGFPE, 14) => ("FPE_FLTUNK", "undiagnosed floating point exception"),
+        (libc::SIGFPE, 15) => ("FPE_CONDTRAP", "trap on condition"),
+
+        (libc::SIGILL, 1) => ("ILL_ILLOPC", "illegal opcode"),
+        (libc::SIGILL, 2) => ("ILL_ILLOPN", "illegal operand"),
+        (libc::SIGILL, 3) => ("ILL_ILLADR", "illegal addressing mode"),
+        (libc::SIGILL, 4) => ("ILL_ILLTRP", "illegal trap"),
+        (libc::SIGILL, 5) => ("ILL_PRVOPC", "privileged opcode"),
+        (libc::SIGILL, 6) => ("ILL_PRVREG", "privileged register"),
+        (libc::SIGILL, 7) => ("ILL_COPROC", "coprocessor error"),
+        (libc::SIGILL, 8) => ("ILL_BADSTK", "internal stack error"),
+        (libc::SIGILL, 9) => ("ILL_BADIADDR", "unimplemented instruction address"),
+
+        (libc::SIGTRAP, 1) => ("TRAP_BRKPT", "process breakpoint"),
+        (libc::SIGTRAP, 2) => ("TRAP_TRACE", "process trace trap"),
+        (libc::SIGTRAP, 3) => ("TRAP_BRANCH", "process taken branch trap"),
+        (libc::SIGTRAP, 4) => ("TRAP_HWBKPT", "hardware breakpoint or watchpoint"),
+        (libc::SIGTRAP, 5) => ("TRAP_UNK", "undiagnosed trap"),
+        (libc::SIGTRAP, 6) => ("TRAP_PERF", "perf event with sigtrap"),
+
+        (libc::SIGSYS, 1) => ("SYS_SECCOMP", "seccomp triggered"),
+
+        (_, 0) => ("SI_USER", "sent by kill, sigsend or raise"),
+        (_, 0x80) => ("SI_KERNEL", "sent by the kernel"),
+        (_, -1) => ("SI_QUEUE", "sent by sigqueue"),
+        (_, -2) => ("SI_TIMER", "POSIX timer expired"),
+        (_, -3) => ("SI_MESGQ", "POSIX message queue state changed"),
+        (_, -4) => ("SI_ASYNCIO", "AIO completed"),
+        (_, -5) => ("SI_SIGIO", "queued SIGIO"),
+        (_, -6) => ("SI_TKILL", "sent by tkill or tgkill"),
+
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_crash_signals() {
+        assert_eq!(signal_name(libc::SIGSEGV), Some("SIGSEGV"));
+        assert_eq!(signal_name(libc::SIGBUS), Some("SIGBUS"));
+        assert_eq!(signal_name(libc::SIGABRT), Some("SIGABRT"));
+    }
+
+    #[test]
+    fn ignores_unknown_signals() {
+        assert_eq!(signal_name(0), None);
+        assert_eq!(signal_name(libc::SIGUSR1), None);
+        assert_eq!(signal_name(-1), None);
+    }
+
+    #[test]
+    fn names_signal_specific_codes() {
+        assert_eq!(
+            code_info(libc::SIGSEGV, 1),
+            Some(("SEGV_MAPERR", "address not mapped to object"))
+        );
+        assert_eq!(
+            code_info(libc::SIGSEGV, 2),
+            Some(("SEGV_ACCERR", "invalid permissions for mapped object"))
+        );
+        assert_eq!(
+            code_info(libc::SIGBUS, 1),
+            Some(("BUS_ADRALN", "invalid address alignment"))
+        );
+        assert_eq!(
+            code_info(libc::SIGBUS, 2),
+            Some(("BUS_ADRERR", "nonexistent physical address"))
+        );
+    }
+
+    #[test]
+    fn names_sender_codes_for_any_signal() {
+        assert_eq!(code_info(libc::SIGSEGV, 0).map(|ci| ci.0), Some("SI_USER"));
+        assert_eq!(
+            code_info(libc::SIGABRT, -6).map(|ci| ci.0),
+            Some("SI_TKILL")
+        );
+        assert_eq!(
+            code_info(libc::SIGBUS, 0x80).map(|ci| ci.0),
+            Some("SI_KERNEL")
+        );
+    }
+
+    #[test]
+    fn ignores_unknown_codes() {
+        assert_eq!(code_info(libc::SIGSEGV, 100), None);
+        assert_eq!(code_info(libc::SIGABRT, 1), None);
+        assert_eq!(code_info(0, 1), None);
+        assert_eq!(code_info(libc::SIGSEGV, -100), None);
+    }
+
+    #[test]
+    fn ignores_windows_exception_codes() {
+        // EXCEPTION_ACCESS_VIOLATION and EXCEPTION_STACK_OVERFLOW, which are
+        // only meaningful on Windows
+        assert_eq!(code_info(libc::SIGSEGV, 0xc000_0005_u32 as i32), None);
+        assert_eq!(code_info(libc::SIGSEGV, 0xc000_00fd_u32 as i32), None);
+    }
+}
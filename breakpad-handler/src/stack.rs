@@ -0,0 +1,44 @@
+//! Detection of crashes that are caused by a thread overflowing its stack,
+//! see [`CrashInfo::is_stack_overflow`](crate::CrashInfo::is_stack_overflow)
+
+/// How far below the lowest address of a stack a fault is still attributed to
+/// the stack overflowing, as a frame that is larger than the guard page skips
+/// past it
+const OVERFLOW_MARGIN: u64 = 64 * 1024;
+
+/// Whether the faulting address is within [`OVERFLOW_MARGIN`] bytes below the
+/// stack of the thread, ie. in or just past its guard page, by looking up the
+/// stack in `/proc/self/task/<tid>/maps`
+pub(crate) fn is_overflow(thread_id: Option<u32>, stack_pointer: u64, address: u64) -> bool {
+    let path = match thread_id {
+        Some(tid) => format!("/proc/self/task/{}/maps", tid),
+        None => "/proc/self/maps".to_owned(),
+    };
+    let maps = match std::fs::read_to_string(path) {
+        Ok(maps) => maps,
+        Err(_) => return false,
+    };
+
+    // The stack is the writable mapping the stack pointer is in, or the first
+    // one above it if the stack pointer has already moved into the guard page.
+    // The mappings are listed in ascending order.
+    let stack_start = maps.lines().find_map(|line| {
+        // start-end perms offset dev inode path
+        let mut parts = line.split_whitespace();
+        let (start, end) = parts.next()?.split_once('-')?;
+        let start = u64::from_str_radix(start, 16).ok()?;
+        let end = u64::from_str_radix(end, 16).ok()?;
+        let writable = parts.next()?.get(1..2) == Some("w");
+
+        (writable && stack_pointer < end).then_some(start)
+    });
+
+    match stack_start {
+        Some(start) => {
+            stack_pointer.saturating_add(OVERFLOW_MARGIN) >= start
+                && address < start
+                && start - address <= OVERFLOW_MARGIN
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,389 @@
+//! Generation of Breakpad symbol files from ELF binaries, the same as
+//! breakpad's `dump_syms` for a binary without debug information, so that
+//! build pipelines don't need to build and run the C++ tool
+
+use std::io::{self, Write};
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+
+const ELFCLASS32: u8 = 1;
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ELFDATA2MSB: u8 = 2;
+
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_NOTE: u32 = 7;
+const SHT_DYNSYM: u32 = 11;
+
+const STT_FUNC: u8 = 2;
+const SHN_UNDEF: u16 = 0;
+
+const NT_GNU_BUILD_ID: u32 = 3;
+
+/// The size of the start of `.text` that is hashed for the id of binaries
+/// without a build id, the same as breakpad
+const TEXT_HASH_SIZE: usize = 4096;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_owned())
+}
+
+/// Reads the fields of an ELF file of either class and byte order
+struct Reader<'e> {
+    elf: &'e [u8],
+    is_64: bool,
+    is_le: bool,
+}
+
+impl<'e> Reader<'e> {
+    fn bytes(&self, offset: u64, len: u64) -> io::Result<&'e [u8]> {
+        let start = usize::try_from(offset).map_err(|_e| invalid("offset is out of bounds"))?;
+        let len = usize::try_from(len).map_err(|_e| invalid("size is out of bounds"))?;
+        start
+            .checked_add(len)
+            .and_then(|end| self.elf.get(start..end))
+            .ok_or_else(|| invalid("data is out of bounds"))
+    }
+
+    fn u8(&self, offset: u64) -> io::Result<u8> {
+        Ok(self.bytes(offset, 1)?[0])
+    }
+
+    fn u16(&self, offset: u64) -> io::Result<u16> {
+        let b = self.bytes(offset, 2)?;
+        let b = [b[0], b[1]];
+        Ok(if self.is_le {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    }
+
+    fn u32(&self, offset: u64) -> io::Result<u32> {
+        let b = self.bytes(offset, 4)?;
+        let b = [b[0], b[1], b[2], b[3]];
+        Ok(if self.is_le {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    }
+
+    fn u64(&self, offset: u64) -> io::Result<u64> {
+        let b = self.bytes(offset, 8)?;
+        let b = [b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]];
+        Ok(if self.is_le {
+            u64::from_le_bytes(b)
+        } else {
+            u64::from_be_bytes(b)
+        })
+    }
+
+    /// Reads an address or offset, which is 32-bit in 32-bit ELF files
+    fn word(&self, offset: u64) -> io::Result<u64> {
+        if self.is_64 {
+            self.u64(offset)
+        } else {
+            self.u32(offset).map(u64::from)
+        }
+    }
+
+    /// Reads a nul terminated string
+    fn str(&self, offset: u64) -> io::Result<&'e str> {
+        let start = usize::try_from(offset).map_err(|_e| invalid("offset is out of bounds"))?;
+        let rest = self
+            .elf
+            .get(start..)
+            .ok_or_else(|| invalid("string is out of bounds"))?;
+        let len = rest
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| invalid("string is not nul terminated"))?;
+        std::str::from_utf8(&rest[..len]).map_err(|_e| invalid("string is not utf-8"))
+    }
+}
+
+struct Section {
+    name: u32,
+    kind: u32,
+    offset: u64,
+    size: u64,
+    link: u32,
+    entry_size: u64,
+}
+
+struct ProgramHeader {
+    kind: u32,
+    offset: u64,
+    vaddr: u64,
+    size: u64,
+}
+
+/// Writes the Breakpad symbol file for the ELF binary, with the `MODULE` and
+/// `INFO CODE_ID` records that identify it, and a `PUBLIC` record for every
+/// function in its symbol table, or its dynamic symbol table if it has been
+/// stripped. `name` is the file name of the binary as it is loaded, eg.
+/// `libfoo.so`, which is what symbol servers look the symbol file up by.
+///
+/// Debug information such as line numbers, inlinees, and call frame
+/// information isn't included, and symbol names are written as they are in
+/// the symbol table, ie. mangled, which Sentry demangles when processing.
+///
+/// ```no_run
+/// let elf = std::fs::read("target/release/libfoo.so")?;
+/// let mut sym = std::fs::File::create("libfoo.so.sym")?;
+/// breakpad_handler::syms::write_sym_file(&elf, "libfoo.so", &mut sym)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn write_sym_file(elf: &[u8], name: &str, out: &mut impl Write) -> io::Result<()> {
+    if elf.get(..4) != Some(&ELF_MAGIC[..]) {
+        return Err(invalid("not an ELF file"));
+    }
+
+    let is_64 = match elf.get(4) {
+        Some(&ELFCLASS32) => false,
+        Some(&ELFCLASS64) => true,
+        _ => return Err(invalid("unknown ELF class")),
+    };
+    let is_le = match elf.get(5) {
+        Some(&ELFDATA2LSB) => true,
+        Some(&ELFDATA2MSB) => false,
+        _ => return Err(invalid("unknown ELF byte order")),
+    };
+    let r = Reader { elf, is_64, is_le };
+
+    let arch = match r.u16(18)? {
+        3 => "x86",
+        62 => "x86_64",
+        40 => "arm",
+        183 => "arm64",
+        8 => "mips",
+        20 => "ppc",
+        21 => "ppc64",
+        43 => "sparcv9",
+        243 if is_64 => "riscv64",
+        243 => "riscv",
+        _ => return Err(invalid("unsupported ELF machine")),
+    };
+
+    let (phoff, shoff, header_sizes) = if is_64 { (32, 40, 52) } else { (28, 32, 40) };
+    let phoff = r.word(phoff)?;
+    let shoff = r.word(shoff)?;
+    let phentsize = u64::from(r.u16(header_sizes + 2)?);
+    let phnum = u64::from(r.u16(header_sizes + 4)?);
+    let shentsize = u64::from(r.u16(header_sizes + 6)?);
+    let shnum = u64::from(r.u16(header_sizes + 8)?);
+    let shstrndx = u64::from(r.u16(header_sizes + 10)?);
+
+    let program_headers = (0..phnum)
+        .map(|i| {
+            let ph = phoff + i * phentsize;
+            Ok(if is_64 {
+                ProgramHeader {
+                    kind: r.u32(ph)?,
+                    offset: r.u64(ph + 8)?,
+                    vaddr: r.u64(ph + 16)?,
+                    size: r.u64(ph + 32)?,
+                }
+            } else {
+                ProgramHeader {
+                    kind: r.u32(ph)?,
+                    offset: u64::from(r.u32(ph + 4)?),
+                    vaddr: u64::from(r.u32(ph + 8)?),
+                    size: u64::from(r.u32(ph + 16)?),
+                }
+            })
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let sections = (0..shnum)
+        .map(|i| {
+            let sh = shoff + i * shentsize;
+            Ok(if is_64 {
+                Section {
+                    name: r.u32(sh)?,
+                    kind: r.u32(sh + 4)?,
+                    offset: r.u64(sh + 24)?,
+                    size: r.u64(sh + 32)?,
+                    link: r.u32(sh + 40)?,
+                    entry_size: r.u64(sh + 56)?,
+                }
+            } else {
+                Section {
+                    name: r.u32(sh)?,
+                    kind: r.u32(sh + 4)?,
+                    offset: u64::from(r.u32(sh + 16)?),
+                    size: u64::from(r.u32(sh + 20)?),
+                    link: r.u32(sh + 24)?,
+                    entry_size: u64::from(r.u32(sh + 36)?),
+                }
+            })
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let section_name = |section: &Section| -> io::Result<&str> {
+        let strtab = sections
+            .get(shstrndx as usize)
+            .ok_or_else(|| invalid("section name table is out of bounds"))?;
+        r.str(strtab.offset + u64::from(section.name))
+    };
+
+    // The build id, from either the section or the segment for it, as the
+    // section headers may have been stripped
+    let mut build_id = None;
+    for (offset, size) in sections
+        .iter()
+        .filter(|s| s.kind == SHT_NOTE)
+        .map(|s| (s.offset, s.size))
+        .chain(
+            program_headers
+                .iter()
+                .filter(|ph| ph.kind == PT_NOTE)
+                .map(|ph| (ph.offset, ph.size)),
+        )
+    {
+        build_id = find_build_id(&r, offset, size)?;
+        if build_id.is_some() {
+            break;
+        }
+    }
+
+    let identifier = if let Some(build_id) = build_id {
+        build_id.to_vec()
+    } else {
+        // Breakpad XORs the start of .text into a GUID sized buffer for
+        // binaries that weren't linked with a build id
+        let text = sections
+            .iter()
+            .find(|s| section_name(s).ok() == Some(".text"))
+            .ok_or_else(|| invalid("no build id or .text section to identify the binary"))?;
+        let text = r.bytes(text.offset, text.size.min(TEXT_HASH_SIZE as u64))?;
+
+        let mut hash = [0u8; 16];
+        for chunk in text.chunks(16) {
+            for (h, b) in hash.iter_mut().zip(chunk) {
+                *h ^= b;
+            }
+        }
+        hash.to_vec()
+    };
+
+    let mut guid = [0u8; 16];
+    let len = identifier.len().min(16);
+    guid[..len].copy_from_slice(&identifier[..len]);
+
+    // The first three fields of the GUID are little endian, the same as on
+    // Windows, and the age is always 0
+    writeln!(
+        out,
+        "MODULE Linux {} {:08X}{:04X}{:04X}{}0 {}",
+        arch,
+        u32::from_le_bytes([guid[0], guid[1], guid[2], guid[3]]),
+        u16::from_le_bytes([guid[4], guid[5]]),
+        u16::from_le_bytes([guid[6], guid[7]]),
+        guid[8..]
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<String>(),
+        name
+    )?;
+    writeln!(
+        out,
+        "INFO CODE_ID {}",
+        identifier
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<String>()
+    )?;
+
+    // Addresses in the symbol file are relative to the start of the binary
+    let load_address = program_headers
+        .iter()
+        .filter(|ph| ph.kind == PT_LOAD)
+        .map(|ph| ph.vaddr)
+        .min()
+        .unwrap_or_default();
+
+    let symtab = sections
+        .iter()
+        .find(|s| s.kind == SHT_SYMTAB)
+        .or_else(|| sections.iter().find(|s| s.kind == SHT_DYNSYM));
+
+    let mut functions = Vec::new();
+    if let Some(symtab) = symtab {
+        let strtab = sections
+            .get(symtab.link as usize)
+            .ok_or_else(|| invalid("symbol name table is out of bounds"))?;
+        let entry_size = if symtab.entry_size != 0 {
+            symtab.entry_size
+        } else if is_64 {
+            24
+        } else {
+            16
+        };
+
+        for i in 0..symtab.size / entry_size {
+            let sym = symtab.offset + i * entry_size;
+            let (name, info, shndx, value) = if is_64 {
+                (
+                    r.u32(sym)?,
+                    r.u8(sym + 4)?,
+                    r.u16(sym + 6)?,
+                    r.u64(sym + 8)?,
+                )
+            } else {
+                (
+                    r.u32(sym)?,
+                    r.u8(sym + 12)?,
+                    r.u16(sym + 14)?,
+                    u64::from(r.u32(sym + 4)?),
+                )
+            };
+
+            if info & 0xf != STT_FUNC || shndx == SHN_UNDEF || value == 0 {
+                continue;
+            }
+
+            let name = r.str(strtab.offset + u64::from(name))?;
+            if !name.is_empty() {
+                functions.push((value.saturating_sub(load_address), name));
+            }
+        }
+    }
+
+    // Aliases of the same function are only written once, as dump_syms does
+    functions.sort();
+    functions.dedup_by_key(|(address, _)| *address);
+
+    for (address, name) in functions {
+        writeln!(out, "PUBLIC {:x} 0 {}", address, name)?;
+    }
+
+    Ok(())
+}
+
+/// Finds the GNU build id in the notes
+fn find_build_id<'e>(r: &Reader<'e>, offset: u64, size: u64) -> io::Result<Option<&'e [u8]>> {
+    let align = |n: u64| (n + 3) & !3;
+
+    let mut note = offset;
+    while note + 12 <= offset + size {
+        let name_size = u64::from(r.u32(note)?);
+        let desc_size = u64::from(r.u32(note + 4)?);
+        let kind = r.u32(note + 8)?;
+
+        let name = r.bytes(note + 12, name_size)?;
+        let desc = note + 12 + align(name_size);
+
+        if kind == NT_GNU_BUILD_ID && name == b"GNU\0" {
+            return r.bytes(desc, desc_size).map(Some);
+        }
+
+        note = desc + align(desc_size);
+    }
+
+    Ok(None)
+}
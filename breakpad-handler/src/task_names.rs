@@ -0,0 +1,215 @@
+//! The labels of the tasks that the worker threads of an async executor are
+//! currently running, so that crash analysis can show eg.
+//! `tokio-worker-3: handle_checkout` rather than an anonymous worker thread
+
+use std::sync::atomic::{self, AtomicPtr, AtomicU32, AtomicU8, AtomicUsize};
+
+/// The maximum length of a task label in bytes, longer labels are truncated
+pub const MAX_TASK_LABEL_LEN: usize = 64;
+
+static REGISTERED: AtomicPtr<TaskNames> = AtomicPtr::new(std::ptr::null_mut());
+
+/// The label of the task a single thread is running
+struct Slot {
+    /// The id of the thread that owns the slot, 0 if it is free
+    thread_id: AtomicU32,
+    /// Odd while the label is being written
+    seq: AtomicU32,
+    len: AtomicUsize,
+    label: [AtomicU8; MAX_TASK_LABEL_LEN],
+}
+
+/// A table of the current task of each worker thread of an executor. The
+/// table is allocated up front, and setting the current task never locks or
+/// allocates, so it can be done every time a task is polled.
+///
+/// ```
+/// use breakpad_handler::TaskNames;
+///
+/// static TASK_NAMES: std::sync::OnceLock<TaskNames> = std::sync::OnceLock::new();
+///
+/// let task_names = TASK_NAMES.get_or_init(|| TaskNames::with_capacity(16));
+/// breakpad_handler::register_task_names(task_names);
+///
+/// // On a worker thread, around every poll of a task
+/// task_names.set_current(Some("handle_checkout"));
+/// task_names.set_current(None);
+/// ```
+pub struct TaskNames {
+    slots: Box<[Slot]>,
+}
+
+impl TaskNames {
+    /// Creates a table for up to `capacity` threads, threads beyond that are
+    /// not recorded
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity)
+                .map(|_| Slot {
+                    thread_id: AtomicU32::new(0),
+                    seq: AtomicU32::new(0),
+                    len: AtomicUsize::new(0),
+                    label: std::array::from_fn(|_| AtomicU8::new(0)),
+                })
+                .collect(),
+        }
+    }
+
+    /// Sets the label of the task the calling thread is running, or clears it
+    /// if `None`, eg. when the task yields
+    pub fn set_current(&self, label: Option<&str>) {
+        let slot = match self.slot(current_thread_id()) {
+            Some(slot) => slot,
+            None => return,
+        };
+
+        let label = label.unwrap_or_default().as_bytes();
+        let len = label.len().min(MAX_TASK_LABEL_LEN);
+
+        // Only the owning thread writes to the slot, so this is a plain seqlock
+        let seq = slot.seq.load(atomic::Ordering::Relaxed);
+        slot.seq
+            .store(seq.wrapping_add(1), atomic::Ordering::Relaxed);
+        atomic::fence(atomic::Ordering::Release);
+
+        for (dst, src) in slot.label.iter().zip(&label[..len]) {
+            dst.store(*src, atomic::Ordering::Relaxed);
+        }
+        slot.len.store(len, atomic::Ordering::Relaxed);
+
+        slot.seq
+            .store(seq.wrapping_add(2), atomic::Ordering::Release);
+    }
+
+    /// Frees the slot of the calling thread, eg. before the worker thread
+    /// exits, so that it can be used by another thread
+    pub fn release_current_thread(&self) {
+        let thread_id = current_thread_id();
+        if let Some(slot) = self
+            .slots
+            .iter()
+            .find(|slot| slot.thread_id.load(atomic::Ordering::Relaxed) == thread_id)
+        {
+            slot.len.store(0, atomic::Ordering::Relaxed);
+            slot.thread_id.store(0, atomic::Ordering::Release);
+        }
+    }
+
+    /// Gets the slot owned by the thread, claiming a free one the first time
+    fn slot(&self, thread_id: u32) -> Option<&Slot> {
+        if let Some(slot) = self
+            .slots
+            .iter()
+            .find(|slot| slot.thread_id.load(atomic::Ordering::Relaxed) == thread_id)
+        {
+            return Some(slot);
+        }
+
+        self.slots.iter().find(|slot| {
+            slot.thread_id
+                .compare_exchange(
+                    0,
+                    thread_id,
+                    atomic::Ordering::Acquire,
+                    atomic::Ordering::Relaxed,
+                )
+                .is_ok()
+        })
+    }
+
+    /// Gets the threads that are currently running a task, and its label
+    fn snapshot(&self) -> Vec<(u32, String)> {
+        self.slots
+            .iter()
+            .filter_map(|slot| {
+                // Give up on a label that keeps changing under us rather than
+                // spinning, it's just as likely to be wrong by the time it's read
+                for _ in 0..4 {
+                    let seq = slot.seq.load(atomic::Ordering::Acquire);
+                    if seq % 2 == 1 {
+                        continue;
+                    }
+
+                    let thread_id = slot.thread_id.load(atomic::Ordering::Relaxed);
+                    let len = slot.len.load(atomic::Ordering::Relaxed);
+                    let label: Vec<u8> = slot.label[..len]
+                        .iter()
+                        .map(|b| b.load(atomic::Ordering::Relaxed))
+                        .collect();
+
+                    atomic::fence(atomic::Ordering::Acquire);
+                    if slot.seq.load(atomic::Ordering::Relaxed) != seq {
+                        continue;
+                    }
+
+                    if thread_id == 0 || label.is_empty() {
+                        return None;
+                    }
+
+                    return Some((thread_id, String::from_utf8_lossy(&label).into_owned()));
+                }
+
+                None
+            })
+            .collect()
+    }
+}
+
+/// Registers the table whose labels are included in the minidump when a crash
+/// occurs, replacing any table that was registered before
+pub fn register_task_names(task_names: &'static TaskNames) {
+    REGISTERED.store(
+        (task_names as *const TaskNames).cast_mut(),
+        atomic::Ordering::Release,
+    );
+}
+
+/// Gets the id of every thread that is currently running a task in the
+/// registered table, as it appears in the minidump, along with the label of
+/// the task
+pub fn task_names() -> Vec<(u32, String)> {
+    let task_names = REGISTERED.load(atomic::Ordering::Acquire);
+
+    #[allow(unsafe_code)]
+    // SAFETY: Only tables with a static lifetime can be registered
+    unsafe { task_names.as_ref() }.map_or_else(Vec::new, TaskNames::snapshot)
+}
+
+/// Gets the id of the calling thread, as breakpad records it in the minidump
+fn current_thread_id() -> u32 {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        #[allow(unsafe_code)]
+        // SAFETY: gettid can't fail
+        unsafe {
+            libc::gettid() as u32
+        }
+    }
+    #[cfg(windows)]
+    {
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn GetCurrentThreadId() -> u32;
+        }
+
+        #[allow(unsafe_code)]
+        // SAFETY: GetCurrentThreadId can't fail
+        unsafe {
+            GetCurrentThreadId()
+        }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        // Breakpad records the mach port of each thread as its id
+        extern "C" {
+            fn pthread_self() -> *mut std::ffi::c_void;
+            fn pthread_mach_thread_np(thread: *mut std::ffi::c_void) -> u32;
+        }
+
+        #[allow(unsafe_code)]
+        // SAFETY: pthread_self is always a valid thread
+        unsafe {
+            pthread_mach_thread_np(pthread_self())
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Detection of minidumps whose thread stacks breakpad truncated to stay
+//! within [`MinidumpSettings::with_size_limit`](crate::MinidumpSettings::with_size_limit)
+
+use std::os::unix::fs::FileExt;
+
+const MINIDUMP_SIGNATURE: u32 = 0x504d_444d;
+/// The size of a `MINIDUMP_DIRECTORY` entry, ie. the stream type, and the size
+/// and RVA of the stream
+const DIRECTORY_ENTRY_SIZE: u64 = 12;
+const THREAD_LIST_STREAM: u32 = 3;
+/// The size of a `MINIDUMP_THREAD`
+const THREAD_SIZE: u64 = 48;
+/// The offset of the `DataSize` of the stack memory in a `MINIDUMP_THREAD`
+const STACK_SIZE_OFFSET: usize = 32;
+/// The number of threads whose stacks breakpad always captures in full
+const BASE_THREAD_COUNT: u64 = 20;
+/// The length breakpad truncates the stacks of the other threads to
+const TRUNCATED_STACK_LEN: u32 = 2 * 1024;
+/// Breakpad writes about 20 streams, so a minidump claiming more than this is
+/// corrupt, and isn't walked any further in the crashing process
+const MAX_STREAMS: u32 = 256;
+/// The number of threads that are scanned at most, so that a process with a
+/// huge number of threads doesn't stall the crash handler
+const MAX_THREADS: u64 = 4096;
+
+/// Whether breakpad truncated the stack of any thread in the minidump. It
+/// doesn't record that it did, so this looks for a thread beyond the first 20
+/// with a stack of exactly the length it truncates to. This is done with fixed
+/// size buffers and a bounded amount of I/O, as it runs in the crashing
+/// process, so only the first 4096 threads are scanned.
+pub(crate) fn is_truncated(path: &std::path::Path) -> std::io::Result<bool> {
+    let file = std::fs::File::open(path)?;
+
+    let mut header = [0u8; 16];
+    file.read_exact_at(&mut header, 0)?;
+    let u32_at = |bytes: &[u8], offset: usize| {
+        u32::from_le_bytes([
+            bytes[offset],
+            bytes[offset + 1],
+            bytes[offset + 2],
+            bytes[offset + 3],
+        ])
+    };
+    if u32_at(&header, 0) != MINIDUMP_SIGNATURE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "not a minidump",
+        ));
+    }
+
+    let stream_count = u32_at(&header, 8);
+    let directory = u64::from(u32_at(&header, 12));
+    if stream_count > MAX_STREAMS {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "too many streams",
+        ));
+    }
+
+    for i in 0..u64::from(stream_count) {
+        let mut entry = [0u8; DIRECTORY_ENTRY_SIZE as usize];
+        file.read_exact_at(&mut entry, directory + i * DIRECTORY_ENTRY_SIZE)?;
+        if u32_at(&entry, 0) != THREAD_LIST_STREAM {
+            continue;
+        }
+
+        let rva = u64::from(u32_at(&entry, 8));
+        let mut count = [0u8; 4];
+        file.read_exact_at(&mut count, rva)?;
+
+        let mut thread = [0u8; THREAD_SIZE as usize];
+        for t in BASE_THREAD_COUNT..u64::from(u32_at(&count, 0)).min(MAX_THREADS) {
+            file.read_exact_at(&mut thread, rva + 4 + t * THREAD_SIZE)?;
+            if u32_at(&thread, STACK_SIZE_OFFSET) == TRUNCATED_STACK_LEN {
+                return Ok(true);
+            }
+        }
+
+        return Ok(false);
+    }
+
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a minidump with only a thread list, with the stack sizes
+    fn write_minidump(name: &str, stacks: &[u32]) -> std::path::PathBuf {
+        let mut minidump = Vec::new();
+        minidump.extend_from_slice(&MINIDUMP_SIGNATURE.to_le_bytes());
+        minidump.extend_from_slice(&0u32.to_le_bytes());
+        // One stream, with the directory right after the header
+        minidump.extend_from_slice(&1u32.to_le_bytes());
+        minidump.extend_from_slice(&16u32.to_le_bytes());
+        minidump.extend_from_slice(&THREAD_LIST_STREAM.to_le_bytes());
+        minidump.extend_from_slice(&(4 + stacks.len() as u32 * THREAD_SIZE as u32).to_le_bytes());
+        minidump.extend_from_slice(&28u32.to_le_bytes());
+
+        minidump.extend_from_slice(&(stacks.len() as u32).to_le_bytes());
+        for &stack in stacks {
+            let mut thread = [0u8; THREAD_SIZE as usize];
+            thread[STACK_SIZE_OFFSET..STACK_SIZE_OFFSET + 4].copy_from_slice(&stack.to_le_bytes());
+            minidump.extend_from_slice(&thread);
+        }
+
+        let path = std::env::temp_dir().join(format!(
+            "breakpad-truncated-{}-{}.dmp",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, minidump).unwrap();
+        path
+    }
+
+    #[test]
+    fn detects_truncated_stacks() {
+        let mut stacks = vec![16 * 1024; 20];
+        stacks.extend_from_slice(&[TRUNCATED_STACK_LEN; 5]);
+        let path = write_minidump("truncated", &stacks);
+        assert!(is_truncated(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn ignores_base_threads() {
+        // The first 20 stacks are never truncated, however long they are
+        let path = write_minidump("base", &[TRUNCATED_STACK_LEN; 20]);
+        assert!(!is_truncated(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn ignores_full_stacks() {
+        let path = write_minidump("full", &[16 * 1024; 25]);
+        assert!(!is_truncated(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_non_minidumps() {
+        let path = std::env::temp_dir().join(format!(
+            "breakpad-truncated-invalid-{}.dmp",
+            std::process::id()
+        ));
+        std::fs::write(&path, [0u8; 64]).unwrap();
+        assert!(is_truncated(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,71 @@
+//! A requested minidump is still written once the process has run out of
+//! file descriptors, using the ones breakpad-sys holds in reserve
+
+#![cfg(any(target_os = "linux", target_os = "android"))]
+
+use breakpad_handler::{BreakpadHandler, InstallOptions};
+
+/// Set for the child process the test runs in, as exhausting the file
+/// descriptors would break the test harness itself
+const CHILD_ENV: &str = "BREAKPAD_FD_EXHAUSTION_CHILD";
+
+#[test]
+fn writes_minidump_without_fds() {
+    if std::env::var_os(CHILD_ENV).is_some() {
+        exhaust_and_dump();
+        return;
+    }
+
+    let status = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "writes_minidump_without_fds", "--nocapture"])
+        .env(CHILD_ENV, "1")
+        .status()
+        .unwrap();
+    assert!(status.success(), "child process failed: {status}");
+}
+
+fn exhaust_and_dump() {
+    let crash_dir =
+        std::env::temp_dir().join(format!("breakpad-fd-exhaustion-{}", std::process::id()));
+    std::fs::create_dir_all(&crash_dir).unwrap();
+
+    // Keeps the table small, so it is quick to fill up
+    let limit = libc::rlimit {
+        rlim_cur: 64,
+        rlim_max: 64,
+    };
+    #[allow(unsafe_code)]
+    // SAFETY: The pointer is to a valid rlimit on the stack
+    let limited = unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) };
+    assert_eq!(limited, 0, "{}", std::io::Error::last_os_error());
+
+    let handler = BreakpadHandler::attach(
+        &crash_dir,
+        InstallOptions::BothHandlers,
+        Box::new(|_minidump_path: std::path::PathBuf| {}),
+    )
+    .unwrap();
+
+    let mut exhausted = Vec::new();
+    let err = loop {
+        match std::fs::File::open("/dev/null") {
+            Ok(file) => exhausted.push(file),
+            Err(err) => break err,
+        }
+    };
+    assert_eq!(err.raw_os_error(), Some(libc::EMFILE));
+
+    let written = handler.generate_minidump();
+    drop(exhausted);
+    written.unwrap();
+
+    let minidumps = std::fs::read_dir(&crash_dir)
+        .unwrap()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "dmp"))
+        .count();
+    assert_eq!(minidumps, 1);
+
+    drop(handler);
+    let _ = std::fs::remove_dir_all(&crash_dir);
+}
@@ -0,0 +1,46 @@
+//! The [`CrashFilter`](breakpad_handler::CrashFilter) decides on the crash
+//! that is being handled, not the previous one
+
+#![cfg(any(target_os = "linux", target_os = "android"))]
+
+use breakpad_handler::{BreakpadHandler, CrashInfo, InstallOptions};
+
+#[test]
+fn filters_on_signal() {
+    let crash_dir = std::env::temp_dir().join(format!("breakpad-filter-{}", std::process::id()));
+    std::fs::create_dir_all(&crash_dir).unwrap();
+
+    let handler = BreakpadHandler::attach(
+        &crash_dir,
+        InstallOptions::BothHandlers,
+        Box::new(|_minidump_path: std::path::PathBuf| {}),
+    )
+    .unwrap();
+    handler.set_filter(Some(Box::new(|info: &CrashInfo| {
+        info.signal != Some(libc::SIGSEGV)
+    })));
+
+    let crash = |signal| CrashInfo {
+        signal: Some(signal),
+        ..Default::default()
+    };
+
+    assert!(handler
+        .generate_minidump_with_exception(&crash(libc::SIGSEGV))
+        .is_err());
+    assert!(handler
+        .generate_minidump_with_exception(&crash(libc::SIGILL))
+        .is_ok());
+    // Rejected again after a crash that wasn't, so it can't have seen the
+    // details of the previous one
+    assert!(handler
+        .generate_minidump_with_exception(&crash(libc::SIGSEGV))
+        .is_err());
+
+    let stats = handler.stats();
+    assert_eq!(stats.dumps_skipped_filtered, 2);
+    assert_eq!(stats.dumps_written, 1);
+
+    drop(handler);
+    let _ = std::fs::remove_dir_all(&crash_dir);
+}
@@ -9,6 +9,44 @@ fn add_sources(build: &mut cc::Build, root: &str, files: &[&str]) {
     build.include(root);
 }
 
+/// Adds the flags for the system breakpad client library from pkg-config,
+/// returning the libraries to link
+fn system_breakpad(build: &mut cc::Build) -> Vec<String> {
+    println!("cargo:rerun-if-env-changed=PKG_CONFIG");
+    println!("cargo:rerun-if-env-changed=PKG_CONFIG_PATH");
+
+    let pkg_config = std::env::var("PKG_CONFIG").unwrap_or_else(|_| "pkg-config".to_owned());
+    let output = std::process::Command::new(&pkg_config)
+        .args(["--cflags", "--libs", "breakpad-client"])
+        .output()
+        .unwrap_or_else(|e| panic!("failed to run '{pkg_config}': {e}"));
+
+    if !output.status.success() {
+        panic!(
+            "unable to find breakpad-client with pkg-config: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let mut libs = Vec::new();
+    for flag in String::from_utf8_lossy(&output.stdout).split_whitespace() {
+        if let Some(dir) = flag.strip_prefix("-I") {
+            build.include(dir);
+            // The shim includes the handler header by name, as it is in a
+            // different directory on every platform
+            build.include(std::path::Path::new(dir).join("client/linux/handler"));
+        } else if let Some(dir) = flag.strip_prefix("-L") {
+            println!("cargo:rustc-link-search=native={dir}");
+        } else if let Some(lib) = flag.strip_prefix("-l") {
+            libs.push(lib.to_owned());
+        } else {
+            build.flag(flag);
+        }
+    }
+
+    libs
+}
+
 fn main() {
     let mut build = cc::Build::new();
 
@@ -16,7 +54,6 @@ fn main() {
         .cpp(true)
         .warnings(false)
         .include(".")
-        .include("breakpad/src")
         .define("BPLOG_MINIMUM_SEVERITY", "SEVERITY_ERROR")
         .define(
             "BPLOG(severity)",
@@ -30,18 +67,46 @@ fn main() {
     // Our file that implements a small C API that we can easily bind to
     build.file("src/impl.cpp");
 
+    build.define("TARGET_OS_WINDOWS", "0");
+
+    let target_os = std::env::var("CARGO_CFG_TARGET_OS").expect("TARGET_OS not specified");
+
+    // Distributions that package breakpad can link against it instead of
+    // compiling the vendored sources
+    println!("cargo:rerun-if-env-changed=BREAKPAD_SYS_USE_SYSTEM");
+    let use_system = std::env::var_os("CARGO_FEATURE_SYSTEM_BREAKPAD").is_some()
+        || std::env::var("BREAKPAD_SYS_USE_SYSTEM").is_ok_and(|v| v == "1");
+
+    if use_system {
+        match target_os.as_str() {
+            "linux" | "android" => {
+                build.define("TARGET_OS_LINUX", None);
+            }
+            unsupported => {
+                panic!("linking the system breakpad is not supported on '{unsupported}'")
+            }
+        }
+
+        let libs = system_breakpad(&mut build);
+
+        // Only our shim is compiled, which must come before the system
+        // library on the link line for its symbols to be resolved
+        build.compile("breakpad-shim");
+        for lib in libs {
+            println!("cargo:rustc-link-lib={lib}");
+        }
+        return;
+    }
+
+    build.include("breakpad/src");
+
     add_sources(
         &mut build,
         "breakpad/src/common",
         &["convert_UTF", "string_conversion"],
     );
 
-    build.define("TARGET_OS_WINDOWS", "0");
-
-    match std::env::var("CARGO_CFG_TARGET_OS")
-        .expect("TARGET_OS not specified")
-        .as_str()
-    {
+    match target_os.as_str() {
         "linux" | "android" => {
             build.define("TARGET_OS_LINUX", None).include("lss");
 
@@ -51,6 +116,7 @@ fn main() {
                 &mut build,
                 "breakpad/src/common/linux",
                 &[
+                    "elf_core_dump",
                     "elfutils",
                     "file_id",
                     "guid_creator",
@@ -71,7 +137,7 @@ fn main() {
             add_sources(
                 &mut build,
                 "breakpad/src/client/linux/crash_generation",
-                &["crash_generation_client"],
+                &["crash_generation_client", "crash_generation_server"],
             );
 
             add_sources(
@@ -84,6 +150,7 @@ fn main() {
                 &mut build,
                 "breakpad/src/client/linux/minidump_writer",
                 &[
+                    "linux_core_dumper",
                     "linux_dumper",
                     "linux_ptrace_dumper",
                     "minidump_writer",
@@ -107,7 +174,12 @@ fn main() {
             add_sources(
                 &mut build,
                 "breakpad/src/client/windows/crash_generation",
-                &["crash_generation_client"],
+                &[
+                    "client_info",
+                    "crash_generation_client",
+                    "crash_generation_server",
+                    "minidump_generator",
+                ],
             );
 
             add_sources(
@@ -141,7 +213,7 @@ fn main() {
             add_sources(
                 &mut build,
                 "breakpad/src/client/mac/crash_generation",
-                &["crash_generation_client"],
+                &["crash_generation_client", "crash_generation_server"],
             );
 
             add_sources(
@@ -20,6 +20,8 @@ fn main() {
         extern "C" fn callback(
             path: *const breakpad_sys::PathChar,
             path_len: usize,
+            _succeeded: bool,
+            _crash_info: *const breakpad_sys::CrashInfo,
             _ctx: *mut std::ffi::c_void,
         ) {
             let path_slice = unsafe { std::slice::from_raw_parts(path, path_len) };
@@ -52,8 +54,12 @@ fn main() {
             path.as_ptr(),
             path.len(),
             callback,
+            None,
             std::ptr::null_mut(),
             breakpad_sys::INSTALL_BOTH_HANDLERS,
+            -1,
+            std::ptr::null(),
+            std::ptr::null(),
         );
 
         if std::env::args().any(|a| a == "--crash") {
@@ -3,41 +3,531 @@
 
 #include "exception_handler.h"
 
+#if TARGET_OS_WINDOWS
+    #include "client/windows/crash_generation/crash_generation_server.h"
+#elif defined(TARGET_OS_MAC)
+    #include "client/mac/crash_generation/crash_generation_server.h"
+
+    #include <mach/mach.h>
+    #include <sys/sysctl.h>
+    #include <unistd.h>
+#elif defined(TARGET_OS_LINUX)
+    #include "client/linux/crash_generation/crash_generation_server.h"
+    #include "client/linux/minidump_writer/linux_core_dumper.h"
+    #include "client/linux/minidump_writer/minidump_writer.h"
+
+    #include <errno.h>
+    #include <fcntl.h>
+    #include <signal.h>
+    #include <sys/eventfd.h>
+    #include <sys/prctl.h>
+    #include <sys/resource.h>
+    #include <sys/syscall.h>
+    #include <sys/uio.h>
+    #include <ucontext.h>
+    #include <unistd.h>
+
+    #ifndef PR_SET_PTRACER
+        #define PR_SET_PTRACER 0x59616d61
+    #endif
+#endif
+
 #if TARGET_OS_WINDOWS
     #define CHAR_TYPE uint16_t
 #else
     #define CHAR_TYPE uint8_t
 #endif
 
+// Details about the crash, fields that are unknown on the current platform are 0
+struct CrashInfo {
+    // The signal that caused the crash, only on Linux/Android
+    int32_t signal;
+    // The si_code on Linux/Android, or the exception code on Windows
+    uint32_t code;
+    // The faulting address on Linux/Android, or the exception address on Windows
+    uint64_t address;
+    // The value of GetLastError on the crashing thread, only on Windows
+    uint32_t last_error;
+    // The number of valid entries in parameters
+    uint32_t num_parameters;
+    // The first parameters of the exception record, only on Windows. For
+    // access violations and in-page errors these are the kind of access, the
+    // address that was accessed, and for the latter the underlying NTSTATUS
+    uint64_t parameters[3];
+    // The id of the crashing thread, only on Linux/Android
+    uint32_t thread_id;
+    // The stack pointer of the crashing thread when it crashed, only on
+    // Linux/Android
+    uint64_t stack_pointer;
+};
+
 // Callback invoked when a minidump occurs. Returns the path + length of the
-// minidump file, along with the callback context.
-typedef void (*dump_callback)(const CHAR_TYPE*, size_t, void*);
+// minidump file, whether breakpad succeeded in writing it, the details of the
+// crash, along with the callback context.
+typedef void (*dump_callback)(const CHAR_TYPE*, size_t, bool, const CrashInfo*, void*);
+// Callback invoked before a minidump is written with the details of the
+// crash, returning false prevents the minidump from being written at all.
+typedef bool (*filter_callback)(const CrashInfo*, void*);
+// Callback invoked first thing in the signal handler with the signal and the
+// faulting address, along with the callback context, only on Linux/Android
+typedef void (*signal_observer)(int32_t, uint64_t, void*);
+// Callback invoked by a crash generation server when it has written a
+// minidump for one of its clients, with the path + length of the minidump
+typedef void (*server_dump_callback)(const CHAR_TYPE*, size_t, void*);
+
+// Settings that control the contents of the minidump
+struct MinidumpSettings {
+    // Capture a small window of memory around every register of the crashing
+    // thread that points to readable memory
+    bool capture_register_memory;
+    // Don't write a minidump if the process has been made non-dumpable, eg.
+    // via PR_SET_DUMPABLE, only applies on Linux/Android
+    bool respect_non_dumpable;
+    // The mach exceptions, as an exception_mask_t, that are left to the
+    // exception ports that were registered before the handler, only applies
+    // on macOS
+    uint32_t forward_exception_mask;
+    // Set the nice value of the crashing thread, which the child process that
+    // writes the minidump inherits, to dumper_nice, only applies on
+    // Linux/Android
+    bool set_dumper_nice;
+    int32_t dumper_nice;
+    // The maximum size of the minidump in bytes, 0 for no limit, only applies
+    // on Linux/Android
+    uint64_t size_limit;
+    // Replace everything on the stacks that isn't a pointer into a mapped
+    // module with a placeholder, only applies on Linux/Android
+    bool sanitize_stacks;
+    // The signals, as a mask of 1 << signal, that are left to the handlers
+    // that were installed before breakpad's, only applies on Linux/Android
+    uint32_t unhooked_signals;
+};
+
+// The crash generation server that a client sends its crashes to, rather than
+// writing the minidump in the crashing process
+struct CrashServerChannel {
+    // The name of the pipe on Windows, or of the mach port on macOS
+    const CHAR_TYPE* name;
+    size_t name_len;
+    // One end of the socket pair created with create_report_channel, only on
+    // Linux/Android
+    int fd;
+};
+
+#if defined(TARGET_OS_LINUX)
+// What happens to a crash once the minidump has been written
+enum AfterDump {
+    // Breakpad's default, the signal is handled by its default action if the
+    // minidump was written, otherwise the previous handlers are restored
+    AFTER_DUMP_DEFAULT = 0,
+    // The handlers that were installed before breakpad's are restored, and
+    // the signal is passed on to them
+    AFTER_DUMP_RESTORE_PREVIOUS = 1,
+    // The handler that was installed before breakpad's for the signal is
+    // called directly, before the signal is handled by its default action
+    AFTER_DUMP_CALL_PREVIOUS = 2,
+    // The process exits with after_dump_exit_code
+    AFTER_DUMP_EXIT = 3,
+};
+
+// The signals breakpad installs its handlers for
+static const int EXCEPTION_SIGNALS[] = { SIGSEGV, SIGABRT, SIGFPE, SIGILL, SIGBUS, SIGTRAP };
+static const size_t EXCEPTION_SIGNAL_COUNT = sizeof(EXCEPTION_SIGNALS) / sizeof(EXCEPTION_SIGNALS[0]);
+
+// The number of file descriptors held in reserve, enough for the minidump
+// file, the pipe breakpad creates to synchronize with the dumper child, and
+// the procfs and mapped files the child opens one at a time
+static const size_t RESERVED_FD_COUNT = 8;
+#endif
 
 struct BreakpadContext {
     dump_callback callback;
+    filter_callback filter;
     void* callback_ctx;
+    CrashInfo crash_info;
+    MinidumpSettings settings;
+    google_breakpad::ExceptionHandler* handler;
+    // The value of PR_GET_DUMPABLE before breakpad changed it, -1 if unknown
+    int prior_dumpable;
+    // The cgroup.procs file of the cgroup the process is moved to before the
+    // minidump is written, -1 if it isn't moved
+    int dumper_cgroup_fd;
+    #if defined(TARGET_OS_LINUX)
+    // The thread whose nice value was changed, 0 if it wasn't, and its nice
+    // value before that
+    pid_t niced_thread;
+    int prior_nice;
+    // Opened when the handler is attached and closed right before the
+    // minidump is written, so that it can still be written if the process has
+    // run out of file descriptors, -1 for the ones that couldn't be opened
+    int reserved_fds[RESERVED_FD_COUNT];
+    // One of AfterDump, and the exit code for AFTER_DUMP_EXIT
+    int after_dump;
+    int after_dump_exit_code;
+    // The handlers that were installed before breakpad's, in the same order
+    // as EXCEPTION_SIGNALS
+    struct sigaction previous_handlers[EXCEPTION_SIGNAL_COUNT];
+    // The signal that is being handled, only set while a crash is handled,
+    // not when a minidump is requested
+    const google_breakpad::ExceptionHandler::CrashContext* crash_context;
+    // Set while generate_minidump_with_exception simulates a signal
+    bool simulating;
+    // Whether the filter rejected the crash that was handled last
+    bool vetoed;
+    signal_observer observer;
+    #endif
 };
 
+static bool should_dump(BreakpadContext* ctx) {
+    return ctx->filter == nullptr || ctx->filter(&ctx->crash_info, ctx->callback_ctx);
+}
+
+#if defined(TARGET_OS_LINUX)
+// Gets the stack pointer from the context of a signal, or 0 if it isn't
+// known for the architecture
+static uint64_t stack_pointer_from_context(const ucontext_t* uc) {
+    #if defined(__x86_64__)
+        return (uint64_t)uc->uc_mcontext.gregs[REG_RSP];
+    #elif defined(__i386__)
+        return (uint64_t)uc->uc_mcontext.gregs[REG_ESP];
+    #elif defined(__aarch64__)
+        return (uint64_t)uc->uc_mcontext.sp;
+    #elif defined(__arm__)
+        return (uint64_t)uc->uc_mcontext.arm_sp;
+    #else
+        (void)uc;
+        return 0;
+    #endif
+}
+
+// The size of the window of memory captured around each register
+static const uintptr_t REGISTER_MEMORY_SIZE = 128;
+
+// Registers a window of memory around every register in the context that
+// points to readable memory, clamped to the page containing the address. This
+// runs in the signal handler, so readability is checked with process_vm_readv,
+// which fails rather than faulting if the memory is not mapped or readable.
+static void register_memory_from_context(
+    google_breakpad::ExceptionHandler* handler,
+    const ucontext_t* uc
+) {
+    #if defined(__x86_64__) || defined(__i386__)
+        const auto* regs = (const uintptr_t*)uc->uc_mcontext.gregs;
+        const size_t reg_count = NGREG;
+    #elif defined(__aarch64__)
+        const auto* regs = (const uintptr_t*)uc->uc_mcontext.regs;
+        const size_t reg_count = 31;
+    #elif defined(__arm__)
+        // r0-r10, fp, ip, sp, lr, pc are laid out contiguously
+        const auto* regs = (const uintptr_t*)&uc->uc_mcontext.arm_r0;
+        const size_t reg_count = 16;
+    #else
+        (void)handler;
+        (void)uc;
+        return;
+    #endif
+
+    #if defined(__x86_64__) || defined(__i386__) || defined(__aarch64__) || defined(__arm__)
+        const uintptr_t page_size = (uintptr_t)sysconf(_SC_PAGESIZE);
+        const pid_t pid = getpid();
+
+        for (size_t i = 0; i < reg_count; ++i) {
+            const uintptr_t addr = regs[i];
+            // Don't bother with values that are obviously not pointers
+            if (addr < page_size) {
+                continue;
+            }
+
+            uint8_t probe;
+            struct iovec local = { &probe, 1 };
+            struct iovec remote = { (void*)addr, 1 };
+            if (process_vm_readv(pid, &local, 1, &remote, 1, 0) != 1) {
+                continue;
+            }
+
+            const uintptr_t page_start = addr & ~(page_size - 1);
+            const uintptr_t page_end = page_start + page_size;
+
+            uintptr_t start = addr - REGISTER_MEMORY_SIZE / 2;
+            if (start < page_start || start > addr) {
+                start = page_start;
+            }
+            uintptr_t end = addr + REGISTER_MEMORY_SIZE / 2;
+            if (end > page_end || end < addr) {
+                end = page_end;
+            }
+
+            handler->RegisterAppMemory((void*)start, end - start);
+        }
+    #endif
+}
+#endif
+
+#if defined(TARGET_OS_LINUX)
+// The child process breakpad clones to write the minidump inherits the nice
+// value and cgroup of the crashing thread, so they are changed right before,
+// as the crash could well be due to the process being starved of CPU. This
+// runs in the signal handler, so only uses async signal safe functions.
+static void prepare_dumper(BreakpadContext* ctx) {
+    int saved_errno = errno;
+
+    if (ctx->settings.set_dumper_nice) {
+        pid_t tid = (pid_t)syscall(SYS_gettid);
+
+        // -1 is a valid nice value, so errors are only reported via errno
+        errno = 0;
+        int prior_nice = getpriority(PRIO_PROCESS, tid);
+        if (errno == 0 && setpriority(PRIO_PROCESS, tid, ctx->settings.dumper_nice) == 0) {
+            ctx->niced_thread = tid;
+            ctx->prior_nice = prior_nice;
+        }
+    }
+
+    if (ctx->dumper_cgroup_fd >= 0) {
+        char digits[16];
+        size_t len = 0;
+        for (unsigned int pid = (unsigned int)getpid(); len == 0 || pid > 0; pid /= 10) {
+            digits[len++] = '0' + pid % 10;
+        }
+
+        char buf[16];
+        for (size_t i = 0; i < len; ++i) {
+            buf[i] = digits[len - 1 - i];
+        }
+
+        // Fails if the process isn't allowed to move to the cgroup, in which
+        // case the minidump is still written, just not any faster
+        if (write(ctx->dumper_cgroup_fd, buf, len) < 0) {}
+    }
+
+    errno = saved_errno;
+}
+
+// Calls the handler that was installed before breakpad's for the signal, with
+// copies of the details of the signal, as it is free to modify them but they
+// aren't ours to change. This runs in the signal handler, so only uses async
+// signal safe functions.
+static void call_previous_handler(
+    BreakpadContext* ctx,
+    const google_breakpad::ExceptionHandler::CrashContext* cc
+) {
+    int sig = cc->siginfo.si_signo;
+    for (size_t i = 0; i < EXCEPTION_SIGNAL_COUNT; ++i) {
+        if (EXCEPTION_SIGNALS[i] != sig) {
+            continue;
+        }
+
+        const struct sigaction& previous = ctx->previous_handlers[i];
+        siginfo_t info = cc->siginfo;
+        ucontext_t context = cc->context;
+        if (previous.sa_flags & SA_SIGINFO) {
+            if (previous.sa_sigaction != nullptr) {
+                previous.sa_sigaction(sig, &info, &context);
+            }
+        } else if (previous.sa_handler != SIG_DFL && previous.sa_handler != SIG_IGN) {
+            previous.sa_handler(sig);
+        }
+        break;
+    }
+}
+
+// Passes a crash that the filter rejected on to the handlers that were
+// installed before breakpad's, the same as breakpad does when its own filter
+// rejects a crash. That filter can't be used, as breakpad invokes it before
+// the details of the signal are known, so the crash is rejected from the
+// crash handler hook instead, which breakpad treats as the signal being
+// handled, and it is handled by its default action once we return. So the
+// previous handler for the signal is called directly.
+static void pass_on_rejected(
+    BreakpadContext* ctx,
+    const google_breakpad::ExceptionHandler::CrashContext* cc
+) {
+    for (size_t i = 0; i < EXCEPTION_SIGNAL_COUNT; ++i) {
+        sigaction(EXCEPTION_SIGNALS[i], &ctx->previous_handlers[i], nullptr);
+    }
+
+    call_previous_handler(ctx, cc);
+}
+
+// Decides what happens to the crash once the minidump has been written, the
+// return value is passed on to breakpad, which treats the signal as handled
+// if it is true. This runs in the signal handler, so only uses async signal
+// safe functions.
+static bool after_dump(
+    BreakpadContext* ctx,
+    const google_breakpad::ExceptionHandler::CrashContext* cc,
+    bool succeeded
+) {
+    switch (ctx->after_dump) {
+        case AFTER_DUMP_RESTORE_PREVIOUS:
+            return false;
+        case AFTER_DUMP_CALL_PREVIOUS:
+            call_previous_handler(ctx, cc);
+            return true;
+        case AFTER_DUMP_EXIT:
+            _exit(ctx->after_dump_exit_code);
+        default:
+            return succeeded;
+    }
+}
+
+// Applies the settings that breakpad takes as part of the descriptor, which has
+// to be done again whenever the descriptor is replaced
+static google_breakpad::MinidumpDescriptor with_settings(
+    google_breakpad::MinidumpDescriptor descriptor,
+    const MinidumpSettings& settings
+) {
+    if (settings.size_limit > 0) {
+        descriptor.set_size_limit((off_t)settings.size_limit);
+    }
+    descriptor.set_sanitize_stacks(settings.sanitize_stacks);
+    return descriptor;
+}
+
+// Opens any of the reserved file descriptors that aren't open, this is also
+// called from the signal handler once the minidump has been written, so only
+// uses async signal safe functions
+static void reserve_fds(BreakpadContext* ctx) {
+    int saved_errno = errno;
+
+    // An eventfd doesn't depend on the filesystem, eg. /dev/null being
+    // available in a sandbox
+    for (size_t i = 0; i < RESERVED_FD_COUNT; ++i) {
+        if (ctx->reserved_fds[i] < 0) {
+            ctx->reserved_fds[i] = eventfd(0, EFD_CLOEXEC);
+        }
+    }
+
+    errno = saved_errno;
+}
+
+static void release_reserved_fds(BreakpadContext* ctx) {
+    int saved_errno = errno;
+
+    for (size_t i = 0; i < RESERVED_FD_COUNT; ++i) {
+        if (ctx->reserved_fds[i] >= 0) {
+            close(ctx->reserved_fds[i]);
+            ctx->reserved_fds[i] = -1;
+        }
+    }
+
+    errno = saved_errno;
+}
+
+// Breakpad makes the process dumpable and allows the dumper child to ptrace
+// it before writing the minidump, but never resets either, so we do that once
+// the minidump has been written, along with the nice value we changed
+static void restore_privileges(BreakpadContext* ctx) {
+    if (ctx->prior_dumpable >= 0) {
+        prctl(PR_SET_DUMPABLE, ctx->prior_dumpable, 0, 0, 0);
+        ctx->prior_dumpable = -1;
+    }
+
+    prctl(PR_SET_PTRACER, 0, 0, 0, 0);
+
+    if (ctx->niced_thread != 0) {
+        setpriority(PRIO_PROCESS, ctx->niced_thread, ctx->prior_nice);
+        ctx->niced_thread = 0;
+    }
+}
+#endif
+
 struct ExcHandler {
     BreakpadContext* bp_ctx;
     google_breakpad::ExceptionHandler* handler;
 };
 
+// A crash generation server that writes minidumps for the processes that
+// crash, so that the minidump doesn't have to be written by the crashing
+// process itself
+struct CrashServer {
+    server_dump_callback callback;
+    void* callback_ctx;
+    google_breakpad::CrashGenerationServer* server;
+};
+
 extern "C" {
     ExcHandler* attach_exception_handler(
         const CHAR_TYPE* path,
         size_t path_len,
         dump_callback crash_cb,
+        filter_callback filter_cb,
         void* callback_ctx,
-        uint32_t install_options
+        uint32_t install_options,
+        int minidump_fd,
+        const MinidumpSettings* settings,
+        const CrashServerChannel* channel
     ) {
         auto* bp_ctx = new BreakpadContext;
         bp_ctx->callback = crash_cb;
+        bp_ctx->filter = filter_cb;
         bp_ctx->callback_ctx = callback_ctx;
+        memset(&bp_ctx->crash_info, 0, sizeof(CrashInfo));
+        memset(&bp_ctx->settings, 0, sizeof(MinidumpSettings));
+        if (settings != nullptr) {
+            bp_ctx->settings = *settings;
+        }
+        bp_ctx->handler = nullptr;
+        bp_ctx->prior_dumpable = -1;
+        bp_ctx->dumper_cgroup_fd = -1;
+        #if defined(TARGET_OS_LINUX)
+            bp_ctx->niced_thread = 0;
+            bp_ctx->prior_nice = 0;
+            for (size_t i = 0; i < RESERVED_FD_COUNT; ++i) {
+                bp_ctx->reserved_fds[i] = -1;
+            }
+            reserve_fds(bp_ctx);
+
+            bp_ctx->after_dump = AFTER_DUMP_DEFAULT;
+            bp_ctx->after_dump_exit_code = 0;
+            bp_ctx->crash_context = nullptr;
+            bp_ctx->simulating = false;
+            bp_ctx->vetoed = false;
+            bp_ctx->observer = nullptr;
+            // Breakpad installs its handlers when the first handler is
+            // attached, and keeps the previous ones to itself
+            for (size_t i = 0; i < EXCEPTION_SIGNAL_COUNT; ++i) {
+                memset(&bp_ctx->previous_handlers[i], 0, sizeof(struct sigaction));
+                sigaction(EXCEPTION_SIGNALS[i], nullptr, &bp_ctx->previous_handlers[i]);
+            }
+        #endif
 
         #if TARGET_OS_WINDOWS
             std::wstring dump_path(reinterpret_cast<const wchar_t*>(path), path_len);
 
+            auto filter = [](
+                void* context,
+                EXCEPTION_POINTERS* exinfo,
+                MDRawAssertionInfo*
+            ) -> bool {
+                auto* ctx = (BreakpadContext*)context;
+
+                // The filter is invoked on the crashing thread, unlike the
+                // crash callback, so this is the only place the last error
+                // can be retrieved, and it must be done before any other calls
+                // that might change it
+                ctx->crash_info.last_error = GetLastError();
+
+                if (exinfo != nullptr && exinfo->ExceptionRecord != nullptr) {
+                    auto* record = exinfo->ExceptionRecord;
+
+                    ctx->crash_info.code = record->ExceptionCode;
+                    ctx->crash_info.address = reinterpret_cast<uint64_t>(record->ExceptionAddress);
+
+                    DWORD num_parameters = record->NumberParameters;
+                    if (num_parameters > 3) {
+                        num_parameters = 3;
+                    }
+
+                    ctx->crash_info.num_parameters = num_parameters;
+                    for (DWORD i = 0; i < num_parameters; ++i) {
+                        ctx->crash_info.parameters[i] = record->ExceptionInformation[i];
+                    }
+                }
+
+                return should_dump(ctx);
+            };
+
             auto crash_callback = [](
                 const wchar_t* breakpad_dump_path,
                 const wchar_t* minidump_id,
@@ -48,6 +538,12 @@ extern "C" {
             ) -> bool {
                 auto* ctx = (BreakpadContext*)context;
 
+                // The minidump was written by the crash generation server, so
+                // the path breakpad gives us doesn't exist
+                if (ctx->handler != nullptr && ctx->handler->IsOutOfProcess()) {
+                    return succeeded;
+                }
+
                 // We have to construct the full path to the minidump file ourselves
                 google_breakpad::wstring dump_path(breakpad_dump_path);
                 dump_path.push_back('/');
@@ -57,22 +553,42 @@ extern "C" {
                 ctx->callback(
                     reinterpret_cast<const CHAR_TYPE*>(dump_path.data()),
                     dump_path.size(),
+                    succeeded,
+                    &ctx->crash_info,
                     ctx->callback_ctx
                 );
 
                 return succeeded;
             };
 
+            // On Windows dbghelp can capture memory referenced from the
+            // registers and the stack of every thread for us
+            MINIDUMP_TYPE dump_type = bp_ctx->settings.capture_register_memory
+                ? static_cast<MINIDUMP_TYPE>(MiniDumpNormal | MiniDumpWithIndirectlyReferencedMemory)
+                : MiniDumpNormal;
+
+            std::wstring pipe_name;
+            if (channel != nullptr) {
+                pipe_name.assign(reinterpret_cast<const wchar_t*>(channel->name), channel->name_len);
+            }
+
             auto* handler = new google_breakpad::ExceptionHandler(
-                dump_path, // Directory to store the minidump in
-                nullptr, // Minidump write filter, might be used later
+                dump_path, // Directory to store the minidump in, if the crash server can't be reached
+                filter, // Decides if a minidump should be written at all
                 crash_callback, // Callback invoked after the minidump has been written
                 bp_ctx, // Callback context
-                google_breakpad::ExceptionHandler::HANDLER_EXCEPTION // Write minidumps when a structured exception occurs
+                google_breakpad::ExceptionHandler::HANDLER_EXCEPTION, // Write minidumps when a structured exception occurs
+                dump_type, // What to include in the minidump
+                channel != nullptr ? pipe_name.c_str() : nullptr, // The out of process crash server, if any
+                nullptr // No custom client info
             );
         #elif defined(TARGET_OS_MAC)
             std::string dump_path(reinterpret_cast<const char*>(path), path_len);
 
+            auto filter = [](void* context) -> bool {
+                return should_dump((BreakpadContext*)context);
+            };
+
             auto crash_callback = [](
                 const char* dump_dir,
                 const char* minidump_id,
@@ -81,6 +597,12 @@ extern "C" {
             ) -> bool {
                 auto* ctx = (BreakpadContext*)context;
 
+                // The minidump was written by the crash generation server, so
+                // the path breakpad gives us doesn't exist
+                if (ctx->handler != nullptr && ctx->handler->IsOutOfProcess()) {
+                    return succeeded;
+                }
+
                 std::string dump_path(dump_dir);
                 dump_path.push_back('/');
                 dump_path.append(minidump_id);
@@ -89,23 +611,86 @@ extern "C" {
                 ctx->callback(
                     reinterpret_cast<const CHAR_TYPE*>(dump_path.data()),
                     dump_path.size(),
+                    succeeded,
+                    &ctx->crash_info,
                     ctx->callback_ctx
                 );
 
                 return succeeded;
             };
 
+            std::string port_name;
+            if (channel != nullptr) {
+                port_name.assign(reinterpret_cast<const char*>(channel->name), channel->name_len);
+            }
+
+            // Breakpad replaces the task's exception ports for every exception
+            // type it handles, so the ports for the types that are forwarded
+            // are saved beforehand
+            exception_mask_t prior_masks[EXC_TYPES_COUNT];
+            mach_port_t prior_ports[EXC_TYPES_COUNT];
+            exception_behavior_t prior_behaviors[EXC_TYPES_COUNT];
+            thread_state_flavor_t prior_flavors[EXC_TYPES_COUNT];
+            mach_msg_type_number_t prior_count = 0;
+
+            if (bp_ctx->settings.forward_exception_mask != 0) {
+                prior_count = EXC_TYPES_COUNT;
+                if (task_get_exception_ports(
+                    mach_task_self(),
+                    bp_ctx->settings.forward_exception_mask,
+                    prior_masks,
+                    &prior_count,
+                    prior_ports,
+                    prior_behaviors,
+                    prior_flavors
+                ) != KERN_SUCCESS) {
+                    prior_count = 0;
+                }
+            }
+
             auto* handler = new google_breakpad::ExceptionHandler(
                 dump_path, // Directory to store the minidump in
-                nullptr, // Minidump write filter, might be used later
+                filter, // Decides if a minidump should be written at all
                 crash_callback, // Callback invoked after the minidump has been written
                 bp_ctx, // Callback context
                 static_cast<google_breakpad::InstallOptions>(install_options), // Which handlers to install, ignored on other platforms
-                nullptr // Don't start a separate process, handle crashes in the same process
+                channel != nullptr ? port_name.c_str() : nullptr // The out of process crash server, if any
             );
+
+            // Hand the forwarded exception types back to their prior ports. If
+            // those don't handle an exception, or there weren't any, the kernel
+            // turns it into a signal, which is still caught if the signal
+            // handler is installed
+            for (mach_msg_type_number_t i = 0; i < prior_count; ++i) {
+                task_set_exception_ports(
+                    mach_task_self(),
+                    prior_masks[i],
+                    prior_ports[i],
+                    prior_behaviors[i],
+                    prior_flavors[i]
+                );
+            }
         #elif defined(TARGET_OS_LINUX)
             std::string dump_path(reinterpret_cast<const char*>(path), path_len);
-            google_breakpad::MinidumpDescriptor descriptor(dump_path);
+            // If we've been given a file descriptor, the minidump is written
+            // to it instead of a new file in the directory
+            google_breakpad::MinidumpDescriptor descriptor = with_settings(
+                minidump_fd >= 0
+                    ? google_breakpad::MinidumpDescriptor(minidump_fd)
+                    : google_breakpad::MinidumpDescriptor(dump_path),
+                bp_ctx->settings
+            );
+
+            auto filter = [](void* context) -> bool {
+                auto* ctx = (BreakpadContext*)context;
+
+                // This is called before breakpad makes the process dumpable,
+                // but also before it has recorded the details of the signal,
+                // so the crash is only rejected from the crash handler hook,
+                // once the observer has been notified
+                ctx->prior_dumpable = prctl(PR_GET_DUMPABLE, 0, 0, 0, 0);
+                return true;
+            };
 
             auto crash_callback = [](
                 const google_breakpad::MinidumpDescriptor& descriptor,
@@ -114,29 +699,111 @@ extern "C" {
             ) -> bool {
                 auto* ctx = (BreakpadContext*)context;
 
-                auto* dump_path = descriptor.path();
+                restore_privileges(ctx);
+                reserve_fds(ctx);
+
+                // A descriptor for a file descriptor has no path, the caller
+                // knows which file it gave us
+                const char* dump_path = descriptor.IsFD() ? "" : descriptor.path();
 
                 ctx->callback(
                     reinterpret_cast<const CHAR_TYPE*>(dump_path),
                     strlen(dump_path),
+                    succeeded,
+                    &ctx->crash_info,
                     ctx->callback_ctx
                 );
 
-                return succeeded;
+                // Requested minidumps aren't crashes, so the process carries on
+                auto* cc = ctx->crash_context;
+                ctx->crash_context = nullptr;
+                if (cc == nullptr) {
+                    return succeeded;
+                }
+
+                return after_dump(ctx, cc, succeeded);
             };
 
             auto* handler = new google_breakpad::ExceptionHandler(
                 descriptor, // Decides where to place the minidump file
-                nullptr, // Minidump write filter, might be used later
+                filter, // Decides if a minidump should be written at all
                 crash_callback, // Callback invoked after the minidump has been written
                 bp_ctx, // Callback context
                 true, // Actually write minidumps when unhandled signals occur
-                -1 // Don't start a separate process, handle crashes in the same process
+                channel != nullptr ? channel->fd : -1 // The out of process crash server, if any
             );
+
+            // Breakpad always installs its handler for all of the signals, so
+            // the previous handlers are put back for the ones it shouldn't hook
+            for (size_t i = 0; i < EXCEPTION_SIGNAL_COUNT; ++i) {
+                if (bp_ctx->settings.unhooked_signals & (1u << EXCEPTION_SIGNALS[i])) {
+                    sigaction(EXCEPTION_SIGNALS[i], &bp_ctx->previous_handlers[i], nullptr);
+                }
+            }
+
+            // Invoked from the signal handler before the minidump is written,
+            // we record the details of the crash and let breakpad continue,
+            // unless our filter rejects the crash, in which case returning
+            // true stops breakpad from writing the minidump
+            handler->set_crash_handler([](
+                const void* crash_context,
+                size_t crash_context_size,
+                void* context
+            ) -> bool {
+                auto* ctx = (BreakpadContext*)context;
+                const google_breakpad::ExceptionHandler::CrashContext* cc = nullptr;
+
+                if (crash_context_size >= sizeof(google_breakpad::ExceptionHandler::CrashContext)) {
+                    cc = (const google_breakpad::ExceptionHandler::CrashContext*)crash_context;
+
+                    // Before anything else, so the observer is notified even
+                    // if the crash is rejected, or writing the minidump fails
+                    signal_observer observer = ctx->observer;
+                    if (observer != nullptr && !ctx->simulating) {
+                        observer(
+                            cc->siginfo.si_signo,
+                            reinterpret_cast<uint64_t>(cc->siginfo.si_addr),
+                            ctx->callback_ctx
+                        );
+                    }
+
+                    ctx->crash_info.signal = cc->siginfo.si_signo;
+                    ctx->crash_info.code = (uint32_t)cc->siginfo.si_code;
+                    ctx->crash_info.address = reinterpret_cast<uint64_t>(cc->siginfo.si_addr);
+                    ctx->crash_info.thread_id = (uint32_t)cc->tid;
+                    ctx->crash_info.stack_pointer = stack_pointer_from_context(&cc->context);
+                }
+
+                ctx->vetoed = !should_dump(ctx)
+                    || (ctx->settings.respect_non_dumpable && ctx->prior_dumpable == 0);
+                if (ctx->vetoed) {
+                    // Breakpad has already made the process dumpable
+                    restore_privileges(ctx);
+                    ctx->crash_context = nullptr;
+                    if (cc != nullptr && !ctx->simulating) {
+                        pass_on_rejected(ctx, cc);
+                    }
+                    return true;
+                }
+
+                ctx->crash_context = ctx->simulating ? nullptr : cc;
+                if (cc != nullptr && ctx->settings.capture_register_memory && ctx->handler != nullptr) {
+                    register_memory_from_context(ctx->handler, &cc->context);
+                }
+
+                // Breakpad opens the minidump file, and the dumper child the
+                // files it reads, by path, so the only way to make sure there
+                // are descriptors left for them is to free up our reserve
+                release_reserved_fds(ctx);
+                prepare_dumper(ctx);
+                return false;
+            });
         #else
             #error "Unknown target platform"
         #endif
 
+        bp_ctx->handler = handler;
+
         auto* exc_handler = new ExcHandler;
         exc_handler->bp_ctx = bp_ctx;
         exc_handler->handler = handler;
@@ -144,9 +811,350 @@ extern "C" {
         return exc_handler;
     }
 
+    #if defined(TARGET_OS_LINUX)
+    bool write_minidump_from_core(
+        const char* core_path,
+        const char* procfs_path,
+        const char* minidump_path
+    ) {
+        // This is the same as breakpad's core2md tool
+        google_breakpad::MappingList mappings;
+        google_breakpad::AppMemoryList app_memory;
+        google_breakpad::LinuxCoreDumper dumper(0, core_path, procfs_path);
+
+        return google_breakpad::WriteMinidump(minidump_path, mappings, app_memory, &dumper);
+    }
+    #endif
+
+    bool register_app_memory(ExcHandler* handler, const void* ptr, size_t len) {
+        #if defined(TARGET_OS_MAC)
+            // The mac handler has no way to include extra memory
+            (void)handler;
+            (void)ptr;
+            (void)len;
+            return false;
+        #else
+            handler->handler->RegisterAppMemory(const_cast<void*>(ptr), len);
+            return true;
+        #endif
+    }
+
+    void unregister_app_memory(ExcHandler* handler, const void* ptr) {
+        #if defined(TARGET_OS_MAC)
+            (void)handler;
+            (void)ptr;
+        #else
+            handler->handler->UnregisterAppMemory(const_cast<void*>(ptr));
+        #endif
+    }
+
+    #if defined(TARGET_OS_LINUX)
+    void set_dumper_cgroup(ExcHandler* handler, int cgroup_procs_fd) {
+        handler->bp_ctx->dumper_cgroup_fd = cgroup_procs_fd;
+    }
+
+    void set_after_dump(ExcHandler* handler, int after_dump, int exit_code) {
+        handler->bp_ctx->after_dump_exit_code = exit_code;
+        handler->bp_ctx->after_dump = after_dump;
+    }
+
+    void set_signal_observer(ExcHandler* handler, signal_observer observer) {
+        handler->bp_ctx->observer = observer;
+    }
+
+    void set_minidump_fd(ExcHandler* handler, int minidump_fd) {
+        handler->handler->set_minidump_descriptor(with_settings(
+            google_breakpad::MinidumpDescriptor(minidump_fd),
+            handler->bp_ctx->settings
+        ));
+    }
+
+    void add_mapping_info(
+        ExcHandler* handler,
+        const char* name,
+        size_t name_len,
+        const uint8_t identifier[16],
+        uintptr_t start_address,
+        size_t mapping_size,
+        size_t file_offset
+    ) {
+        handler->handler->AddMappingInfo(
+            std::string(name, name_len),
+            identifier,
+            start_address,
+            mapping_size,
+            file_offset
+        );
+    }
+    #endif
+
+    bool generate_minidump(ExcHandler* handler) {
+        auto* ctx = handler->bp_ctx;
+        // Details from a previous dump must not leak into this one
+        memset(&ctx->crash_info, 0, sizeof(CrashInfo));
+
+        #if defined(TARGET_OS_LINUX)
+            // Breakpad makes the process dumpable here as well, but without
+            // invoking the filter, so the flag is recorded here instead
+            ctx->prior_dumpable = prctl(PR_GET_DUMPABLE, 0, 0, 0, 0);
+            if (ctx->settings.respect_non_dumpable && ctx->prior_dumpable == 0) {
+                ctx->prior_dumpable = -1;
+                return false;
+            }
+
+            // A minidump can be requested because the process is running out
+            // of file descriptors, so the reserve is freed up for it as well
+            release_reserved_fds(ctx);
+            bool written = handler->handler->WriteMinidump();
+            // The crash callback already restores them, but it isn't invoked
+            // if the minidump is requested from a crash generation server
+            restore_privileges(ctx);
+            reserve_fds(ctx);
+            return written;
+        #else
+            return handler->handler->WriteMinidump();
+        #endif
+    }
+
+    bool generate_minidump_with_exception(
+        ExcHandler* handler,
+        int32_t signal,
+        uint32_t code,
+        uint64_t address
+    ) {
+        #if TARGET_OS_WINDOWS
+            // The filter isn't invoked when writing a minidump for an
+            // exception, so the crash info is recorded here instead
+            auto* ctx = handler->bp_ctx;
+            memset(&ctx->crash_info, 0, sizeof(CrashInfo));
+            ctx->crash_info.last_error = GetLastError();
+            ctx->crash_info.code = code;
+            ctx->crash_info.address = address;
+
+            CONTEXT context;
+            RtlCaptureContext(&context);
+
+            EXCEPTION_RECORD record;
+            memset(&record, 0, sizeof(EXCEPTION_RECORD));
+            record.ExceptionCode = code;
+            record.ExceptionAddress = reinterpret_cast<PVOID>(address);
+
+            EXCEPTION_POINTERS exinfo = { &record, &context };
+
+            return handler->handler->WriteMinidumpForException(&exinfo);
+        #elif defined(TARGET_OS_MAC)
+            // The mac handler can only write an exception stream for mach
+            // exceptions it has actually received
+            (void)handler;
+            (void)signal;
+            (void)code;
+            (void)address;
+            return false;
+        #elif defined(TARGET_OS_LINUX)
+            if (signal == 0) {
+                signal = SIGABRT;
+            }
+
+            // This is the same as SimulateSignalDelivery, but with the
+            // details of the signal filled in
+            siginfo_t info;
+            memset(&info, 0, sizeof(siginfo_t));
+            info.si_signo = signal;
+            info.si_code = (int)code;
+            info.si_addr = reinterpret_cast<void*>(address);
+            // Breakpad only trusts user signals sent by the process itself
+            info.si_pid = getpid();
+
+            ucontext_t context;
+            getcontext(&context);
+
+            // A crash the filter rejected is reported as handled by breakpad,
+            // but no minidump was written for it
+            handler->bp_ctx->simulating = true;
+            handler->bp_ctx->vetoed = false;
+            bool handled = handler->handler->HandleSignal(signal, &info, &context);
+            handler->bp_ctx->simulating = false;
+            // Same as for generate_minidump, the process keeps running
+            restore_privileges(handler->bp_ctx);
+            return handled && !handler->bp_ctx->vetoed;
+        #endif
+    }
+
     void detach_exception_handler(ExcHandler* handler) {
+        #if defined(TARGET_OS_LINUX)
+            release_reserved_fds(handler->bp_ctx);
+        #endif
         delete handler->bp_ctx;
         delete handler->handler;
         delete handler;
     }
+
+    bool is_out_of_process(const ExcHandler* handler) {
+        return handler->handler->IsOutOfProcess();
+    }
+
+    bool is_debugger_attached() {
+        #if TARGET_OS_WINDOWS
+            return IsDebuggerPresent() != FALSE;
+        #elif defined(TARGET_OS_MAC)
+            struct kinfo_proc info;
+            memset(&info, 0, sizeof(info));
+            int mib[4] = { CTL_KERN, KERN_PROC, KERN_PROC_PID, getpid() };
+            size_t size = sizeof(info);
+            if (sysctl(mib, 4, &info, &size, nullptr, 0) != 0) {
+                return false;
+            }
+            return (info.kp_proc.p_flag & P_TRACED) != 0;
+        #elif defined(TARGET_OS_LINUX)
+            // This is called from the signal handler, so the status is read
+            // into a buffer on the stack rather than with anything that
+            // allocates
+            int fd = open("/proc/self/status", O_RDONLY | O_CLOEXEC);
+            if (fd < 0) {
+                return false;
+            }
+
+            char status[4096];
+            ssize_t len = read(fd, status, sizeof(status) - 1);
+            close(fd);
+            if (len <= 0) {
+                return false;
+            }
+            status[len] = '\0';
+
+            // The pid of the process tracing us, 0 if there is none
+            const char* tracer = strstr(status, "TracerPid:");
+            if (tracer == nullptr) {
+                return false;
+            }
+            tracer += strlen("TracerPid:");
+            while (*tracer == ' ' || *tracer == '\t') {
+                ++tracer;
+            }
+            return *tracer >= '1' && *tracer <= '9';
+        #endif
+    }
+
+    #if defined(TARGET_OS_LINUX)
+    bool create_report_channel(int* server_fd, int* client_fd) {
+        return google_breakpad::CrashGenerationServer::CreateReportChannel(server_fd, client_fd);
+    }
+    #endif
+
+    CrashServer* start_crash_generation_server(
+        const CHAR_TYPE* path,
+        size_t path_len,
+        const CrashServerChannel* channel,
+        server_dump_callback dump_cb,
+        void* callback_ctx
+    ) {
+        auto* crash_server = new CrashServer;
+        crash_server->callback = dump_cb;
+        crash_server->callback_ctx = callback_ctx;
+
+        #if TARGET_OS_WINDOWS
+            std::wstring dump_path(reinterpret_cast<const wchar_t*>(path), path_len);
+            std::wstring pipe_name(reinterpret_cast<const wchar_t*>(channel->name), channel->name_len);
+
+            auto dump_callback = [](
+                void* context,
+                const google_breakpad::ClientInfo*,
+                const std::wstring* file_path
+            ) {
+                auto* cs = (CrashServer*)context;
+
+                cs->callback(
+                    reinterpret_cast<const CHAR_TYPE*>(file_path->data()),
+                    file_path->size(),
+                    cs->callback_ctx
+                );
+            };
+
+            auto* server = new google_breakpad::CrashGenerationServer(
+                pipe_name, // The pipe clients connect to
+                nullptr, // Default security attributes for the pipe
+                nullptr, // No connect callback
+                nullptr, // Connect callback context
+                dump_callback, // Callback invoked after a minidump has been written
+                crash_server, // Dump callback context
+                nullptr, // No exit callback
+                nullptr, // Exit callback context
+                nullptr, // No upload request callback
+                nullptr, // Upload request callback context
+                true, // Actually write minidumps when clients request them
+                &dump_path // Directory to store the minidumps in
+            );
+        #elif defined(TARGET_OS_MAC)
+            std::string dump_path(reinterpret_cast<const char*>(path), path_len);
+            std::string port_name(reinterpret_cast<const char*>(channel->name), channel->name_len);
+
+            auto dump_callback = [](
+                void* context,
+                const google_breakpad::ClientInfo&,
+                const std::string& file_path
+            ) {
+                auto* cs = (CrashServer*)context;
+
+                cs->callback(
+                    reinterpret_cast<const CHAR_TYPE*>(file_path.data()),
+                    file_path.size(),
+                    cs->callback_ctx
+                );
+            };
+
+            auto* server = new google_breakpad::CrashGenerationServer(
+                port_name.c_str(), // The mach port clients send their crashes to
+                nullptr, // No filter
+                nullptr, // Filter context
+                dump_callback, // Callback invoked after a minidump has been written
+                crash_server, // Dump callback context
+                nullptr, // No exit callback
+                nullptr, // Exit callback context
+                true, // Actually write minidumps when clients request them
+                dump_path // Directory to store the minidumps in
+            );
+        #elif defined(TARGET_OS_LINUX)
+            std::string dump_path(reinterpret_cast<const char*>(path), path_len);
+
+            auto dump_callback = [](
+                void* context,
+                const google_breakpad::ClientInfo*,
+                const std::string* file_path
+            ) {
+                auto* cs = (CrashServer*)context;
+
+                cs->callback(
+                    reinterpret_cast<const CHAR_TYPE*>(file_path->data()),
+                    file_path->size(),
+                    cs->callback_ctx
+                );
+            };
+
+            auto* server = new google_breakpad::CrashGenerationServer(
+                channel->fd, // The server end of the socket pair
+                dump_callback, // Callback invoked after a minidump has been written
+                crash_server, // Dump callback context
+                nullptr, // No exit callback
+                nullptr, // Exit callback context
+                true, // Actually write minidumps when clients request them
+                &dump_path // Directory to store the minidumps in
+            );
+        #endif
+
+        crash_server->server = server;
+
+        if (!server->Start()) {
+            delete server;
+            delete crash_server;
+            return nullptr;
+        }
+
+        return crash_server;
+    }
+
+    void stop_crash_generation_server(CrashServer* server) {
+        // The server is stopped when it is destroyed
+        delete server->server;
+        delete server;
+    }
 }
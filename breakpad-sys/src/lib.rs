@@ -3,36 +3,278 @@ pub struct ExceptionHandler {
     _unused: [u8; 0],
 }
 
+#[repr(C)]
+pub struct CrashGenerationServer {
+    _unused: [u8; 0],
+}
+
 #[cfg(not(windows))]
 pub type PathChar = u8;
 #[cfg(windows)]
 pub type PathChar = u16;
 
+/// Details about a crash, fields that are not known on the current platform
+/// are 0
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CrashInfo {
+    /// The signal that caused the crash, only on Linux/Android
+    pub signal: i32,
+    /// The `si_code` on Linux/Android, or the exception code on Windows
+    pub code: u32,
+    /// The faulting address on Linux/Android, or the exception address on Windows
+    pub address: u64,
+    /// The value of `GetLastError` on the crashing thread, only on Windows
+    pub last_error: u32,
+    /// The number of valid entries in `parameters`
+    pub num_parameters: u32,
+    /// The first parameters of the exception record, only on Windows
+    pub parameters: [u64; 3],
+    /// The id of the crashing thread, only on Linux/Android
+    pub thread_id: u32,
+    /// The stack pointer of the crashing thread when it crashed, only on
+    /// Linux/Android
+    pub stack_pointer: u64,
+}
+
+/// Settings that control the contents of the minidump
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MinidumpSettings {
+    /// Capture a small window of memory around every register of the crashing
+    /// thread that points to readable memory. On Windows this instead uses
+    /// dbghelp's `MiniDumpWithIndirectlyReferencedMemory`, and it is ignored on
+    /// macOS
+    pub capture_register_memory: bool,
+    /// Don't write a minidump if the process has been made non-dumpable, eg.
+    /// via `PR_SET_DUMPABLE`, only applies on Linux/Android
+    pub respect_non_dumpable: bool,
+    /// The mach exceptions, as an `exception_mask_t`, that are left to the
+    /// exception ports that were registered before the handler, only applies
+    /// on macOS
+    pub forward_exception_mask: u32,
+    /// Set the nice value of the crashing thread, which the child process that
+    /// writes the minidump inherits, to `dumper_nice`, only applies on
+    /// Linux/Android
+    pub set_dumper_nice: bool,
+    pub dumper_nice: i32,
+    /// The maximum size of the minidump in bytes, 0 for no limit, only applies
+    /// on Linux/Android
+    pub size_limit: u64,
+    /// Replace everything on the stacks that isn't a pointer into a mapped
+    /// module with a placeholder, only applies on Linux/Android
+    pub sanitize_stacks: bool,
+    /// The signals, as a mask of `1 << signal`, that are left to the handlers
+    /// that were installed before breakpad's, only applies on Linux/Android
+    pub unhooked_signals: u32,
+}
+
 pub type CrashCallback = extern "C" fn(
     minidump_path: *const PathChar,
     minidump_path_len: usize,
+    succeeded: bool,
+    crash_info: *const CrashInfo,
     ctx: *mut std::ffi::c_void,
 );
 
+/// Invoked before a minidump is written, returning `false` prevents the
+/// minidump from being written, and the crash is passed on as if no handler
+/// was installed. The details of the crash are available on Linux/Android and
+/// Windows, on macOS they are always 0.
+///
+/// On Linux/Android this is invoked from breakpad's crash handler hook, as
+/// its filter runs before the details of the signal are known, so a rejected
+/// crash is passed on by calling the handler that was installed before
+/// breakpad's for the signal directly.
+pub type FilterCallback =
+    extern "C" fn(crash_info: *const CrashInfo, ctx: *mut std::ffi::c_void) -> bool;
+
+/// Invoked first thing in the signal handler, before the crash can be rejected
+/// or the minidump is written, with the signal and the faulting address, only
+/// on Linux/Android
+pub type SignalObserver = extern "C" fn(signal: i32, address: u64, ctx: *mut std::ffi::c_void);
+
+/// Invoked by a crash generation server on its own thread when it has written
+/// a minidump for one of its clients
+pub type ServerDumpCallback = extern "C" fn(
+    minidump_path: *const PathChar,
+    minidump_path_len: usize,
+    ctx: *mut std::ffi::c_void,
+);
+
+/// The crash generation server that a client sends its crashes to, rather than
+/// writing the minidump in the crashing process
+#[repr(C)]
+pub struct CrashServerChannel {
+    /// The name of the pipe on Windows, or of the mach port on macOS
+    pub name: *const PathChar,
+    pub name_len: usize,
+    /// One end of the socket pair created with [`create_report_channel`], only
+    /// on Linux/Android
+    pub fd: std::os::raw::c_int,
+}
+
 pub const INSTALL_NO_HANDLER: u32 = 0x0;
 pub const INSTALL_EXCEPTION_HANDLER: u32 = 0x1;
 pub const INSTALL_SIGNAL_HANDLER: u32 = 0x2;
 pub const INSTALL_BOTH_HANDLERS: u32 = INSTALL_EXCEPTION_HANDLER | INSTALL_SIGNAL_HANDLER;
 
+/// Breakpad's default, the signal is handled by its default action if the
+/// minidump was written, otherwise the previous handlers are restored
+pub const AFTER_DUMP_DEFAULT: i32 = 0;
+/// The handlers that were installed before breakpad's are restored, and the
+/// signal is passed on to them
+pub const AFTER_DUMP_RESTORE_PREVIOUS: i32 = 1;
+/// The handler that was installed before breakpad's for the signal is called
+/// directly, before the signal is handled by its default action
+pub const AFTER_DUMP_CALL_PREVIOUS: i32 = 2;
+/// The process exits with the exit code passed to [`set_after_dump`]
+pub const AFTER_DUMP_EXIT: i32 = 3;
+
 extern "C" {
     /// Creates and attaches an exception handler that will monitor this process
-    /// for crashes
+    /// for crashes. The `crash_callback_ctx` is passed to both the
+    /// `crash_callback` and `filter_callback`.
     ///
     /// Note: The `install_options` only applies on MacOS/iOS, it is ignored
     /// for all other platforms.
+    ///
+    /// Note: The `minidump_fd` only applies on Linux/Android, if it is not -1
+    /// the minidump is written to it instead of a file in `path`, and the path
+    /// passed to the `crash_callback` is empty. The minidump is written at
+    /// absolute offsets from the start of the file, without truncating it.
+    /// The descriptor is never closed by breakpad, so it must stay open until
+    /// the handler is detached.
+    ///
+    /// `settings` may be null, in which case the defaults are used.
+    ///
+    /// If `channel` is not null, crashes are sent to the crash generation
+    /// server it identifies, which writes the minidump, and the
+    /// `crash_callback` is not invoked.
     pub fn attach_exception_handler(
         path: *const PathChar,
         path_len: usize,
         crash_callback: CrashCallback,
+        filter_callback: Option<FilterCallback>,
         crash_callback_ctx: *mut std::ffi::c_void,
         install_options: u32,
+        minidump_fd: std::os::raw::c_int,
+        settings: *const MinidumpSettings,
+        channel: *const CrashServerChannel,
     ) -> *mut ExceptionHandler;
 
+    /// Writes a minidump from a core dump, using the copies of the `auxv`,
+    /// `cmdline`, `environ`, `maps`, and `status` files from the `/proc/<pid>`
+    /// of the crashed process in `procfs_path`. All paths are nul terminated.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn write_minidump_from_core(
+        core_path: *const std::os::raw::c_char,
+        procfs_path: *const std::os::raw::c_char,
+        minidump_path: *const std::os::raw::c_char,
+    ) -> bool;
+
+    /// Includes the memory region in the memory list of every minidump that is
+    /// written, until it is unregistered. Returns `false` on macOS, where this
+    /// is not supported.
+    pub fn register_app_memory(
+        handler: *mut ExceptionHandler,
+        ptr: *const std::ffi::c_void,
+        len: usize,
+    ) -> bool;
+
+    /// Stops including the memory region that starts at `ptr` in minidumps
+    pub fn unregister_app_memory(handler: *mut ExceptionHandler, ptr: *const std::ffi::c_void);
+
+    /// Moves the process to a cgroup before a minidump is written by writing
+    /// its pid to the descriptor, which must be the `cgroup.procs` file of the
+    /// cgroup, or -1 to not move it. The descriptor is not owned by breakpad.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_dumper_cgroup(handler: *mut ExceptionHandler, cgroup_procs_fd: std::os::raw::c_int);
+
+    /// Sets the observer that is notified of every crash, or removes it if
+    /// `None`. The observer is passed the crash callback context.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_signal_observer(handler: *mut ExceptionHandler, observer: Option<SignalObserver>);
+
+    /// Sets what happens to a crash once its minidump has been written, one
+    /// of the `AFTER_DUMP_*` constants. Minidumps that are requested rather
+    /// than written for a crash are unaffected.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_after_dump(
+        handler: *mut ExceptionHandler,
+        after_dump: std::os::raw::c_int,
+        exit_code: std::os::raw::c_int,
+    );
+
+    /// Writes minidumps to the descriptor from now on, the same as the
+    /// `minidump_fd` passed to `attach_exception_handler`. The previous
+    /// descriptor, and this one, are never closed by breakpad.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_minidump_fd(handler: *mut ExceptionHandler, minidump_fd: std::os::raw::c_int);
+
+    /// Adds a module to the module list of every minidump that is written, for
+    /// code that was mapped without a file name, eg. by a custom loader
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn add_mapping_info(
+        handler: *mut ExceptionHandler,
+        name: *const std::os::raw::c_char,
+        name_len: usize,
+        identifier: *const [u8; 16],
+        start_address: usize,
+        mapping_size: usize,
+        file_offset: usize,
+    );
+
+    /// Writes a minidump of the current state of the process, without it
+    /// crashing. The `crash_callback` is invoked the same as for a crash.
+    pub fn generate_minidump(handler: *mut ExceptionHandler) -> bool;
+
+    /// Writes a minidump with a synthetic exception, without the process
+    /// crashing. On Linux/Android the minidump is written as if `signal` was
+    /// delivered with the `code` as the `si_code` and the `address` as the
+    /// faulting address, defaulting to `SIGABRT` if `signal` is 0. On Windows
+    /// `code` is the exception code and `address` the exception address, and
+    /// `signal` is ignored. Not supported on macOS, where this returns `false`.
+    pub fn generate_minidump_with_exception(
+        handler: *mut ExceptionHandler,
+        signal: i32,
+        code: u32,
+        address: u64,
+    ) -> bool;
+
     /// Detaches and frees the exception handler
     pub fn detach_exception_handler(handler: *mut ExceptionHandler);
+
+    /// Checks if the handler sends its crashes to a crash generation server.
+    /// On Windows this is `false` if the server could not be reached when the
+    /// handler was attached, in which case minidumps are written in process.
+    pub fn is_out_of_process(handler: *const ExceptionHandler) -> bool;
+
+    /// Checks if a debugger is attached to the process, ie. `TracerPid` in
+    /// `/proc/self/status` on Linux/Android, `IsDebuggerPresent` on Windows,
+    /// and `P_TRACED` on macOS. This is safe to call in a signal handler on
+    /// Linux/Android.
+    pub fn is_debugger_attached() -> bool;
+
+    /// Creates the socket pair that a crash generation server and its client
+    /// communicate over, the client end is meant to be inherited by the client
+    /// process
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn create_report_channel(
+        server_fd: *mut std::os::raw::c_int,
+        client_fd: *mut std::os::raw::c_int,
+    ) -> bool;
+
+    /// Starts a crash generation server that writes minidumps to `path` for
+    /// the clients that crash, returning null if it could not be started
+    pub fn start_crash_generation_server(
+        path: *const PathChar,
+        path_len: usize,
+        channel: *const CrashServerChannel,
+        dump_callback: ServerDumpCallback,
+        dump_callback_ctx: *mut std::ffi::c_void,
+    ) -> *mut CrashGenerationServer;
+
+    /// Stops and frees the crash generation server
+    pub fn stop_crash_generation_server(server: *mut CrashGenerationServer);
 }
@@ -0,0 +1,136 @@
+//! Files that are attached to the envelope of a crash, see
+//! [`BreakpadIntegration::register_attachment`](crate::BreakpadIntegration::register_attachment)
+
+use sentry_core::protocol as proto;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// The name of the manifest in the attachments directory of a crash
+const MANIFEST_FILE: &str = "manifest.json";
+
+#[cfg(feature = "handler")]
+static REGISTERED: std::sync::Mutex<Vec<(PathBuf, String)>> = std::sync::Mutex::new(Vec::new());
+
+/// An attachment that was copied for a crash
+#[derive(Serialize, Deserialize)]
+struct ManifestEntry {
+    /// The name of the copy in the attachments directory
+    stored_as: String,
+    /// The name of the original file
+    filename: String,
+    content_type: String,
+}
+
+#[cfg(feature = "handler")]
+pub(crate) fn register(path: PathBuf, content_type: String) {
+    REGISTERED
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .push((path, content_type));
+}
+
+/// Gets the files that have been registered so far
+#[cfg(feature = "handler")]
+pub(crate) fn registered() -> Vec<(PathBuf, String)> {
+    REGISTERED
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .clone()
+}
+
+/// Gets the directory the attachments for a crash are copied to
+fn attachments_dir(crash_dir: &Path, id: &str) -> PathBuf {
+    crash_dir.join(format!("{}.attachments", id))
+}
+
+/// Copies the registered files to the attachments directory of the crash, as
+/// eg. log files are likely to be overwritten by the time the crash is sent
+#[cfg(feature = "handler")]
+pub(crate) fn capture(crash_dir: &Path, id: &str) {
+    // Another thread could have crashed while registering an attachment
+    let registered = match REGISTERED.try_lock() {
+        Ok(registered) => registered,
+        Err(_) => return,
+    };
+
+    if registered.is_empty() {
+        return;
+    }
+
+    let dir = attachments_dir(crash_dir, id);
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        debug_print!("unable to create {}: {}", dir.display(), e);
+        return;
+    }
+
+    let mut manifest = Vec::new();
+    for (index, (path, content_type)) in registered.iter().enumerate() {
+        let filename = match path.file_name().and_then(|fname| fname.to_str()) {
+            Some(filename) => filename.to_owned(),
+            None => continue,
+        };
+
+        // Prefixed with the index as different files can have the same name
+        let stored_as = format!("{}-{}", index, filename);
+        if let Err(e) = std::fs::copy(path, dir.join(&stored_as)) {
+            debug_print!("unable to copy attachment {}: {}", path.display(), e);
+            continue;
+        }
+
+        let entry = ManifestEntry {
+            stored_as,
+            filename,
+            content_type: content_type.clone(),
+        };
+        if serde_json::to_writer(&mut manifest, &entry).is_ok() {
+            manifest.push(b'\n');
+        }
+    }
+
+    if let Err(e) = std::fs::write(dir.join(MANIFEST_FILE), &manifest) {
+        debug_print!("unable to write attachment manifest: {}", e);
+    }
+}
+
+/// Reads the attachments that were copied for the crash, and removes them
+pub(crate) fn collect(crash_dir: &Path, id: &str) -> Vec<proto::Attachment> {
+    let dir = attachments_dir(crash_dir, id);
+
+    let manifest = match std::fs::read_to_string(dir.join(MANIFEST_FILE)) {
+        Ok(manifest) => manifest,
+        Err(_) => return Vec::new(),
+    };
+
+    let attachments = manifest
+        .lines()
+        .filter_map(|line| serde_json::from_str::<ManifestEntry>(line).ok())
+        .filter_map(|entry| match std::fs::read(dir.join(&entry.stored_as)) {
+            Ok(buffer) => Some(proto::Attachment {
+                buffer,
+                filename: entry.filename,
+                content_type: Some(entry.content_type),
+                ty: None,
+            }),
+            Err(e) => {
+                debug_print!("unable to read attachment {}: {}", entry.stored_as, e);
+                None
+            }
+        })
+        .collect();
+
+    if let Err(e) = std::fs::remove_dir_all(&dir) {
+        debug_print!("failed to remove {}: {}", dir.display(), e);
+    }
+
+    attachments
+}
+
+/// Removes the attachments that were copied for the crash without reading them
+pub(crate) fn remove(crash_dir: &Path, id: &str) {
+    let dir = attachments_dir(crash_dir, id);
+    if dir.exists() {
+        if let Err(e) = std::fs::remove_dir_all(&dir) {
+            debug_print!("failed to remove {}: {}", dir.display(), e);
+        }
+    }
+}
@@ -1,21 +1,629 @@
 use sentry_core::protocol as proto;
 use std::{path::Path, time::SystemTime};
 
-pub use breakpad_handler::InstallOptions;
+use breakpad_handler::{CrashEvent, CrashInfo, DumpError, MinidumpInfo};
+pub use breakpad_handler::{InstallOptions, MinidumpSettings};
+
+/// Adapts a closure that also wants the result of writing the minidump and the
+/// details of the crash to a [`CrashEvent`]
+struct OnCrash<F>(F);
+
+impl<F> OnCrash<F>
+where
+    F: Fn(Result<MinidumpInfo, DumpError>, &CrashInfo) + Send + Sync,
+{
+    /// The handler only calls [`CrashEvent::on_crash_with_result`], so the
+    /// minidump is assumed to have been written for the other methods
+    fn written(&self, minidump_path: std::path::PathBuf, info: &CrashInfo) {
+        let size = std::fs::metadata(&minidump_path).map_or(0, |md| md.len());
+        (self.0)(
+            Ok(MinidumpInfo {
+                minidump_path,
+                size,
+            }),
+            info,
+        );
+    }
+}
+
+impl<F> CrashEvent for OnCrash<F>
+where
+    F: Fn(Result<MinidumpInfo, DumpError>, &CrashInfo) + Send + Sync,
+{
+    fn on_crash(&self, minidump_path: std::path::PathBuf) {
+        self.written(minidump_path, &CrashInfo::default());
+    }
+
+    fn on_crash_with_info(&self, minidump_path: std::path::PathBuf, info: &CrashInfo) {
+        self.written(minidump_path, info);
+    }
+
+    fn on_crash_with_result(&self, result: Result<MinidumpInfo, DumpError>, info: &CrashInfo) {
+        (self.0)(result, info);
+    }
+}
+
+/// What the crash callback needs from the integration, captured when it is
+/// created
+struct CrashContext {
+    hub: std::sync::Weak<sentry_core::Hub>,
+    stats: std::sync::Arc<crate::stats::PersistentStats>,
+    sanitizes_stacks: bool,
+    crash_dir: std::path::PathBuf,
+    policy: crate::CrashDirPolicy,
+}
+
+impl CrashContext {
+    /// Reports the crash a minidump was written, or failed to be written, for
+    fn on_crash(&self, result: Result<MinidumpInfo, DumpError>, info: &CrashInfo) {
+        // The metadata budget is measured from here
+        let crashed_at = std::time::Instant::now();
+        let panic = crate::panic::take();
+        // Hangs, panics, and minidumps that were requested aren't counted as
+        // crashes
+        let stats = if info.hang || info.requested || panic.is_some() {
+            self.stats.get()
+        } else {
+            self.stats.record_crash(info.signal)
+        };
+        // The process keeps running after a hang, a requested minidump, or a
+        // panic that the hook wrote the minidump for
+        let process_continues =
+            info.hang || info.requested || panic.as_ref().is_some_and(|panic| panic.on_demand);
+
+        let (minidump_path, dump_error) = match result {
+            Ok(written) => (written.minidump_path, None),
+            Err(err) => (err.minidump_path().to_owned(), Some(err)),
+        };
+
+        let crash_hub = match self.hub.upgrade() {
+            Some(crash_hub) => crash_hub,
+            None => return,
+        };
+
+        // We **don't** do end_session_with_status as it just
+        // immediately takes the session from the scope and sends it,
+        // but we want to send the event, session update, and minidump
+        // all in the same event
+        // crash_hub.end_session_with_status(protocol::SessionStatus::Crashed);
+
+        let mut extra = std::collections::BTreeMap::new();
+        // We should never get here unless the path is valid utf-8, so this is fine
+        extra.insert(
+            "__breakpad_minidump_path".to_owned(),
+            minidump_path
+                .to_str()
+                .expect("utf-8 path")
+                .to_owned()
+                .into(),
+        );
+        // Lets the transport know that events still need to be sent after
+        // this one
+        if process_continues {
+            extra.insert("__breakpad_process_continues".to_owned(), true.into());
+        }
+
+        let (dump_quality, debug_meta) = self.capture_minidump(
+            &crash_hub,
+            &minidump_path,
+            dump_error.as_ref(),
+            info,
+            &mut extra,
+        );
+
+        let stack_overflow = info.is_stack_overflow();
+        let mut tags = self.crash_tags(info, panic.as_ref(), stack_overflow, &mut extra);
+        // Tag the event with the persisted stats so that crashes can be
+        // triaged across an entire fleet
+        tags.insert(
+            "breakpad.total_crashes".to_owned(),
+            stats.total_crashes.to_string(),
+        );
+        tags.insert("breakpad.dump_quality".to_owned(), dump_quality.to_owned());
+
+        let crash_reason = crash_reason(info, panic.as_ref(), stack_overflow, &mut tags);
+        let mechanism_meta = mechanism_meta(info);
+        // Breakpad doesn't pass the mach exception to the callback on macOS,
+        // but it is recorded in the minidump
+        #[cfg(target_os = "macos")]
+        let mut mechanism_meta = mechanism_meta;
+        #[cfg(target_os = "macos")]
+        let crash_reason = crash_reason.or_else(|| {
+            mach_exception_reason(&minidump_path, &mut tags, &mut mechanism_meta, &mut extra)
+        });
+        if let Some(reason) = &crash_reason {
+            extra.insert("breakpad.crash_reason".to_owned(), reason.clone().into());
+        }
+
+        // Create an event for crash so that we can add all of the context
+        // we can to it, the important information like stack traces/threads
+        // modules/etc is contained in the minidump recorded by breakpad
+        let event = proto::Event {
+            level: proto::Level::Fatal,
+            // We want to set the timestamp here since we aren't actually
+            // going to send the crash directly, but rather the next time
+            // this integration is initialized
+            timestamp: SystemTime::now(),
+            // This is the easiest way to indicate a session crash update
+            // in the same envelope with the crash itself. :p
+            exception: vec![crash_exception(
+                info,
+                panic.as_ref(),
+                stack_overflow,
+                mechanism_meta,
+            )]
+            .into(),
+            // Used as the title of the event until the minidump has been
+            // processed
+            message: crash_reason,
+            extra,
+            tags,
+            contexts: crash_contexts(),
+            debug_meta: std::borrow::Cow::Owned(debug_meta.unwrap_or_default()),
+            ..Default::default()
+        };
+
+        // Crashes that can't wait until the next session can't wait for
+        // consent either
+        if event.extra.contains_key("__breakpad_send_immediately")
+            && crate::consent::current() != crate::ConsentPolicy::AlwaysSend
+        {
+            debug_print!("dropping crash event, as consent is required to send it");
+            return;
+        }
+
+        capture_within_budget(&crash_hub, event, crashed_at);
+
+        // Events still need to be sent if the process keeps running
+        if process_continues {
+            return;
+        }
+
+        if let Some(client) = crash_hub.client() {
+            client.close(None);
+        }
+    }
+
+    /// Applies the crash directory policy to the minidump and captures what is
+    /// sent along with it, returning the dump quality and the debug meta read
+    /// from it
+    fn capture_minidump(
+        &self,
+        crash_hub: &sentry_core::Hub,
+        minidump_path: &Path,
+        dump_error: Option<&DumpError>,
+        info: &CrashInfo,
+        extra: &mut proto::Map<String, proto::Value>,
+    ) -> (&'static str, Option<proto::DebugMeta>) {
+        // A partially written minidump can't be processed, so it isn't left in
+        // the crash directory to be uploaded later
+        if dump_error.is_some()
+            && minidump_path.starts_with(&self.crash_dir)
+            && minidump_path.is_file()
+        {
+            crate::policy::remove(&self.crash_dir, minidump_path);
+        }
+
+        // Keep the crash directory within the policy before anything else is
+        // written for the minidump
+        let rate_limited = dump_error.is_none()
+            && minidump_path.starts_with(&self.crash_dir)
+            && !self.policy.apply(&self.crash_dir, minidump_path);
+
+        // If the minidump only exists in memory, or couldn't be written at
+        // all, eg. due to a read-only filesystem, it can't wait until the next
+        // session
+        if dump_error.is_some() || !minidump_path.is_file() {
+            let reason = match dump_error {
+                _ if rate_limited => self.policy.rate_limit_reason(),
+                Some(err) => err.to_string(),
+                None => format!("unable to write minidump to '{}'", minidump_path.display()),
+            };
+            extra.insert("breakpad.minidump_unavailable".to_owned(), reason.into());
+            extra.insert("__breakpad_send_immediately".to_owned(), true.into());
+
+            let dump_quality = if rate_limited { "discarded" } else { "failed" };
+            return (dump_quality, None);
+        }
+
+        let mut debug_meta = None;
+
+        // Read before anything is appended to the minidump, so that Sentry can
+        // still symbolicate the crash if the attachment is rejected, eg. for
+        // its size
+        if let Some(modules) = crate::debug_meta::capture(minidump_path) {
+            if !modules.versions.is_empty() {
+                extra.insert(
+                    "breakpad.module_versions".to_owned(),
+                    proto::Value::Object(
+                        modules
+                            .versions
+                            .into_iter()
+                            .map(|(name, version)| (name, version.into()))
+                            .collect(),
+                    ),
+                );
+            }
+            debug_meta = Some(modules.debug_meta);
+        }
+
+        crate::embedded::capture(minidump_path);
+        crate::task_names::capture(minidump_path);
+        crate::telemetry::capture(minidump_path);
+
+        if minidump_path.starts_with("/proc/self/fd") {
+            extra.insert("__breakpad_send_immediately".to_owned(), true.into());
+        } else if let Some(id) = minidump_path.file_stem().and_then(|fs| fs.to_str()) {
+            crate::attachments::capture(&self.crash_dir, id);
+
+            let boot_ref = crate::clock::BootReference::now();
+            crate::index::append(
+                &self.crash_dir,
+                &crate::IndexEntry {
+                    id: id.to_owned(),
+                    timestamp: SystemTime::now()
+                        .duration_since(SystemTime::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs(),
+                    signal: info.signal,
+                    release: crash_hub.client().and_then(|client| {
+                        client.options().release.as_ref().map(|r| r.to_string())
+                    }),
+                    boot_id: boot_ref.as_ref().map(|br| br.boot_id.clone()),
+                    uptime_ms: boot_ref.as_ref().map(|br| br.uptime.as_millis() as u64),
+                },
+            );
+        }
+
+        // In debug builds, make sure breakpad actually wrote something that
+        // Sentry will be able to process
+        #[cfg(debug_assertions)]
+        if let Some(problems) = crate::minidump::validate_file(minidump_path) {
+            extra.insert("breakpad.minidump_problems".to_owned(), problems);
+        }
+
+        // Some of the stacks are incomplete to stay within the size limit
+        let dump_quality = if info.truncated { "truncated" } else { "full" };
+        (dump_quality, debug_meta)
+    }
+
+    /// Tags the crash with what kind of crash it was, and records the details
+    /// that don't fit in a tag in the extra
+    fn crash_tags(
+        &self,
+        info: &CrashInfo,
+        panic: Option<&crate::panic::Panic>,
+        stack_overflow: bool,
+        extra: &mut proto::Map<String, proto::Value>,
+    ) -> std::collections::BTreeMap<String, String> {
+        // A SIGBUS is most likely due to accessing a memory mapped file
+        // that was truncated, rather than memory corruption, so record
+        // the file and where in it the fault was to tell them apart
+        if info.is_bus_error() {
+            if let Some(fm) = info.fault_mapping() {
+                extra.insert(
+                    "breakpad.fault_file".to_owned(),
+                    fm.path.display().to_string().into(),
+                );
+                extra.insert("breakpad.fault_file_offset".to_owned(), fm.offset.into());
+            }
+        }
+
+        let mut tags = std::collections::BTreeMap::new();
+        // Local variables are missing from the stack traces of
+        // sanitized minidumps, which is otherwise confusing
+        if self.sanitizes_stacks {
+            tags.insert("breakpad.sanitized_stacks".to_owned(), "true".to_owned());
+        }
+        // The command line and environment may still be in the minidump
+        if info.redaction_failed {
+            tags.insert("breakpad.redaction".to_owned(), "failed".to_owned());
+        }
+        if let Some(signal) = info.signal {
+            tags.insert("breakpad.signal".to_owned(), signal.to_string());
+        }
+        if let Some(name) = info.signal_name() {
+            tags.insert("breakpad.signal_name".to_owned(), name.to_owned());
+        }
+        // Fail fast exceptions mean memory corruption was detected,
+        // so they are tagged to make them easy to find
+        if info.is_fail_fast() {
+            tags.insert("breakpad.fail_fast".to_owned(), "true".to_owned());
+        }
+        // The stack trace of a stack overflow is usually cut off
+        // long before where the recursion started, so they are
+        // tagged to tell them apart from other crashes
+        if stack_overflow {
+            tags.insert("breakpad.stack_overflow".to_owned(), "true".to_owned());
+        }
+        // Written by a HangMonitor, the process is still running
+        if info.hang {
+            tags.insert("breakpad.app_hang".to_owned(), "true".to_owned());
+        }
+        // Written by the hook installed with hook_panics
+        if let Some(panic) = panic {
+            tags.insert("breakpad.panic".to_owned(), "true".to_owned());
+            if let Some(location) = &panic.location {
+                extra.insert(
+                    "breakpad.panic_location".to_owned(),
+                    location.clone().into(),
+                );
+            }
+            if let Some(thread) = &panic.thread {
+                extra.insert("breakpad.panic_thread".to_owned(), thread.clone().into());
+            }
+        }
+        if let Some(last_error) = info.last_error {
+            extra.insert("breakpad.last_error".to_owned(), last_error.into());
+        }
+
+        tags
+    }
+}
+
+/// Describes the crash, used as the message of the crash event until the
+/// minidump has been processed
+fn crash_reason(
+    info: &CrashInfo,
+    panic: Option<&crate::panic::Panic>,
+    stack_overflow: bool,
+    tags: &mut std::collections::BTreeMap<String, String>,
+) -> Option<String> {
+    let crash_reason = info.code_info().map(|(code_name, description)| {
+        let mut reason = if info.signal.is_some() {
+            tags.insert("breakpad.signal_code".to_owned(), code_name.to_owned());
+            format!(
+                "{} / {}: {}",
+                info.signal_name().unwrap_or("unknown signal"),
+                code_name,
+                description
+            )
+        } else {
+            tags.insert("breakpad.exception_code".to_owned(), code_name.to_owned());
+            format!("{}: {}", code_name, description)
+        };
+
+        if let Some(access) = info.access {
+            reason.push_str(&format!(
+                " ({} at {:#x})",
+                access.kind.as_str(),
+                access.address
+            ));
+            if let Some(status) = access.status {
+                reason.push_str(&format!(", NTSTATUS {:#010x}", status));
+            }
+        }
+
+        reason
+    });
+    // Unlike the Windows exception, a SIGSEGV doesn't say that
+    // it was a stack overflow by itself
+    let crash_reason = match crash_reason {
+        Some(reason) if stack_overflow && info.signal.is_some() => {
+            Some(format!("Stack overflow: {}", reason))
+        }
+        reason => reason,
+    };
+    // The message says a lot more than the SIGABRT that
+    // follows a panic with panic=abort
+    let crash_reason = match panic {
+        Some(panic) => Some(match &panic.location {
+            Some(location) => format!("panicked at {}: {}", location, panic.message),
+            None => format!("panicked: {}", panic.message),
+        }),
+        None => crash_reason,
+    };
+    crash_reason.or_else(|| {
+        info.hang
+            .then(|| "App hang: the monitored thread stopped responding".to_owned())
+    })
+}
+
+/// The details of the signal in the structured form Sentry shows on the
+/// exception mechanism
+fn mechanism_meta(info: &CrashInfo) -> proto::MechanismMeta {
+    let mut mechanism_meta = proto::MechanismMeta::default();
+    if let Some(signal) = info.signal {
+        mechanism_meta.signal = Some(proto::PosixSignal {
+            number: signal,
+            code: info.code.map(|code| code as i32),
+            name: info.signal_name().map(str::to_owned),
+            code_name: info.code_info().map(|(name, _)| name.to_owned()),
+        });
+    }
+    mechanism_meta
+}
+
+/// Describes the mach exception recorded in the minidump. Analysts usually
+/// reason in terms of signals, so the equivalent one is recorded too
+#[cfg(target_os = "macos")]
+fn mach_exception_reason(
+    minidump_path: &Path,
+    tags: &mut std::collections::BTreeMap<String, String>,
+    mechanism_meta: &mut proto::MechanismMeta,
+    extra: &mut proto::Map<String, proto::Value>,
+) -> Option<String> {
+    let bytes = std::fs::read(minidump_path).ok()?;
+    let exc = crate::mach::MachException::from_minidump(&bytes)?;
+
+    tags.insert("breakpad.mach_exception".to_owned(), exc.name().to_owned());
+    mechanism_meta.mach_exception = Some(proto::MachException {
+        exception: exc.kind as i32,
+        code: exc.code.into(),
+        subcode: exc.subcode.unwrap_or_default(),
+        name: Some(exc.name().to_owned()),
+    });
+    extra.insert("breakpad.mach_code".to_owned(), exc.code.into());
+
+    let mut reason = exc.name().to_owned();
+    if let Some(code_name) = exc.code_name() {
+        reason.push_str(" / ");
+        reason.push_str(code_name);
+    }
+    if let Some((signal, name)) = exc.signal() {
+        tags.insert("breakpad.signal".to_owned(), signal.to_string());
+        tags.insert("breakpad.signal_name".to_owned(), name.to_owned());
+        mechanism_meta.signal = Some(proto::PosixSignal {
+            number: signal,
+            name: Some(name.to_owned()),
+            ..Default::default()
+        });
+        reason.push_str(&format!(" ({})", name));
+    }
+    if let Some(subcode) = exc.subcode {
+        extra.insert("breakpad.mach_subcode".to_owned(), subcode.into());
+        reason.push_str(&format!(" at {:#x}", subcode));
+    }
+
+    Some(reason)
+}
+
+/// The exception of the crash event, the stack trace is filled in by Sentry
+/// from the minidump
+fn crash_exception(
+    info: &CrashInfo,
+    panic: Option<&crate::panic::Panic>,
+    stack_overflow: bool,
+    mechanism_meta: proto::MechanismMeta,
+) -> proto::Exception {
+    proto::Exception {
+        ty: panic.map_or_else(String::new, |_| "panic".to_owned()),
+        value: panic.map(|panic| panic.message.clone()),
+        thread_id: info.thread_id.map(|tid| proto::ThreadId::Int(tid.into())),
+        mechanism: Some(proto::Mechanism {
+            ty: panic.map_or_else(String::new, |_| "panic".to_owned()),
+            handled: Some(false),
+            description: stack_overflow.then(|| "stack overflow".to_owned()),
+            data: stack_overflow
+                .then(|| ("stack_overflow".to_owned(), true.into()))
+                .into_iter()
+                .collect(),
+            meta: mechanism_meta,
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// The contexts of the crash event
+fn crash_contexts() -> proto::Map<String, proto::Context> {
+    // Breakpad has no way to add custom streams to the minidump, so the
+    // security relevant state of the process is only attached to the event
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let mut contexts = {
+        let mut contexts = proto::Map::new();
+        contexts.insert(
+            crate::hardening::CONTEXT_NAME.to_owned(),
+            crate::hardening::capture(),
+        );
+        contexts
+    };
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let mut contexts = proto::Map::new();
+
+    // Crash keys set via breakpad_handler::set_annotation, or from C/C++ via
+    // its C API
+    let annotations = breakpad_handler::annotations();
+    if !annotations.is_empty() {
+        contexts.insert(
+            "annotations".to_owned(),
+            proto::Context::Other(
+                annotations
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into()))
+                    .collect(),
+            ),
+        );
+    }
+
+    contexts
+}
+
+/// Captures the crash event with as much of the scope as the metadata budget
+/// allows. Applying the scope is what can stall on a huge scope, so the budget
+/// is enforced before it is applied.
+fn capture_within_budget(
+    crash_hub: &sentry_core::Hub,
+    mut event: proto::Event<'static>,
+    crashed_at: std::time::Instant,
+) {
+    let level = crate::transport::metadata_budget()
+        .map_or(crate::transport::MetadataLevel::Full, |budget| {
+            crate::transport::MetadataLevel::select(crashed_at, budget)
+        });
+    if level != crate::transport::MetadataLevel::Full {
+        event.extra.insert(
+            "__breakpad_metadata_level".to_owned(),
+            level.as_str().into(),
+        );
+    }
+
+    match level {
+        crate::transport::MetadataLevel::Full => {
+            crash_hub.capture_event(event);
+        }
+        crate::transport::MetadataLevel::TagsOnly => {
+            crash_hub.with_scope(
+                |scope| scope.clear_breadcrumbs(),
+                || crash_hub.capture_event(event),
+            );
+        }
+        crate::transport::MetadataLevel::EventIdOnly => {
+            if let Some(client) = crash_hub.client() {
+                client.capture_event(event, None);
+            }
+        }
+    }
+}
 
 /// Monitors the current process for crashes, writing them to disk as minidumps
 /// and reporting the crash event to Sentry.
 pub struct BreakpadIntegration {
     crash_handler: Option<breakpad_handler::BreakpadHandler>,
+    crash_dir: std::path::PathBuf,
+    hub: std::sync::Weak<sentry_core::Hub>,
+    stats: std::sync::Arc<crate::stats::PersistentStats>,
 }
 
 impl BreakpadIntegration {
     /// Creates a new Breakpad Integration, note that only *one* can exist
     /// in the application at a time!
+    #[track_caller]
     pub fn new(
         crash_dir: impl AsRef<Path>,
         install_options: InstallOptions,
         hub: std::sync::Arc<sentry_core::Hub>,
+    ) -> Result<Self, crate::Error> {
+        Self::with_settings(crash_dir, install_options, MinidumpSettings::default(), hub)
+    }
+
+    /// Same as [`BreakpadIntegration::new`], but with settings that control
+    /// the contents of the minidumps
+    #[track_caller]
+    pub fn with_settings(
+        crash_dir: impl AsRef<Path>,
+        install_options: InstallOptions,
+        settings: MinidumpSettings,
+        hub: std::sync::Arc<sentry_core::Hub>,
+    ) -> Result<Self, crate::Error> {
+        Self::with_policy(
+            crash_dir,
+            install_options,
+            settings,
+            crate::CrashDirPolicy::default(),
+            hub,
+        )
+    }
+
+    /// Same as [`BreakpadIntegration::with_settings`], but with a policy that
+    /// limits the minidumps kept in the crash directory until they are uploaded
+    #[track_caller]
+    pub fn with_policy(
+        crash_dir: impl AsRef<Path>,
+        install_options: InstallOptions,
+        settings: MinidumpSettings,
+        policy: crate::CrashDirPolicy,
+        hub: std::sync::Arc<sentry_core::Hub>,
     ) -> Result<Self, crate::Error> {
         // The paths generated by breakpad are just guids with an extension so they
         // are utf-8 safe, however, due to how we pass the path via metadata
@@ -26,122 +634,286 @@ impl BreakpadIntegration {
 
         // Ensure the directory exists, breakpad should do this when writing crashdumps
         // anyway, but then again, it's C++ code, so I have low trust
-        std::fs::create_dir_all(&crash_dir)?;
+        if let Err(e) = std::fs::create_dir_all(&crash_dir) {
+            // If the directory can't be created because eg. we're running on
+            // a read-only filesystem, we can still capture crashes, they just
+            // have to be sent immediately instead
+            if !matches!(
+                e.kind(),
+                std::io::ErrorKind::ReadOnlyFilesystem | std::io::ErrorKind::PermissionDenied
+            ) {
+                return Err(e.into());
+            }
+
+            debug_print!(
+                "unable to create crash directory '{}': {}",
+                crash_dir.as_ref().display(),
+                e
+            );
+        }
 
-        let crash_hub = std::sync::Arc::downgrade(&hub);
-        let crash_handler = breakpad_handler::BreakpadHandler::attach(
+        let stats = std::sync::Arc::new(crate::stats::PersistentStats::load(crash_dir.as_ref()));
+
+        let crash_context = CrashContext {
+            hub: std::sync::Arc::downgrade(&hub),
+            stats: stats.clone(),
+            sanitizes_stacks: settings.sanitizes_stacks(),
+            crash_dir: crash_dir.as_ref().to_owned(),
+            policy,
+        };
+        let crash_handler = breakpad_handler::BreakpadHandler::attach_with_settings(
             &crash_dir,
             install_options,
-            Box::new(move |minidump_path: std::path::PathBuf| {
-                if let Some(crash_hub) = crash_hub.upgrade() {
-                    // We **don't** do end_session_with_status as it just
-                    // immediately takes the session from the scope and sends it,
-                    // but we want to send the event, session update, and minidump
-                    // all in the same event
-                    // crash_hub.end_session_with_status(protocol::SessionStatus::Crashed);
-
-                    let mut extra = std::collections::BTreeMap::new();
-                    // We should never get here unless the path is valid utf-8, so this is fine
-                    extra.insert(
-                        "__breakpad_minidump_path".to_owned(),
-                        minidump_path
-                            .to_str()
-                            .expect("utf-8 path")
-                            .to_owned()
-                            .into(),
-                    );
-
-                    // Create an event for crash so that we can add all of the context
-                    // we can to it, the important information like stack traces/threads
-                    // modules/etc is contained in the minidump recorded by breakpad
-                    let event = proto::Event {
-                        level: proto::Level::Fatal,
-                        // We want to set the timestamp here since we aren't actually
-                        // going to send the crash directly, but rather the next time
-                        // this integration is initialized
-                        timestamp: SystemTime::now(),
-                        // This is the easiest way to indicate a session crash update
-                        // in the same envelope with the crash itself. :p
-                        exception: vec![proto::Exception {
-                            mechanism: Some(proto::Mechanism {
-                                handled: Some(false),
-                                ..Default::default()
-                            }),
-                            ..Default::default()
-                        }]
-                        .into(),
-                        extra,
-                        ..Default::default()
-                    };
-
-                    crash_hub.capture_event(event);
-
-                    if let Some(client) = crash_hub.client() {
-                        client.close(None);
-                    }
-                }
-            }),
+            settings,
+            Box::new(OnCrash(
+                move |result: Result<MinidumpInfo, DumpError>, info: &CrashInfo| {
+                    crash_context.on_crash(result, info);
+                },
+            )),
         )?;
 
         let crash_dir = crash_dir.as_ref().to_owned();
 
-        Self::upload_minidumps(&crash_dir, &hub);
+        crate::upload::upload_consented(&crash_dir, &hub);
 
         Ok(Self {
             crash_handler: Some(crash_handler),
+            crash_dir,
+            hub: std::sync::Arc::downgrade(&hub),
+            stats,
         })
     }
 
-    /// Called during startup to send any minidumps + metadata that have been
-    /// captured in previous sessions but (seem to) have not been sent yet
-    fn upload_minidumps(crash_dir: &Path, hub: &sentry_core::Hub) {
-        // Scan the directory the integration was initialized with to find any
-        // envelopes that have been serialized to disk and send + delete them
-        let rd = match std::fs::read_dir(crash_dir) {
-            Ok(rd) => rd,
-            Err(e) => {
-                debug_print!(
-                    "Unable to read crash directory '{}': {}",
-                    crash_dir.display(),
-                    e
-                );
-                return;
-            }
-        };
+    /// Creates a new Breakpad Integration with configuration loaded at runtime,
+    /// see [`BreakpadConfig`](crate::BreakpadConfig) for the available settings
+    #[track_caller]
+    pub fn from_config(
+        source: &crate::ConfigSource,
+        hub: std::sync::Arc<sentry_core::Hub>,
+    ) -> Result<Self, crate::Error> {
+        let config = crate::BreakpadConfig::load(source)?;
+
+        // Set before the integration is created, as that uploads the crashes
+        // from previous sessions that the user consented to
+        Self::set_consent_policy(config.consent);
+
+        Self::with_policy(
+            &config.crash_dir,
+            config.install_options,
+            config.minidump_settings(),
+            config.dir_policy(),
+            hub,
+        )
+    }
 
-        let client = match hub.client() {
+    /// Sends any minidumps + metadata in the crash directory that have not
+    /// been sent yet. This is done automatically when the integration is
+    /// created, but can be called again to send dumps that were written by
+    /// other processes, eg. when notified by a [`DumpWatcher`](crate::DumpWatcher)
+    pub fn upload_pending(&self) {
+        if let Some(hub) = self.hub.upgrade() {
+            crate::upload::upload_consented(&self.crash_dir, &hub);
+        }
+    }
+
+    /// Same as [`BreakpadIntegration::upload_pending`], but on a background
+    /// worker, so the caller isn't blocked while the minidumps are read and
+    /// sent. The minidumps are sent one at a time, waiting for each to be
+    /// flushed by the transport before reading the next, which keeps only one
+    /// of them in memory, and `on_progress` is invoked on the worker after
+    /// each of them, eg. to show a "sending crash report" dialog. See
+    /// [`CrashStore::upload_pending_async`](crate::CrashStore::upload_pending_async)
+    /// for how the [`ConsentPolicy`](crate::ConsentPolicy) affects the progress.
+    pub fn upload_pending_async<F>(
+        &self,
+        on_progress: F,
+    ) -> Result<std::thread::JoinHandle<()>, crate::Error>
+    where
+        F: Fn(crate::UploadProgress) + Send + 'static,
+    {
+        Ok(crate::upload::upload_pending_async(
+            self.crash_dir.clone(),
+            self.hub.clone(),
+            on_progress,
+        )?)
+    }
+
+    /// Gets the entries of the index of minidumps in the crash directory that
+    /// haven't been uploaded yet, without having to read the minidumps or their
+    /// metadata. The index is kept up to date by the integration, so minidumps
+    /// written to the crash directory by other processes are not included.
+    pub fn pending_dumps(&self) -> Vec<crate::IndexEntry> {
+        crate::index::read(&self.crash_dir)
+    }
+
+    /// Sets whether the minidumps in the crash directory are uploaded without
+    /// asking the user, which is [`ConsentPolicy::AlwaysSend`](crate::ConsentPolicy::AlwaysSend)
+    /// by default. Unless minidumps are always sent, crash events for
+    /// minidumps that couldn't be written to the crash directory are dropped,
+    /// as they can only be sent from the crashing process.
+    ///
+    /// The policy should be set before the integration is created, as that
+    /// uploads the minidumps from previous sessions.
+    pub fn set_consent_policy(policy: crate::ConsentPolicy) {
+        crate::consent::set(policy);
+    }
+
+    /// Sets how crashes that are the same as the previously uploaded one are
+    /// handled, `None` by default. Crashes that occur within the window of
+    /// the previous one, from the same place according to the stack of the
+    /// crashing thread, are tagged with `breakpad.recurrence`, and optionally
+    /// uploaded without their minidump.
+    ///
+    /// The policy should be set before the integration is created, as that
+    /// uploads the minidumps from previous sessions.
+    pub fn set_recurrence_policy(policy: Option<crate::RecurrencePolicy>) {
+        crate::recurrence::set(policy);
+    }
+
+    /// Gets the crashes in the crash directory that haven't been uploaded yet,
+    /// eg. to ask the user whether to send them with
+    /// [`ConsentPolicy::AskBeforeSend`](crate::ConsentPolicy::AskBeforeSend)
+    pub fn pending_crashes(&self) -> Vec<crate::PendingCrash> {
+        crate::consent::pending(&self.crash_dir)
+    }
+
+    /// Uploads a pending crash that the user approved, regardless of the
+    /// [`ConsentPolicy`](crate::ConsentPolicy)
+    pub fn approve_crash(&self, crash: &crate::PendingCrash) {
+        let client = match self.hub.upgrade().and_then(|hub| hub.client()) {
             Some(c) => c,
             None => return,
         };
 
-        // The minidumps are what we care about the most, but of course, the
-        // metadata that we (hopefully) were able to capture along with the crash
-        for entry in rd.filter_map(|e| e.ok()) {
-            if entry
-                .file_name()
-                .to_str()
-                .map_or(true, |s| !s.ends_with(".dmp"))
-            {
-                continue;
-            }
+        // Skipped if another process sharing the crash directory is already
+        // uploading it
+        let _claim = match self.claim_pending(crash) {
+            Some(claim) => claim,
+            None => return,
+        };
 
-            let mut minidump_path = entry.path();
-            minidump_path.set_extension("metadata");
+        let index = crate::index::read(&self.crash_dir);
+        if let Some(id) = crate::upload::upload_minidump(
+            &self.crash_dir,
+            &index,
+            &client,
+            crash.minidump_path.clone(),
+        ) {
+            crate::index::prune(&self.crash_dir, &[id]);
+        }
+    }
 
-            let md = crate::shared::CrashMetadata::deserialize(&minidump_path);
-            if let Err(e) = std::fs::remove_file(&minidump_path) {
-                debug_print!("failed to remove {}: {}", minidump_path.display(), e);
-            }
+    /// Deletes a pending crash that the user declined to send, along with
+    /// everything that was written for it
+    pub fn discard_crash(&self, crash: &crate::PendingCrash) {
+        let _claim = match self.claim_pending(crash) {
+            Some(claim) => claim,
+            None => return,
+        };
 
-            minidump_path.set_extension("dmp");
+        if let Some(id) = crate::policy::remove(&self.crash_dir, &crash.minidump_path) {
+            crate::index::prune(&self.crash_dir, &[id]);
+        }
+    }
 
-            let envelope = crate::shared::assemble_envelope(md, &minidump_path);
-            if let Err(e) = std::fs::remove_file(&minidump_path) {
-                debug_print!("failed to remove {}: {}", minidump_path.display(), e);
-            }
+    /// Claims a pending crash, unless it has been uploaded or removed since
+    /// it was listed, or another process has claimed it
+    fn claim_pending(&self, crash: &crate::PendingCrash) -> Option<crate::CrashClaim> {
+        let store = crate::CrashStore::new(&self.crash_dir);
+        let stored = store
+            .list()
+            .into_iter()
+            .find(|stored| stored.minidump_path == crash.minidump_path)?;
+        store.claim(&stored)
+    }
 
-            client.send_envelope(envelope);
-        }
+    /// Registers a callback that captures additional artifacts for a crash, eg.
+    /// a screenshot or a UI state file, which are attached to the same envelope
+    /// as the minidump. The callback is given the path of the minidump.
+    ///
+    /// Callbacks are never invoked in the crashing process, but rather when
+    /// the minidumps from previous sessions are uploaded, so they should be
+    /// registered before the integration is created to apply to those.
+    pub fn register_companion_artifact<F>(capture: F)
+    where
+        F: Fn(&Path) -> Vec<proto::Attachment> + Send + Sync + 'static,
+    {
+        crate::upload::COMPANION_ARTIFACTS
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push(Box::new(capture));
+    }
+
+    /// Registers a file, eg. a log or config file, that is attached to the
+    /// same envelope as the minidump. The file is copied to the crash
+    /// directory when a crash occurs, and sent along with the minidump the
+    /// next time the integration is initialized.
+    pub fn register_attachment(
+        path: impl Into<std::path::PathBuf>,
+        content_type: impl Into<String>,
+    ) {
+        crate::attachments::register(path.into(), content_type.into());
+    }
+
+    /// Registers a small file, eg. `/etc/lsb-release` or a state file of the
+    /// application, whose contents are embedded in the minidump itself as a
+    /// [`StreamType::EmbeddedFile`](crate::minidump::StreamType::EmbeddedFile)
+    /// stream when a crash occurs. At most `max_len` bytes of the file are
+    /// embedded, files that can't be read are skipped.
+    ///
+    /// Unlike [`BreakpadIntegration::register_attachment`], the file travels
+    /// with the minidump wherever it is processed, but Sentry doesn't show it.
+    pub fn embed_file(path: impl Into<std::path::PathBuf>, max_len: u64) {
+        crate::embedded::register(path.into(), max_len);
+    }
+
+    /// Registers a ring buffer that the application writes telemetry to, eg.
+    /// high frequency logs, which is snapshotted into the minidump as a
+    /// [`StreamType::TelemetryRing`](crate::minidump::StreamType::TelemetryRing)
+    /// stream when a crash occurs, so that it survives the crash
+    pub fn register_telemetry_ring(ring: crate::TelemetryRing) {
+        crate::telemetry::register(ring);
+    }
+
+    /// Registers a callback that decodes the snapshots of the telemetry ring
+    /// with the name into attachments, eg. a plain text log, which are
+    /// attached to the same envelope as the minidump. The snapshot is left in
+    /// the minidump either way.
+    ///
+    /// Like [`BreakpadIntegration::register_companion_artifact`], decoders are
+    /// invoked when the minidumps from previous sessions are uploaded, so
+    /// they should be registered before the integration is created.
+    pub fn register_telemetry_decoder<F>(name: impl Into<String>, decode: F)
+    where
+        F: Fn(&crate::minidump::TelemetrySnapshot<'_>) -> Vec<proto::Attachment>
+            + Send
+            + Sync
+            + 'static,
+    {
+        crate::telemetry::DECODERS
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push((name.into(), Box::new(decode)));
+    }
+
+    /// Installs a panic hook that writes a minidump when a Rust panic occurs,
+    /// which is reported the same as a crash, with the message and location
+    /// of the panic, eg. instead of using `sentry-panic` on top of the
+    /// integration. The hook runs the one that was installed before it first.
+    ///
+    /// The minidump is written by the hook, and the process keeps running,
+    /// so a minidump is also written for panics that are caught. With
+    /// `panic = "abort"` on Linux/Android, the panic is instead recorded for
+    /// the `SIGABRT` that follows it, so only one minidump is written.
+    pub fn hook_panics(&self) {
+        crate::panic::hook();
+    }
+
+    /// Gets the crash counters for the application, which are persisted in the
+    /// crash directory across runs. Only actual crashes are counted, not
+    /// hangs, panics, or minidumps that were requested.
+    pub fn stats(&self) -> crate::CrashStats {
+        self.stats.get()
     }
 
     #[inline]
@@ -0,0 +1,65 @@
+//! Corrects the timestamps of crashes that occurred while the system clock
+//! was wrong, which is common on consoles and embedded devices that only set
+//! the clock some time after booting
+
+use std::time::{Duration, SystemTime};
+
+/// The difference between the recorded and the corrected timestamp of a
+/// crash after which the recorded timestamp is considered to be wrong
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(5 * 60);
+
+/// A reference to the monotonic clock, which is only comparable to other
+/// references from the same boot
+pub(crate) struct BootReference {
+    /// The random id the kernel generates on every boot
+    pub(crate) boot_id: String,
+    /// The time since boot, including time spent suspended
+    pub(crate) uptime: Duration,
+}
+
+impl BootReference {
+    /// Gets the current reference, only available on Linux/Android
+    pub(crate) fn now() -> Option<Self> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let boot_id = std::fs::read_to_string("/proc/sys/kernel/random/boot_id").ok()?;
+            // The first value is the time since boot in seconds, which
+            // unlike CLOCK_MONOTONIC includes time spent suspended
+            let uptime = std::fs::read_to_string("/proc/uptime").ok()?;
+            let uptime = uptime.split_whitespace().next()?.parse::<f64>().ok()?;
+
+            Some(Self {
+                boot_id: boot_id.trim().to_owned(),
+                uptime: Duration::from_secs_f64(uptime),
+            })
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            None
+        }
+    }
+}
+
+/// Recomputes when a crash occurred from the time elapsed since then on the
+/// monotonic clock, returning the corrected timestamp if the recorded one is
+/// off by more than [`MAX_CLOCK_SKEW`]. This is only possible if the crash
+/// occurred during the current boot.
+pub(crate) fn corrected_timestamp(
+    recorded: SystemTime,
+    crashed_at: &BootReference,
+) -> Option<SystemTime> {
+    let current = BootReference::now()?;
+    if current.boot_id != crashed_at.boot_id {
+        return None;
+    }
+
+    let elapsed = current.uptime.checked_sub(crashed_at.uptime)?;
+    let corrected = SystemTime::now().checked_sub(elapsed)?;
+
+    let skew = match recorded.duration_since(corrected) {
+        Ok(skew) => skew,
+        Err(e) => e.duration(),
+    };
+
+    (skew > MAX_CLOCK_SKEW).then_some(corrected)
+}
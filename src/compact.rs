@@ -0,0 +1,642 @@
+use sentry_core::protocol as proto;
+use std::{io::Write, time::SystemTime};
+
+/// The initial size of the buffer, which is enough for most events without
+/// breadcrumbs, or with only a few of them
+const INITIAL_CAPACITY: usize = 16 * 1024;
+
+/// Serializes crash metadata in the same format as
+/// [`CrashMetadata::serialize`](crate::shared::CrashMetadata::serialize), but
+/// without `serde_json`, writing directly into a preallocated buffer and only
+/// capturing a fixed subset of the event and session update fields. The output
+/// is still valid JSON for those types, so it is read back with the normal
+/// serde path at upload time.
+///
+/// Of the event, only the basic attributes, user, fingerprint, exceptions
+/// without their stack traces, sdk, tags, extra, symbolic debug images and
+/// breadcrumbs are captured, along with the `os` and `runtime` contexts and
+/// contexts that aren't typed, such as the ones added by the integration.
+/// Everything else, eg. the message, threads, request, and the other typed
+/// contexts like `device` and `app`, is left out.
+pub(crate) fn serialize(
+    event: Option<&proto::Event<'_>>,
+    session_update: Option<&proto::SessionUpdate<'_>>,
+) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(INITIAL_CAPACITY);
+
+    let _ = writeln!(
+        buf,
+        "{}{}",
+        crate::shared::METADATA_HEADER,
+        crate::shared::METADATA_VERSION
+    );
+
+    if let Some(eve) = event {
+        write_event(&mut buf, eve);
+    }
+    buf.push(b'\n');
+
+    if let Some(su) = session_update {
+        write_session_update(&mut buf, su);
+    }
+    buf.push(b'\n');
+
+    buf
+}
+
+fn write_event(buf: &mut Vec<u8>, eve: &proto::Event<'_>) {
+    let mut obj = Object::new(buf);
+
+    let _ = write!(obj.key("event_id"), "\"{}\"", eve.event_id);
+    let _ = write!(obj.key("level"), "\"{}\"", eve.level);
+    obj.string("platform", &eve.platform);
+    write_timestamp(obj.key("timestamp"), eve.timestamp);
+    obj.opt_string("server_name", eve.server_name.as_deref());
+    obj.opt_string("release", eve.release.as_deref());
+    obj.opt_string("dist", eve.dist.as_deref());
+    obj.opt_string("environment", eve.environment.as_deref());
+    obj.opt_string("transaction", eve.transaction.as_deref());
+
+    if let Some(user) = &eve.user {
+        let mut uobj = Object::new(obj.key("user"));
+        uobj.opt_string("id", user.id.as_deref());
+        uobj.opt_string("email", user.email.as_deref());
+        uobj.opt_string("username", user.username.as_deref());
+        if let Some(ip) = &user.ip_address {
+            let _ = write!(uobj.key("ip_address"), "\"{}\"", ip);
+        }
+        uobj.end();
+    }
+
+    if !eve.fingerprint.is_empty() {
+        let values = obj.key("fingerprint");
+        values.push(b'[');
+        for (i, fp) in eve.fingerprint.iter().enumerate() {
+            if i > 0 {
+                values.push(b',');
+            }
+            write_string(values, fp);
+        }
+        values.push(b']');
+    }
+
+    if !eve.exception.values.is_empty() {
+        let mut xobj = Object::new(obj.key("exception"));
+        let values = xobj.key("values");
+        values.push(b'[');
+        for (i, exc) in eve.exception.values.iter().enumerate() {
+            if i > 0 {
+                values.push(b',');
+            }
+            write_exception(values, exc);
+        }
+        values.push(b']');
+        xobj.end();
+    }
+
+    if let Some(sdk) = &eve.sdk {
+        let mut sobj = Object::new(obj.key("sdk"));
+        sobj.string("name", &sdk.name);
+        sobj.string("version", &sdk.version);
+        if !sdk.integrations.is_empty() {
+            let values = sobj.key("integrations");
+            values.push(b'[');
+            for (i, integration) in sdk.integrations.iter().enumerate() {
+                if i > 0 {
+                    values.push(b',');
+                }
+                write_string(values, integration);
+            }
+            values.push(b']');
+        }
+        if !sdk.packages.is_empty() {
+            let values = sobj.key("packages");
+            values.push(b'[');
+            for (i, package) in sdk.packages.iter().enumerate() {
+                if i > 0 {
+                    values.push(b',');
+                }
+                let mut pobj = Object::new(values);
+                pobj.string("name", &package.name);
+                pobj.string("version", &package.version);
+                pobj.end();
+            }
+            values.push(b']');
+        }
+        sobj.end();
+    }
+
+    let contexts: Vec<_> = eve
+        .contexts
+        .iter()
+        .filter(|(_, ctx)| {
+            matches!(
+                ctx,
+                proto::Context::Os(_) | proto::Context::Runtime(_) | proto::Context::Other(_)
+            )
+        })
+        .collect();
+    if !contexts.is_empty() {
+        let mut cobj = Object::new(obj.key("contexts"));
+        for (k, ctx) in contexts {
+            write_context(cobj.key(k), ctx);
+        }
+        cobj.end();
+    }
+
+    if !eve.tags.is_empty() {
+        let mut tobj = Object::new(obj.key("tags"));
+        for (k, v) in &eve.tags {
+            tobj.string(k, v);
+        }
+        tobj.end();
+    }
+
+    if !eve.extra.is_empty() {
+        let mut eobj = Object::new(obj.key("extra"));
+        for (k, v) in &eve.extra {
+            write_value(eobj.key(k), v);
+        }
+        eobj.end();
+    }
+
+    // Only the symbolic images the integration adds from the module list, so
+    // that the event can still be symbolicated without the minidump
+    let images: Vec<_> = eve
+        .debug_meta
+        .images
+        .iter()
+        .filter_map(|image| match image {
+            proto::DebugImage::Symbolic(image) => Some(image),
+            _ => None,
+        })
+        .collect();
+    if !images.is_empty() {
+        let mut dobj = Object::new(obj.key("debug_meta"));
+        let values = dobj.key("images");
+        values.push(b'[');
+        for (i, image) in images.into_iter().enumerate() {
+            if i > 0 {
+                values.push(b',');
+            }
+
+            let mut iobj = Object::new(values);
+            iobj.string("type", "symbolic");
+            iobj.string("name", &image.name);
+            iobj.opt_string("arch", image.arch.as_deref());
+            let _ = write!(iobj.key("image_addr"), "\"{:#x}\"", image.image_addr.0);
+            let _ = write!(iobj.key("image_size"), "{}", image.image_size);
+            let _ = write!(iobj.key("id"), "\"{}\"", image.id);
+            if let Some(code_id) = &image.code_id {
+                let _ = write!(iobj.key("code_id"), "\"{}\"", code_id);
+            }
+            iobj.opt_string("debug_file", image.debug_file.as_deref());
+            iobj.end();
+        }
+        values.push(b']');
+        dobj.end();
+    }
+
+    if !eve.breadcrumbs.values.is_empty() {
+        let mut bobj = Object::new(obj.key("breadcrumbs"));
+        let values = bobj.key("values");
+        values.push(b'[');
+        for (i, bc) in eve.breadcrumbs.values.iter().enumerate() {
+            if i > 0 {
+                values.push(b',');
+            }
+
+            let mut bcobj = Object::new(values);
+            write_timestamp(bcobj.key("timestamp"), bc.timestamp);
+            bcobj.string("type", &bc.ty);
+            bcobj.opt_string("category", bc.category.as_deref());
+            let _ = write!(bcobj.key("level"), "\"{}\"", bc.level);
+            bcobj.opt_string("message", bc.message.as_deref());
+            if !bc.data.is_empty() {
+                let mut dobj = Object::new(bcobj.key("data"));
+                for (k, v) in &bc.data {
+                    write_value(dobj.key(k), v);
+                }
+                dobj.end();
+            }
+            bcobj.end();
+        }
+        values.push(b']');
+        bobj.end();
+    }
+
+    obj.end();
+}
+
+fn write_exception(buf: &mut Vec<u8>, exc: &proto::Exception) {
+    let mut obj = Object::new(buf);
+
+    obj.string("type", &exc.ty);
+    obj.opt_string("value", exc.value.as_deref());
+    obj.opt_string("module", exc.module.as_deref());
+    match &exc.thread_id {
+        Some(proto::ThreadId::Int(tid)) => {
+            let _ = write!(obj.key("thread_id"), "{}", tid);
+        }
+        Some(proto::ThreadId::String(tid)) => obj.string("thread_id", tid),
+        None => {}
+    }
+
+    if let Some(mechanism) = &exc.mechanism {
+        let mut mobj = Object::new(obj.key("mechanism"));
+        mobj.string("type", &mechanism.ty);
+        mobj.opt_string("description", mechanism.description.as_deref());
+        if let Some(handled) = mechanism.handled {
+            mobj.key("handled")
+                .extend_from_slice(if handled { b"true" } else { b"false" });
+        }
+        if let Some(synthetic) = mechanism.synthetic {
+            mobj.key("synthetic")
+                .extend_from_slice(if synthetic { b"true" } else { b"false" });
+        }
+        if !mechanism.data.is_empty() {
+            let mut dobj = Object::new(mobj.key("data"));
+            for (k, v) in &mechanism.data {
+                write_value(dobj.key(k), v);
+            }
+            dobj.end();
+        }
+
+        let meta = &mechanism.meta;
+        if meta.signal.is_some() || meta.mach_exception.is_some() {
+            let mut meta_obj = Object::new(mobj.key("meta"));
+            if let Some(signal) = &meta.signal {
+                let mut sobj = Object::new(meta_obj.key("signal"));
+                let _ = write!(sobj.key("number"), "{}", signal.number);
+                if let Some(code) = signal.code {
+                    let _ = write!(sobj.key("code"), "{}", code);
+                }
+                sobj.opt_string("name", signal.name.as_deref());
+                sobj.opt_string("code_name", signal.code_name.as_deref());
+                sobj.end();
+            }
+            if let Some(mach) = &meta.mach_exception {
+                let mut eobj = Object::new(meta_obj.key("mach_exception"));
+                let _ = write!(eobj.key("exception"), "{}", mach.exception);
+                let _ = write!(eobj.key("code"), "{}", mach.code);
+                let _ = write!(eobj.key("subcode"), "{}", mach.subcode);
+                eobj.opt_string("name", mach.name.as_deref());
+                eobj.end();
+            }
+            meta_obj.end();
+        }
+        mobj.end();
+    }
+
+    obj.end();
+}
+
+/// Writes one of the contexts that are captured, see [`serialize`]
+fn write_context(buf: &mut Vec<u8>, ctx: &proto::Context) {
+    let mut obj = Object::new(buf);
+    obj.string("type", ctx.type_name());
+
+    let other = match ctx {
+        proto::Context::Os(os) => {
+            obj.opt_string("name", os.name.as_deref());
+            obj.opt_string("version", os.version.as_deref());
+            obj.opt_string("build", os.build.as_deref());
+            obj.opt_string("kernel_version", os.kernel_version.as_deref());
+            if let Some(rooted) = os.rooted {
+                obj.key("rooted")
+                    .extend_from_slice(if rooted { b"true" } else { b"false" });
+            }
+            &os.other
+        }
+        proto::Context::Runtime(rt) => {
+            obj.opt_string("name", rt.name.as_deref());
+            obj.opt_string("version", rt.version.as_deref());
+            &rt.other
+        }
+        proto::Context::Other(map) => map,
+        _ => {
+            obj.end();
+            return;
+        }
+    };
+    for (k, v) in other.iter().filter(|(k, _)| *k != "type") {
+        write_value(obj.key(k), v);
+    }
+
+    obj.end();
+}
+
+fn write_session_update(buf: &mut Vec<u8>, su: &proto::SessionUpdate<'_>) {
+    let mut obj = Object::new(buf);
+
+    let _ = write!(obj.key("sid"), "\"{}\"", su.session_id);
+    match &su.distinct_id {
+        Some(did) => obj.string("did", did),
+        None => obj.key("did").extend_from_slice(b"null"),
+    }
+    if let Some(seq) = su.sequence {
+        let _ = write!(obj.key("seq"), "{}", seq);
+    }
+    if let Some(ts) = su.timestamp {
+        write_rfc3339(obj.key("timestamp"), ts);
+    }
+    write_rfc3339(obj.key("started"), su.started);
+    if su.init {
+        obj.key("init").extend_from_slice(b"true");
+    }
+    if let Some(duration) = su.duration {
+        let _ = write!(obj.key("duration"), "{}", duration);
+    }
+    let _ = write!(obj.key("status"), "\"{}\"", su.status);
+    let _ = write!(obj.key("errors"), "{}", su.errors);
+
+    let attrs = &su.attributes;
+    let mut aobj = Object::new(obj.key("attrs"));
+    aobj.string("release", &attrs.release);
+    aobj.opt_string("environment", attrs.environment.as_deref());
+    if let Some(ip) = &attrs.ip_address {
+        let _ = write!(aobj.key("ip_address"), "\"{}\"", ip);
+    }
+    aobj.opt_string("user_agent", attrs.user_agent.as_deref());
+    aobj.end();
+
+    obj.end();
+}
+
+/// Keeps track of whether a comma is needed before the next key of an object
+struct Object<'b> {
+    buf: &'b mut Vec<u8>,
+    empty: bool,
+}
+
+impl<'b> Object<'b> {
+    fn new(buf: &'b mut Vec<u8>) -> Self {
+        buf.push(b'{');
+        Self { buf, empty: true }
+    }
+
+    /// Writes the key, returning the buffer to write the value into
+    fn key(&mut self, key: &str) -> &mut Vec<u8> {
+        if !self.empty {
+            self.buf.push(b',');
+        }
+        self.empty = false;
+
+        write_string(self.buf, key);
+        self.buf.push(b':');
+        self.buf
+    }
+
+    fn string(&mut self, key: &str, value: &str) {
+        write_string(self.key(key), value);
+    }
+
+    fn opt_string(&mut self, key: &str, value: Option<&str>) {
+        if let Some(value) = value {
+            self.string(key, value);
+        }
+    }
+
+    fn end(self) {
+        self.buf.push(b'}');
+    }
+}
+
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    buf.push(b'"');
+
+    for c in s.chars() {
+        match c {
+            '"' => buf.extend_from_slice(b"\\\""),
+            '\\' => buf.extend_from_slice(b"\\\\"),
+            '\n' => buf.extend_from_slice(b"\\n"),
+            '\r' => buf.extend_from_slice(b"\\r"),
+            '\t' => buf.extend_from_slice(b"\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(buf, "\\u{:04x}", c as u32);
+            }
+            c => {
+                let mut utf8 = [0u8; 4];
+                buf.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+            }
+        }
+    }
+
+    buf.push(b'"');
+}
+
+fn write_value(buf: &mut Vec<u8>, value: &proto::Value) {
+    match value {
+        proto::Value::Null => buf.extend_from_slice(b"null"),
+        proto::Value::Bool(b) => buf.extend_from_slice(if *b { b"true" } else { b"false" }),
+        proto::Value::Number(n) => {
+            let _ = write!(buf, "{}", n);
+        }
+        proto::Value::String(s) => write_string(buf, s),
+        proto::Value::Array(values) => {
+            buf.push(b'[');
+            for (i, v) in values.iter().enumerate() {
+                if i > 0 {
+                    buf.push(b',');
+                }
+                write_value(buf, v);
+            }
+            buf.push(b']');
+        }
+        proto::Value::Object(map) => {
+            let mut obj = Object::new(buf);
+            for (k, v) in map {
+                write_value(obj.key(k), v);
+            }
+            obj.end();
+        }
+    }
+}
+
+/// Writes the timestamp as fractional seconds since the Unix epoch
+fn write_timestamp(buf: &mut Vec<u8>, ts: SystemTime) {
+    let since_epoch = ts
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let _ = write!(
+        buf,
+        "{}.{:06}",
+        since_epoch.as_secs(),
+        since_epoch.subsec_micros()
+    );
+}
+
+/// Writes the timestamp as an RFC 3339 string in UTC, with microsecond precision
+fn write_rfc3339(buf: &mut Vec<u8>, ts: SystemTime) {
+    let since_epoch = ts
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = since_epoch.as_secs();
+
+    // Convert days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = secs / 86400 + 719_468;
+    let era = days / 146_097;
+    let doe = days - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    let secs_of_day = secs % 86400;
+    let _ = write!(
+        buf,
+        "\"{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z\"",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        (secs_of_day % 3600) / 60,
+        secs_of_day % 60,
+        since_epoch.subsec_micros()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// A timestamp that survives the microsecond precision of the writer, and
+    /// the float the event timestamps are parsed from, eg. with half seconds
+    fn at(micros: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_micros(micros)
+    }
+
+    fn write(s: &str) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_string(&mut buf, s);
+        buf
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut event = proto::Event {
+            level: proto::Level::Fatal,
+            timestamp: at(1_700_000_000_500_000),
+            release: Some("app@1.0.0".into()),
+            environment: Some("production".into()),
+            user: Some(proto::User {
+                id: Some("42".to_owned()),
+                ..Default::default()
+            }),
+            fingerprint: vec!["{{ default }}".into(), "breakpad".into()].into(),
+            exception: vec![proto::Exception {
+                thread_id: Some(proto::ThreadId::Int(1234)),
+                mechanism: Some(proto::Mechanism {
+                    ty: "minidump".to_owned(),
+                    handled: Some(false),
+                    data: std::iter::once(("stack_overflow".to_owned(), true.into())).collect(),
+                    meta: proto::MechanismMeta {
+                        signal: Some(proto::PosixSignal {
+                            number: 11,
+                            code: Some(1),
+                            name: Some("SIGSEGV".to_owned()),
+                            code_name: Some("SEGV_MAPERR".to_owned()),
+                        }),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }]
+            .into(),
+            sdk: Some(std::borrow::Cow::Owned(proto::ClientSdkInfo {
+                name: "sentry.rust".to_owned(),
+                version: "0.31.7".to_owned(),
+                integrations: vec!["breakpad".to_owned()],
+                packages: vec![proto::ClientSdkPackage {
+                    name: "cargo:sentry".to_owned(),
+                    version: "0.31.7".to_owned(),
+                }],
+            })),
+            tags: std::iter::once(("breakpad.signal".to_owned(), "11".to_owned())).collect(),
+            extra: std::iter::once(("breakpad.crashes".to_owned(), 3.into())).collect(),
+            breadcrumbs: vec![proto::Breadcrumb {
+                timestamp: at(1_699_999_999_000_000),
+                message: Some("loading level".to_owned()),
+                ..Default::default()
+            }]
+            .into(),
+            ..Default::default()
+        };
+        event.contexts.insert(
+            "os".to_owned(),
+            proto::OsContext {
+                name: Some("Linux".to_owned()),
+                kernel_version: Some("6.1.0".to_owned()),
+                ..Default::default()
+            }
+            .into(),
+        );
+        event.contexts.insert(
+            "annotations".to_owned(),
+            proto::Context::Other(
+                std::iter::once(("level".to_owned(), "dungeon".into())).collect(),
+            ),
+        );
+
+        let session_update = proto::SessionUpdate {
+            session_id: sentry_core::types::Uuid::from_u128(0x5678),
+            distinct_id: None,
+            sequence: Some(3),
+            timestamp: Some(at(1_700_000_000_000_000)),
+            started: at(1_699_999_000_000_000),
+            init: false,
+            duration: Some(1000.5),
+            status: proto::SessionStatus::Crashed,
+            errors: 1,
+            attributes: proto::SessionAttributes {
+                release: "app@1.0.0".into(),
+                environment: None,
+                ip_address: None,
+                user_agent: None,
+            },
+        };
+
+        // Typed contexts other than os and runtime are left out
+        let mut with_device = event.clone();
+        with_device.contexts.insert(
+            "device".to_owned(),
+            proto::DeviceContext {
+                model: Some("server".to_owned()),
+                ..Default::default()
+            }
+            .into(),
+        );
+
+        let buf = serialize(Some(&with_device), Some(&session_update));
+        let md = crate::CrashMetadata::parse(std::str::from_utf8(&buf).unwrap());
+
+        assert_eq!(md.event, Some(event));
+        assert_eq!(md.session_update, Some(session_update));
+    }
+
+    #[test]
+    fn escapes_control_characters() {
+        let s = "quote \" backslash \\ newline \n return \r tab \t nul \u{0} unit \u{1f}";
+        let buf = write(s);
+
+        assert!(!buf.iter().any(|b| *b < 0x20), "raw control character");
+        let written = std::str::from_utf8(&buf).unwrap();
+        assert!(written.contains("\\u0000") && written.contains("\\u001f"));
+        assert_eq!(serde_json::from_slice::<String>(&buf).unwrap(), s);
+    }
+
+    #[test]
+    fn keeps_non_ascii_characters() {
+        let s = "héllo wörld ✓ 日本語 🦀 \u{7f} \u{2028}";
+        let buf = write(s);
+
+        // Written as UTF-8 rather than \u escapes
+        assert_eq!(buf.len(), s.len() + 2);
+        assert_eq!(serde_json::from_slice::<String>(&buf).unwrap(), s);
+    }
+}
@@ -0,0 +1,523 @@
+use crate::{
+    BreakpadTransportFactory, ConsentPolicy, CrashDirPolicy, CrashSendStyle, InstallOptions,
+    MinidumpSettings,
+};
+use serde::Deserialize;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// The prefix of the environment variables read by [`ConfigSource::Env`]
+const ENV_PREFIX: &str = "SENTRY_BREAKPAD_";
+
+/// Where to load a [`BreakpadConfig`] from
+pub enum ConfigSource {
+    /// A JSON file containing an object with the same keys as the fields of
+    /// [`BreakpadConfig`], eg. `{ "crash_dir": "/var/crashes", "install_options": "both" }`
+    File(PathBuf),
+    /// Environment variables named after the fields of [`BreakpadConfig`],
+    /// uppercased and prefixed with `SENTRY_BREAKPAD_`, eg.
+    /// `SENTRY_BREAKPAD_CRASH_DIR=/var/crashes`
+    Env,
+}
+
+/// The kind of minidump the handler writes
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DumpType {
+    /// The threads, their stacks and the loaded modules of the process
+    #[default]
+    Normal,
+    /// Same as [`DumpType::Normal`], but also the memory around the registers
+    /// of the crashing thread that point to readable memory, see
+    /// [`MinidumpSettings::with_register_memory`]
+    WithRegisterMemory,
+}
+
+/// Configuration for a [`BreakpadIntegration`](crate::BreakpadIntegration)
+/// that can be loaded at runtime, so that crash handling can be tuned without
+/// rebuilding the application.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BreakpadConfig {
+    /// The directory minidumps are written to
+    pub crash_dir: PathBuf,
+    /// The handlers to install, one of `none`, `exception`, `signal` or `both`,
+    /// defaults to `both`
+    #[serde(
+        default = "default_install_options",
+        deserialize_with = "deserialize_install_options"
+    )]
+    pub install_options: InstallOptions,
+    /// The kind of minidump to write, one of `normal` or `register_memory`,
+    /// defaults to `normal`
+    #[serde(default, deserialize_with = "deserialize_dump_type")]
+    pub dump_type: DumpType,
+    /// Whether to skip writing minidumps for processes that are non-dumpable,
+    /// see [`MinidumpSettings::with_respect_non_dumpable`], defaults to `false`
+    #[serde(default)]
+    pub respect_non_dumpable: bool,
+    /// The maximum size of a minidump in bytes, see
+    /// [`MinidumpSettings::with_size_limit`], defaults to no limit
+    #[serde(default)]
+    pub size_limit: Option<u64>,
+    /// Whether to sanitize the stacks of the threads, see
+    /// [`MinidumpSettings::with_sanitized_stacks`], defaults to `false`
+    #[serde(default)]
+    pub sanitize_stacks: bool,
+    /// Whether to remove the command line and environment of the process
+    /// from the minidumps, see
+    /// [`MinidumpSettings::with_redacted_process_info`], defaults to `false`
+    #[serde(default)]
+    pub redact_process_info: bool,
+    /// Whether to skip writing minidumps while a debugger is attached, see
+    /// [`MinidumpSettings::with_skip_under_debugger`], defaults to `false`
+    #[serde(default)]
+    pub skip_under_debugger: bool,
+    /// Whether crashes are uploaded without asking the user, one of
+    /// `always_send`, `ask_before_send` or `never_send`, see
+    /// [`BreakpadIntegration::set_consent_policy`](crate::BreakpadIntegration::set_consent_policy),
+    /// defaults to `always_send`
+    #[serde(default, deserialize_with = "deserialize_consent")]
+    pub consent: ConsentPolicy,
+    /// How crashes are sent to Sentry, one of `send_immediately`,
+    /// `send_next_session` or `store_all_next_session`, see
+    /// [`BreakpadConfig::transport_factory`], defaults to `send_next_session`
+    #[serde(
+        default = "default_send_style",
+        deserialize_with = "deserialize_send_style"
+    )]
+    pub send_style: CrashSendStyle,
+    /// The maximum number of minidumps kept in the crash directory, see
+    /// [`CrashDirPolicy::max_dumps`], defaults to no limit
+    #[serde(default)]
+    pub max_dumps: Option<usize>,
+    /// The maximum total size of the minidumps kept in the crash directory in
+    /// bytes, see [`CrashDirPolicy::max_total_size`], defaults to no limit
+    #[serde(default)]
+    pub max_total_size: Option<u64>,
+    /// The maximum number of minidumps written in an hour, see
+    /// [`CrashDirPolicy::max_dumps_per_hour`], defaults to no limit
+    #[serde(default)]
+    pub max_dumps_per_hour: Option<u32>,
+}
+
+impl BreakpadConfig {
+    /// Loads the configuration from the specified source
+    pub fn load(source: &ConfigSource) -> Result<Self, crate::Error> {
+        match source {
+            ConfigSource::File(path) => Self::from_file(path),
+            ConfigSource::Env => Self::from_env(),
+        }
+    }
+
+    fn from_file(path: &Path) -> Result<Self, crate::Error> {
+        let contents = std::fs::read_to_string(path)?;
+        serde_json::from_str(&contents)
+            .map_err(|e| crate::Error::InvalidConfig(format!("{}: {}", path.display(), e)))
+    }
+
+    fn from_env() -> Result<Self, crate::Error> {
+        let var = |name: &str| std::env::var(format!("{}{}", ENV_PREFIX, name)).ok();
+
+        let crash_dir = var("CRASH_DIR").ok_or_else(|| {
+            crate::Error::InvalidConfig(format!("{}CRASH_DIR is not set", ENV_PREFIX))
+        })?;
+
+        let install_options = match var("INSTALL_OPTIONS") {
+            Some(io) => parse_install_options(&io)?,
+            None => default_install_options(),
+        };
+
+        let dump_type = match var("DUMP_TYPE") {
+            Some(dt) => parse_dump_type(&dt)?,
+            None => DumpType::default(),
+        };
+
+        let respect_non_dumpable = match var("RESPECT_NON_DUMPABLE") {
+            Some(rnd) => parse_bool("RESPECT_NON_DUMPABLE", &rnd)?,
+            None => false,
+        };
+
+        let size_limit = match var("SIZE_LIMIT") {
+            Some(sl) => Some(parse_number("SIZE_LIMIT", &sl, "a size in bytes")?),
+            None => None,
+        };
+
+        let sanitize_stacks = match var("SANITIZE_STACKS") {
+            Some(ss) => parse_bool("SANITIZE_STACKS", &ss)?,
+            None => false,
+        };
+
+        let redact_process_info = match var("REDACT_PROCESS_INFO") {
+            Some(rpi) => parse_bool("REDACT_PROCESS_INFO", &rpi)?,
+            None => false,
+        };
+
+        let skip_under_debugger = match var("SKIP_UNDER_DEBUGGER") {
+            Some(sud) => parse_bool("SKIP_UNDER_DEBUGGER", &sud)?,
+            None => false,
+        };
+
+        let consent = match var("CONSENT") {
+            Some(c) => parse_consent(&c)?,
+            None => ConsentPolicy::default(),
+        };
+
+        let send_style = match var("SEND_STYLE") {
+            Some(ss) => parse_send_style(&ss)?,
+            None => default_send_style(),
+        };
+
+        let max_dumps = match var("MAX_DUMPS") {
+            Some(md) => Some(parse_number("MAX_DUMPS", &md, "a number of minidumps")?),
+            None => None,
+        };
+
+        let max_total_size = match var("MAX_TOTAL_SIZE") {
+            Some(mts) => Some(parse_number("MAX_TOTAL_SIZE", &mts, "a size in bytes")?),
+            None => None,
+        };
+
+        let max_dumps_per_hour = match var("MAX_DUMPS_PER_HOUR") {
+            Some(mdph) => Some(parse_number(
+                "MAX_DUMPS_PER_HOUR",
+                &mdph,
+                "a number of minidumps",
+            )?),
+            None => None,
+        };
+
+        Ok(Self {
+            crash_dir: crash_dir.into(),
+            install_options,
+            dump_type,
+            respect_non_dumpable,
+            size_limit,
+            sanitize_stacks,
+            redact_process_info,
+            skip_under_debugger,
+            consent,
+            send_style,
+            max_dumps,
+            max_total_size,
+            max_dumps_per_hour,
+        })
+    }
+
+    /// Describes, as JSON, the minidump streams and the fields of the crash
+    /// event that are captured with this configuration, and the files that
+    /// have been registered with
+    /// [`BreakpadIntegration::register_attachment`](crate::BreakpadIntegration::register_attachment)
+    /// and [`BreakpadIntegration::embed_file`](crate::BreakpadIntegration::embed_file)
+    /// so far, eg. to check them in an automated privacy review. Fields of the
+    /// event that come from the scope of the hub aren't included.
+    pub fn describe(&self) -> serde_json::Value {
+        crate::describe::describe(self)
+    }
+
+    /// Gets the settings for the minidumps written by the handler
+    pub(crate) fn minidump_settings(&self) -> MinidumpSettings {
+        MinidumpSettings::default()
+            .with_register_memory(self.dump_type == DumpType::WithRegisterMemory)
+            .with_respect_non_dumpable(self.respect_non_dumpable)
+            .with_size_limit(self.size_limit)
+            .with_sanitized_stacks(self.sanitize_stacks)
+            .with_redacted_process_info(self.redact_process_info)
+            .with_skip_under_debugger(self.skip_under_debugger)
+    }
+
+    /// Gets the limits on the minidumps kept in the crash directory
+    pub(crate) fn dir_policy(&self) -> CrashDirPolicy {
+        CrashDirPolicy {
+            max_dumps: self.max_dumps,
+            max_total_size: self.max_total_size,
+            max_dumps_per_hour: self.max_dumps_per_hour,
+        }
+    }
+
+    /// Creates the transport factory for the client, with the configured send
+    /// style, that forwards envelopes to `transport`
+    pub fn transport_factory(
+        &self,
+        transport: Arc<dyn sentry_core::TransportFactory>,
+    ) -> BreakpadTransportFactory {
+        BreakpadTransportFactory::new(self.send_style, transport)
+    }
+}
+
+fn parse_bool(name: &str, s: &str) -> Result<bool, crate::Error> {
+    match s {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        other => Err(crate::Error::InvalidConfig(format!(
+            "'{}' is not a valid value for {}{}, expected one of true, false, 1, 0",
+            other, ENV_PREFIX, name
+        ))),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(
+    name: &str,
+    s: &str,
+    expected: &str,
+) -> Result<T, crate::Error> {
+    s.parse().map_err(|_e| {
+        crate::Error::InvalidConfig(format!(
+            "'{}' is not a valid value for {}{}, expected {}",
+            s, ENV_PREFIX, name, expected
+        ))
+    })
+}
+
+fn default_install_options() -> InstallOptions {
+    InstallOptions::BothHandlers
+}
+
+fn parse_install_options(s: &str) -> Result<InstallOptions, crate::Error> {
+    Ok(match s {
+        "none" => InstallOptions::NoHandlers,
+        "exception" => InstallOptions::ExceptionHandler,
+        "signal" => InstallOptions::SignalHandler,
+        "both" => InstallOptions::BothHandlers,
+        other => {
+            return Err(crate::Error::InvalidConfig(format!(
+                "'{}' is not a valid install option, expected one of none, exception, signal, both",
+                other
+            )))
+        }
+    })
+}
+
+fn deserialize_install_options<'de, D>(d: D) -> Result<InstallOptions, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s = String::deserialize(d)?;
+    parse_install_options(&s).map_err(serde::de::Error::custom)
+}
+
+fn parse_dump_type(s: &str) -> Result<DumpType, crate::Error> {
+    Ok(match s {
+        "normal" => DumpType::Normal,
+        "register_memory" => DumpType::WithRegisterMemory,
+        other => {
+            return Err(crate::Error::InvalidConfig(format!(
+                "'{}' is not a valid dump type, expected one of normal, register_memory",
+                other
+            )))
+        }
+    })
+}
+
+fn deserialize_dump_type<'de, D>(d: D) -> Result<DumpType, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s = String::deserialize(d)?;
+    parse_dump_type(&s).map_err(serde::de::Error::custom)
+}
+
+fn parse_consent(s: &str) -> Result<ConsentPolicy, crate::Error> {
+    Ok(match s {
+        "always_send" => ConsentPolicy::AlwaysSend,
+        "ask_before_send" => ConsentPolicy::AskBeforeSend,
+        "never_send" => ConsentPolicy::NeverSend,
+        other => {
+            return Err(crate::Error::InvalidConfig(format!(
+                "'{}' is not a valid consent policy, expected one of always_send, ask_before_send, never_send",
+                other
+            )))
+        }
+    })
+}
+
+fn deserialize_consent<'de, D>(d: D) -> Result<ConsentPolicy, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s = String::deserialize(d)?;
+    parse_consent(&s).map_err(serde::de::Error::custom)
+}
+
+fn default_send_style() -> CrashSendStyle {
+    CrashSendStyle::SendNextSession
+}
+
+fn parse_send_style(s: &str) -> Result<CrashSendStyle, crate::Error> {
+    Ok(match s {
+        "send_immediately" => CrashSendStyle::SendImmediately,
+        "send_next_session" => CrashSendStyle::SendNextSession,
+        "store_all_next_session" => CrashSendStyle::StoreAllNextSession,
+        other => {
+            return Err(crate::Error::InvalidConfig(format!(
+                "'{}' is not a valid send style, expected one of send_immediately, send_next_session, store_all_next_session",
+                other
+            )))
+        }
+    })
+}
+
+fn deserialize_send_style<'de, D>(d: D) -> Result<CrashSendStyle, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s = String::deserialize(d)?;
+    parse_send_style(&s).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_config(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "breakpad-config-{}-{}.json",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn loads_file() {
+        let path = write_config(
+            "full",
+            r#"{
+                "crash_dir": "/var/crashes",
+                "install_options": "signal",
+                "dump_type": "register_memory",
+                "size_limit": 1048576,
+                "sanitize_stacks": true,
+                "consent": "ask_before_send",
+                "send_style": "store_all_next_session",
+                "max_dumps": 10,
+                "max_total_size": 104857600,
+                "max_dumps_per_hour": 3
+            }"#,
+        );
+        let config = BreakpadConfig::load(&ConfigSource::File(path.clone())).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.crash_dir, Path::new("/var/crashes"));
+        assert!(matches!(
+            config.install_options,
+            InstallOptions::SignalHandler
+        ));
+        assert_eq!(config.dump_type, DumpType::WithRegisterMemory);
+        assert_eq!(config.size_limit, Some(1024 * 1024));
+        assert!(config.sanitize_stacks);
+        assert!(!config.redact_process_info);
+        assert_eq!(config.consent, ConsentPolicy::AskBeforeSend);
+        assert!(matches!(
+            config.send_style,
+            CrashSendStyle::StoreAllNextSession
+        ));
+        assert_eq!(
+            config.dir_policy(),
+            CrashDirPolicy::default()
+                .with_max_dumps(10)
+                .with_max_total_size(100 * 1024 * 1024)
+                .with_max_dumps_per_hour(3)
+        );
+    }
+
+    #[test]
+    fn defaults_missing_fields() {
+        let path = write_config("minimal", r#"{ "crash_dir": "/var/crashes" }"#);
+        let config = BreakpadConfig::load(&ConfigSource::File(path.clone())).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            config.install_options,
+            InstallOptions::BothHandlers
+        ));
+        assert_eq!(config.dump_type, DumpType::Normal);
+        assert_eq!(config.size_limit, None);
+        assert_eq!(config.consent, ConsentPolicy::AlwaysSend);
+        assert!(matches!(config.send_style, CrashSendStyle::SendNextSession));
+        assert_eq!(config.dir_policy(), CrashDirPolicy::default());
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        for (name, contents) in [
+            (
+                "unknown",
+                r#"{ "crash_dir": "/var/crashes", "dump": true }"#,
+            ),
+            (
+                "dump_type",
+                r#"{ "crash_dir": "/var/crashes", "dump_type": "full" }"#,
+            ),
+            (
+                "consent",
+                r#"{ "crash_dir": "/var/crashes", "consent": "maybe" }"#,
+            ),
+            ("no_dir", r#"{ "install_options": "both" }"#),
+        ] {
+            let path = write_config(name, contents);
+            let loaded = BreakpadConfig::load(&ConfigSource::File(path.clone()));
+            std::fs::remove_file(&path).unwrap();
+            assert!(
+                matches!(loaded, Err(crate::Error::InvalidConfig(_))),
+                "{}",
+                name
+            );
+        }
+    }
+
+    /// The only test that touches the environment, so the variables can't
+    /// leak into another one running at the same time
+    #[test]
+    fn loads_env() {
+        let vars = [
+            ("CRASH_DIR", "/var/crashes"),
+            ("INSTALL_OPTIONS", "exception"),
+            ("DUMP_TYPE", "register_memory"),
+            ("RESPECT_NON_DUMPABLE", "1"),
+            ("CONSENT", "never_send"),
+            ("SEND_STYLE", "send_immediately"),
+            ("MAX_DUMPS_PER_HOUR", "5"),
+        ];
+        let set = |vars: &[(&str, &str)]| {
+            for (name, value) in vars {
+                std::env::set_var(format!("{}{}", ENV_PREFIX, name), value);
+            }
+        };
+        let clear = || {
+            for (name, _) in &vars {
+                std::env::remove_var(format!("{}{}", ENV_PREFIX, name));
+            }
+        };
+
+        set(&vars);
+        let config = BreakpadConfig::load(&ConfigSource::Env);
+
+        set(&[("MAX_DUMPS_PER_HOUR", "often")]);
+        let invalid = BreakpadConfig::load(&ConfigSource::Env);
+
+        clear();
+        let missing = BreakpadConfig::load(&ConfigSource::Env);
+
+        let config = config.unwrap();
+        assert_eq!(config.crash_dir, Path::new("/var/crashes"));
+        assert!(matches!(
+            config.install_options,
+            InstallOptions::ExceptionHandler
+        ));
+        assert_eq!(config.dump_type, DumpType::WithRegisterMemory);
+        assert!(config.respect_non_dumpable);
+        assert!(!config.sanitize_stacks);
+        assert_eq!(config.consent, ConsentPolicy::NeverSend);
+        assert!(matches!(config.send_style, CrashSendStyle::SendImmediately));
+        assert_eq!(
+            config.dir_policy(),
+            CrashDirPolicy::default().with_max_dumps_per_hour(5)
+        );
+
+        assert!(matches!(invalid, Err(crate::Error::InvalidConfig(_))));
+        assert!(matches!(missing, Err(crate::Error::InvalidConfig(_))));
+    }
+}
@@ -0,0 +1,69 @@
+//! Whether crashes may be uploaded, see
+//! [`BreakpadIntegration::set_consent_policy`](crate::BreakpadIntegration::set_consent_policy)
+
+use std::path::{Path, PathBuf};
+
+/// Whether the minidumps in the crash directory are uploaded without asking
+/// the user, as they may contain memory contents that regulations like the
+/// GDPR require the user to confirm before they are sent
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ConsentPolicy {
+    /// Minidumps are uploaded automatically
+    #[default]
+    AlwaysSend,
+    /// Minidumps are kept in the crash directory until each one is either
+    /// approved or discarded, see
+    /// [`BreakpadIntegration::pending_crashes`](crate::BreakpadIntegration::pending_crashes)
+    AskBeforeSend,
+    /// Minidumps are deleted without being uploaded
+    NeverSend,
+}
+
+static POLICY: std::sync::Mutex<ConsentPolicy> = std::sync::Mutex::new(ConsentPolicy::AlwaysSend);
+
+#[cfg(feature = "handler")]
+pub(crate) fn set(policy: ConsentPolicy) {
+    *POLICY
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = policy;
+}
+
+/// Gets the current policy, assuming the strictest one if another thread
+/// crashed while setting it
+pub(crate) fn current() -> ConsentPolicy {
+    match POLICY.try_lock() {
+        Ok(policy) => *policy,
+        Err(_) => ConsentPolicy::NeverSend,
+    }
+}
+
+/// A crash in the crash directory that is waiting for the user to approve or
+/// discard it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingCrash {
+    /// The file stem of the minidump
+    pub id: String,
+    /// The path of the minidump
+    pub minidump_path: PathBuf,
+    /// The size of the minidump in bytes
+    pub size: u64,
+    /// The entry in the index for the minidump, only available for minidumps
+    /// written by the integration
+    pub entry: Option<crate::IndexEntry>,
+}
+
+/// Gets the crashes in the crash directory that haven't been uploaded yet
+pub(crate) fn pending(crash_dir: &Path) -> Vec<PendingCrash> {
+    let index = crate::index::read(crash_dir);
+
+    crate::CrashStore::new(crash_dir)
+        .list()
+        .into_iter()
+        .map(|crash| PendingCrash {
+            entry: index.iter().find(|entry| entry.id == crash.id).cloned(),
+            id: crash.id,
+            minidump_path: crash.minidump_path,
+            size: crash.size,
+        })
+        .collect()
+}
@@ -0,0 +1,336 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{atomic, mpsc, Arc},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How often the crash directories are rescanned even if no new minidumps
+/// were reported, so that expired minidumps are removed and uploads that were
+/// held back by the rate limit or a lack of consent are retried
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Callback that decides if crashes may currently be uploaded
+type Consent = dyn Fn() -> bool + Send;
+
+/// Uploads the minidumps written to one or more crash directories, so that a
+/// small side process can centralize uploading for a fleet of applications
+/// that only write their minidumps, eg. with a
+/// [`BreakpadHandler`](crate::BreakpadHandler) that isn't wrapped in a
+/// [`BreakpadIntegration`](crate::BreakpadIntegration).
+///
+/// The minidumps are sent to the hub passed to [`CrashUploaderDaemon::new`],
+/// whose client must be configured with the DSN of the project and a
+/// [`BreakpadTransportFactory`](crate::BreakpadTransportFactory). The policies
+/// are applied every time a directory is scanned, which happens when a
+/// [`DumpWatcher`](crate::DumpWatcher) reports a new minidump, and every
+/// minute regardless.
+///
+/// ```no_run
+/// # fn run(hub: std::sync::Arc<sentry_core::Hub>) -> Result<(), sentry_contrib_breakpad::Error> {
+/// use sentry_contrib_breakpad::CrashUploaderDaemon;
+/// use std::time::Duration;
+///
+/// let _daemon = CrashUploaderDaemon::new(hub)
+///     .watch_dir("/var/crashes/app-a")
+///     .watch_dir("/var/crashes/app-b")
+///     .with_retention(Duration::from_secs(7 * 24 * 60 * 60))
+///     .with_rate_limit(10, Duration::from_secs(60 * 60))
+///     .start()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct CrashUploaderDaemon {
+    hub: Arc<sentry_core::Hub>,
+    crash_dirs: Vec<PathBuf>,
+    retention: Option<Duration>,
+    rate_limit: Option<(u32, Duration)>,
+    consent: Option<Box<Consent>>,
+}
+
+impl CrashUploaderDaemon {
+    /// Creates a daemon that uploads to the client of the specified hub
+    pub fn new(hub: Arc<sentry_core::Hub>) -> Self {
+        Self {
+            hub,
+            crash_dirs: Vec::new(),
+            retention: None,
+            rate_limit: None,
+            consent: None,
+        }
+    }
+
+    /// Adds a crash directory to upload the minidumps of, including the ones
+    /// that are already in it when the daemon is started
+    pub fn watch_dir(mut self, crash_dir: impl Into<PathBuf>) -> Self {
+        self.crash_dirs.push(crash_dir.into());
+        self
+    }
+
+    /// Removes minidumps that are older than `max_age` without uploading them,
+    /// eg. ones that were held back for a long time by a lack of consent
+    pub fn with_retention(mut self, max_age: Duration) -> Self {
+        self.retention = Some(max_age);
+        self
+    }
+
+    /// Uploads at most `max_uploads` minidumps in a window of time, across all
+    /// of the crash directories. Minidumps over the limit stay in their crash
+    /// directory until a later window.
+    pub fn with_rate_limit(mut self, max_uploads: u32, window: Duration) -> Self {
+        self.rate_limit = Some((max_uploads, window));
+        self
+    }
+
+    /// Only uploads minidumps while `consent` returns `true`, eg. if the user
+    /// has opted in to crash reporting. Minidumps stay in their crash
+    /// directory, subject to the retention, until consent is given.
+    pub fn with_consent<F>(mut self, consent: F) -> Self
+    where
+        F: Fn() -> bool + Send + 'static,
+    {
+        self.consent = Some(Box::new(consent));
+        self
+    }
+
+    /// Starts watching the crash directories and uploading their minidumps on
+    /// a background thread, until the returned handle is dropped
+    pub fn start(self) -> Result<RunningDaemon, crate::Error> {
+        let (tx, rx) = mpsc::channel();
+
+        let watchers = self
+            .crash_dirs
+            .iter()
+            .map(|crash_dir| {
+                let tx = tx.clone();
+                crate::DumpWatcher::new(crash_dir, move |_| {
+                    let _ = tx.send(());
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let stop = Arc::new(atomic::AtomicBool::new(false));
+        let thread_stop = stop.clone();
+
+        let thread = std::thread::Builder::new()
+            .name("breakpad-upload-daemon".to_owned())
+            .spawn(move || {
+                let mut window = Window::default();
+
+                while !thread_stop.load(atomic::Ordering::Relaxed) {
+                    for crash_dir in &self.crash_dirs {
+                        if let Some(max_age) = self.retention {
+                            remove_expired(crash_dir, max_age);
+                        }
+
+                        if self.consent.as_ref().is_some_and(|consent| !consent()) {
+                            continue;
+                        }
+
+                        crate::upload::upload_minidumps(crash_dir, &self.hub, &mut |_| {
+                            window.try_acquire(self.rate_limit)
+                        });
+                    }
+
+                    // Wait for a new minidump, coalescing the notifications
+                    // for minidumps that are written at the same time
+                    if rx.recv_timeout(SWEEP_INTERVAL).is_ok() {
+                        while rx.try_recv().is_ok() {}
+                    }
+                }
+            })?;
+
+        Ok(RunningDaemon {
+            stop,
+            wake: tx,
+            _watchers: watchers,
+            thread: Some(thread),
+        })
+    }
+}
+
+/// A started [`CrashUploaderDaemon`], which stops watching and uploading when
+/// dropped, waiting for an upload that is in progress to finish
+pub struct RunningDaemon {
+    stop: Arc<atomic::AtomicBool>,
+    wake: mpsc::Sender<()>,
+    _watchers: Vec<crate::DumpWatcher>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Drop for RunningDaemon {
+    fn drop(&mut self) {
+        self.stop.store(true, atomic::Ordering::Relaxed);
+        let _ = self.wake.send(());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The uploads in the current rate limit window
+#[derive(Default)]
+struct Window {
+    start: Option<Instant>,
+    uploads: u32,
+}
+
+impl Window {
+    /// Counts an upload against the rate limit, if it's within the limit
+    fn try_acquire(&mut self, rate_limit: Option<(u32, Duration)>) -> bool {
+        let (max_uploads, window) = match rate_limit {
+            Some(rl) => rl,
+            None => return true,
+        };
+
+        let now = Instant::now();
+        match self.start {
+            Some(start) if now.duration_since(start) < window => {}
+            _ => {
+                self.start = Some(now);
+                self.uploads = 0;
+            }
+        }
+
+        if self.uploads >= max_uploads {
+            return false;
+        }
+
+        self.uploads += 1;
+        true
+    }
+}
+
+/// Removes the minidumps, and everything written along with them, that were
+/// last modified more than `max_age` ago
+fn remove_expired(crash_dir: &Path, max_age: Duration) {
+    let store = crate::CrashStore::new(crash_dir);
+    let mut removed = Vec::new();
+    for crash in store.list() {
+        let expired = SystemTime::now()
+            .duration_since(crash.modified)
+            .is_ok_and(|age| age > max_age);
+        if !expired {
+            continue;
+        }
+
+        // Left alone while another process is uploading it
+        let _claim = match store.claim(&crash) {
+            Some(claim) => claim,
+            None => continue,
+        };
+
+        if let Some(id) = crate::policy::remove(crash_dir, &crash.minidump_path) {
+            removed.push(id);
+        }
+    }
+
+    // Expired minidumps are removed on purpose, so they aren't reported as
+    // missing from the index
+    if !removed.is_empty() {
+        crate::index::prune(crash_dir, &removed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a crash directory with empty minidumps for a test
+    fn fixture(name: &str, ids: &[&str]) -> crate::CrashStore {
+        let crash_dir = std::env::temp_dir().join(format!(
+            "sentry-contrib-breakpad-daemon-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&crash_dir);
+        std::fs::create_dir_all(&crash_dir).unwrap();
+
+        for id in ids {
+            std::fs::write(crash_dir.join(format!("{}.dmp", id)), b"MDMP").unwrap();
+        }
+        crate::CrashStore::new(crash_dir)
+    }
+
+    struct Discard;
+
+    impl sentry_core::Transport for Discard {
+        fn send_envelope(&self, _envelope: sentry_core::Envelope) {}
+    }
+
+    fn hub() -> Arc<sentry_core::Hub> {
+        let mut options = sentry_core::ClientOptions::default();
+        options.dsn = Some("https://public@sentry.invalid/1".parse().unwrap());
+        options.transport = Some(Arc::new(
+            |_: &sentry_core::ClientOptions| -> Arc<dyn sentry_core::Transport> {
+                Arc::new(Discard)
+            },
+        ));
+        let client = sentry_core::Client::from(options);
+        Arc::new(sentry_core::Hub::new(
+            Some(Arc::new(client)),
+            Arc::new(Default::default()),
+        ))
+    }
+
+    #[test]
+    fn limits_uploads_per_window() {
+        let mut window = Window::default();
+        let rate_limit = Some((2, Duration::from_secs(60 * 60)));
+
+        assert!(window.try_acquire(rate_limit));
+        assert!(window.try_acquire(rate_limit));
+        assert!(!window.try_acquire(rate_limit));
+        assert!(window.try_acquire(None));
+
+        // A new window starts once the current one has passed
+        let mut window = Window::default();
+        let rate_limit = Some((1, Duration::ZERO));
+        assert!(window.try_acquire(rate_limit));
+        assert!(window.try_acquire(rate_limit));
+    }
+
+    #[test]
+    fn claimed_crashes_dont_count_against_rate_limit() {
+        let store = fixture("rate-limit", &["a", "b"]);
+
+        // Whichever is visited first is being uploaded by another process
+        let crashes = store.list();
+        let claim = store.claim(&crashes[0]).unwrap();
+
+        let mut window = Window::default();
+        crate::upload::upload_minidumps(store.crash_dir(), &hub(), &mut |_| {
+            window.try_acquire(Some((1, Duration::from_secs(60 * 60))))
+        });
+
+        let remaining: Vec<_> = store.list().into_iter().map(|crash| crash.id).collect();
+        assert_eq!(remaining, vec![crashes[0].id.clone()]);
+
+        drop(claim);
+        let _ = std::fs::remove_dir_all(store.crash_dir());
+    }
+
+    #[test]
+    fn removes_expired_unless_claimed() {
+        let store = fixture("retention", &["a", "b"]);
+        let claim = store.claim(
+            &store
+                .list()
+                .into_iter()
+                .find(|crash| crash.id == "b")
+                .unwrap(),
+        );
+
+        std::thread::sleep(Duration::from_millis(10));
+        remove_expired(store.crash_dir(), Duration::from_millis(1));
+
+        let remaining: Vec<_> = store.list().into_iter().map(|crash| crash.id).collect();
+        assert_eq!(remaining, vec!["b".to_owned()]);
+
+        // Minidumps that haven't expired yet are kept
+        drop(claim);
+        remove_expired(store.crash_dir(), Duration::from_secs(60 * 60));
+        assert_eq!(store.list().len(), 1);
+
+        let _ = std::fs::remove_dir_all(store.crash_dir());
+    }
+}
@@ -0,0 +1,130 @@
+//! The modules that were loaded when the crash occurred, attached to the crash
+//! event so that Sentry can symbolicate it even without the minidump, eg. if
+//! the minidump attachment is rejected for its size
+
+use crate::minidump::Module;
+use sentry_core::protocol::{
+    self as proto,
+    debugid::{CodeId, DebugId},
+};
+use std::{collections::BTreeMap, path::Path};
+
+/// `RSDS` in little endian, the `CodeView` record of a PDB 7.0 file, which
+/// breakpad also writes for the UUID of a Mach-O image
+const CV_SIGNATURE_PDB70: u32 = 0x5344_5352;
+/// `BpEL` in little endian, breakpad's `CodeView` record for the build id of an
+/// ELF file
+const CV_SIGNATURE_ELF: u32 = 0x4270_454c;
+
+/// The debug images and file versions of the modules in the minidump
+pub(crate) struct LoadedModules {
+    pub(crate) debug_meta: proto::DebugMeta,
+    /// The file version of each module that has one, keyed by the file name
+    pub(crate) versions: BTreeMap<String, String>,
+}
+
+/// Reads the module list from the minidump, skipping modules without a debug
+/// id, as Sentry can't look up debug files for them anyway
+pub(crate) fn capture(minidump_path: &Path) -> Option<LoadedModules> {
+    let bytes = match std::fs::read(minidump_path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            debug_print!(
+                "unable to read minidump '{}' for its modules: {}",
+                minidump_path.display(),
+                e
+            );
+            return None;
+        }
+    };
+
+    let mut loaded = LoadedModules {
+        debug_meta: proto::DebugMeta::default(),
+        versions: BTreeMap::new(),
+    };
+
+    for module in crate::minidump::module_list(&bytes) {
+        if let Some(version) = &module.version {
+            let file_name = module
+                .name
+                .rsplit(['/', '\\'])
+                .next()
+                .unwrap_or(&module.name);
+            loaded
+                .versions
+                .insert(file_name.to_owned(), version.clone());
+        }
+
+        if let Some(image) = debug_image(module) {
+            loaded.debug_meta.images.push(image.into());
+        }
+    }
+
+    if loaded.debug_meta.images.is_empty() {
+        return None;
+    }
+
+    Some(loaded)
+}
+
+fn debug_image(module: Module) -> Option<proto::SymbolicDebugImage> {
+    let cv = &module.cv_record;
+    let signature = u32::from_le_bytes([*cv.first()?, *cv.get(1)?, *cv.get(2)?, *cv.get(3)?]);
+
+    let (id, code_id, debug_file) = match signature {
+        CV_SIGNATURE_PDB70 => {
+            let guid = cv.get(4..20)?;
+            let age = cv.get(20..24)?;
+            let id =
+                DebugId::from_guid_age(guid, u32::from_le_bytes([age[0], age[1], age[2], age[3]]))
+                    .ok()?;
+
+            let debug_file = cv
+                .get(24..)
+                .and_then(|name| name.split(|b| *b == 0).next())
+                .filter(|name| !name.is_empty())
+                .map(|name| String::from_utf8_lossy(name).into_owned());
+
+            // Only PE images have a timestamp, which along with the size of
+            // the image is how Windows symbol servers identify them
+            let code_id = if module.time_date_stamp != 0 {
+                Some(CodeId::new(format!(
+                    "{:08X}{:x}",
+                    module.time_date_stamp, module.size_of_image
+                )))
+            } else {
+                None
+            };
+
+            (id, code_id, debug_file)
+        }
+        CV_SIGNATURE_ELF => {
+            let build_id = cv.get(4..)?;
+            if build_id.is_empty() {
+                return None;
+            }
+
+            // The same truncation or padding of the build id to a GUID that
+            // breakpad's dump_syms does
+            let mut guid = [0u8; 16];
+            let len = build_id.len().min(16);
+            guid[..len].copy_from_slice(&build_id[..len]);
+            let id = DebugId::from_guid_age(&guid, 0).ok()?;
+
+            let code_id: String = build_id.iter().map(|b| format!("{:02x}", b)).collect();
+            (id, Some(CodeId::new(code_id)), None)
+        }
+        _ => return None,
+    };
+
+    Some(proto::SymbolicDebugImage {
+        name: module.name,
+        arch: None,
+        image_addr: module.base_of_image.into(),
+        image_size: module.size_of_image.into(),
+        image_vmaddr: proto::Addr(0),
+        id,
+        code_id,
+        debug_file,
+    })
+}
@@ -0,0 +1,308 @@
+//! A machine-readable description of the data that is captured for a crash,
+//! see [`BreakpadConfig::describe`](crate::BreakpadConfig::describe)
+
+use crate::{minidump::StreamType, BreakpadConfig, DumpType, InstallOptions};
+use serde_json::{json, Value};
+
+/// The streams breakpad writes to every minidump on the current platform, and
+/// what they contain
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const STREAMS: &[(StreamType, &str)] = &[
+    (
+        StreamType::ThreadList,
+        "registers and stack memory of every thread",
+    ),
+    (
+        StreamType::ModuleList,
+        "path, version, and build id of every loaded module",
+    ),
+    (
+        StreamType::MemoryList,
+        "the stack memory of the threads, and registered app memory",
+    ),
+    (
+        StreamType::Exception,
+        "the signal, and the registers of the crashing thread",
+    ),
+    (
+        StreamType::SystemInfo,
+        "the cpu and version of the operating system",
+    ),
+    (StreamType::LinuxCpuInfo, "the contents of /proc/cpuinfo"),
+    (
+        StreamType::LinuxProcStatus,
+        "the contents of /proc/self/status",
+    ),
+    (
+        StreamType::LinuxLsbRelease,
+        "the contents of /etc/lsb-release",
+    ),
+    (StreamType::LinuxCmdLine, "the command line of the process"),
+    (
+        StreamType::LinuxEnviron,
+        "the environment variables of the process",
+    ),
+    (StreamType::LinuxAuxv, "the auxiliary vector of the process"),
+    (
+        StreamType::LinuxMaps,
+        "the memory mappings of the process, including file paths",
+    ),
+    (StreamType::LinuxDsoDebug, "the state of the dynamic linker"),
+];
+
+#[cfg(windows)]
+const STREAMS: &[(StreamType, &str)] = &[
+    (
+        StreamType::ThreadList,
+        "registers and stack memory of every thread",
+    ),
+    (
+        StreamType::ModuleList,
+        "path, version, and debug id of every loaded module",
+    ),
+    (
+        StreamType::UnloadedModuleList,
+        "path of recently unloaded modules",
+    ),
+    (
+        StreamType::MemoryList,
+        "the stack memory of the threads, and registered app memory",
+    ),
+    (
+        StreamType::Exception,
+        "the exception record, and the registers of the crashing thread",
+    ),
+    (
+        StreamType::SystemInfo,
+        "the cpu and version of the operating system",
+    ),
+    (
+        StreamType::MiscInfo,
+        "the process id, and the start and cpu times of the process",
+    ),
+    (
+        StreamType::BreakpadInfo,
+        "the ids of the crashing and the dumping thread",
+    ),
+];
+
+#[cfg(target_os = "macos")]
+const STREAMS: &[(StreamType, &str)] = &[
+    (
+        StreamType::ThreadList,
+        "registers and stack memory of every thread",
+    ),
+    (
+        StreamType::ModuleList,
+        "path, version, and uuid of every loaded image",
+    ),
+    (
+        StreamType::MemoryList,
+        "the stack memory of the threads, and registered app memory",
+    ),
+    (
+        StreamType::Exception,
+        "the mach exception, and the registers of the crashing thread",
+    ),
+    (
+        StreamType::SystemInfo,
+        "the cpu and version of the operating system",
+    ),
+    (
+        StreamType::MiscInfo,
+        "the process id, and the start and cpu times of the process",
+    ),
+    (
+        StreamType::BreakpadInfo,
+        "the ids of the crashing and the dumping thread",
+    ),
+];
+
+/// The tags that can be set on the crash event
+const TAGS: &[(&str, &str)] = &[
+    (
+        "breakpad.total_crashes",
+        "the number of crashes persisted in the crash directory",
+    ),
+    (
+        "breakpad.dump_quality",
+        "whether the minidump was written in full, truncated to the size limit, failed, or was discarded",
+    ),
+    (
+        "breakpad.sanitized_stacks",
+        "whether the stacks in the minidump were sanitized",
+    ),
+    (
+        "breakpad.redaction",
+        "whether removing the command line and environment from the minidump failed",
+    ),
+    ("breakpad.signal", "the signal that caused the crash"),
+    ("breakpad.signal_name", "the name of the signal"),
+    (
+        "breakpad.signal_code",
+        "the name of the signal or exception code",
+    ),
+    (
+        "breakpad.exception_code",
+        "the name of the exception code on Windows",
+    ),
+    (
+        "breakpad.fail_fast",
+        "whether the crash was a fail fast exception on Windows",
+    ),
+    (
+        "breakpad.stack_overflow",
+        "whether the crash was the crashing thread overflowing its stack",
+    ),
+    (
+        "breakpad.app_hang",
+        "whether the minidump was written by a HangMonitor for a hang",
+    ),
+    (
+        "breakpad.panic",
+        "whether the minidump was written for a Rust panic",
+    ),
+    (
+        "breakpad.mach_exception",
+        "the mach exception type on macOS",
+    ),
+    (
+        "breakpad.recurrence",
+        "whether the crash is the same as the previously uploaded one",
+    ),
+    (
+        "breakpad.metadata_level",
+        "how much of the event was captured within the metadata budget",
+    ),
+];
+
+/// The extra data that can be set on the crash event
+const EXTRA: &[(&str, &str)] = &[
+    (
+        "breakpad.crash_reason",
+        "a description of the signal, exception, or panic",
+    ),
+    (
+        "breakpad.last_error",
+        "the last Win32 error of the crashing thread",
+    ),
+    (
+        "breakpad.fault_file",
+        "the path of the mapped file a SIGBUS occurred in",
+    ),
+    (
+        "breakpad.fault_file_offset",
+        "the offset in the mapped file a SIGBUS occurred at",
+    ),
+    ("breakpad.panic_location", "where the panic was raised"),
+    (
+        "breakpad.panic_thread",
+        "the name of the thread that panicked",
+    ),
+    ("breakpad.mach_code", "the code of the mach exception"),
+    (
+        "breakpad.mach_subcode",
+        "the subcode of the mach exception, eg. the faulting address",
+    ),
+    (
+        "breakpad.minidump_unavailable",
+        "why the minidump couldn't be written, including its path",
+    ),
+    (
+        "breakpad.minidump_problems",
+        "problems with the minidump, only in debug builds",
+    ),
+    (
+        "breakpad.module_versions",
+        "the file version of every loaded module that has one",
+    ),
+    (
+        "breakpad.original_timestamp",
+        "the timestamp of the crash if the clock was corrected",
+    ),
+];
+
+/// The contexts that can be set on the crash event, in addition to the ones
+/// set by the scope of the hub
+const CONTEXTS: &[(&str, &str)] = &[
+    (
+        "hardening",
+        "ASLR, seccomp, no_new_privs, NX, and container state on Linux/Android",
+    ),
+    ("annotations", "the keys and values set with set_annotation"),
+];
+
+pub(crate) fn describe(config: &BreakpadConfig) -> Value {
+    let fields = |fields: &[(&str, &str)]| -> Vec<Value> {
+        fields
+            .iter()
+            .map(|(name, contents)| json!({ "name": name, "contents": contents }))
+            .collect()
+    };
+
+    let streams: Vec<_> = STREAMS
+        .iter()
+        .filter(|(ty, _)| {
+            !(config.redact_process_info
+                && matches!(ty, StreamType::LinuxCmdLine | StreamType::LinuxEnviron))
+        })
+        .map(|(ty, contents)| (*ty, contents.to_string()))
+        .chain(
+            crate::embedded::registered()
+                .into_iter()
+                .map(|(path, max_len)| {
+                    (
+                        StreamType::EmbeddedFile,
+                        format!("up to {} bytes of {}", max_len, path.display()),
+                    )
+                }),
+        )
+        .chain(
+            crate::telemetry::registered()
+                .into_iter()
+                .map(|(name, len)| {
+                    (
+                        StreamType::TelemetryRing,
+                        format!("the {} bytes of the telemetry ring {}", len, name),
+                    )
+                }),
+        )
+        .map(|(ty, contents)| json!({ "type": ty, "contents": contents }))
+        .collect();
+
+    let attachments: Vec<_> = crate::attachments::registered()
+        .into_iter()
+        .map(|(path, content_type)| {
+            json!({ "path": path.display().to_string(), "content_type": content_type })
+        })
+        .collect();
+
+    json!({
+        "platform": std::env::consts::OS,
+        "crash_dir": config.crash_dir.display().to_string(),
+        "install_options": match config.install_options {
+            InstallOptions::NoHandlers => "none",
+            InstallOptions::ExceptionHandler => "exception",
+            InstallOptions::SignalHandler => "signal",
+            InstallOptions::BothHandlers => "both",
+        },
+        "minidump": {
+            "streams": streams,
+            "dump_type": match config.dump_type {
+                DumpType::Normal => "normal",
+                DumpType::WithRegisterMemory => "register_memory",
+            },
+            "respect_non_dumpable": config.respect_non_dumpable,
+            "size_limit": config.size_limit,
+            "sanitize_stacks": config.sanitize_stacks,
+            "redact_process_info": config.redact_process_info,
+        },
+        "event": {
+            "debug_meta": "the path, address, size, and debug id of every loaded module",
+            "tags": fields(TAGS),
+            "extra": fields(EXTRA),
+            "contexts": fields(CONTEXTS),
+        },
+        "attachments": attachments,
+    })
+}
@@ -0,0 +1,67 @@
+//! Small files that are embedded in the minidump itself, see
+//! [`BreakpadIntegration::embed_file`](crate::BreakpadIntegration::embed_file)
+
+use crate::minidump::{NewStream, StreamType};
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+static REGISTERED: std::sync::Mutex<Vec<(PathBuf, u64)>> = std::sync::Mutex::new(Vec::new());
+
+pub(crate) fn register(path: PathBuf, max_len: u64) {
+    REGISTERED
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .push((path, max_len));
+}
+
+/// Gets the files that have been registered so far
+pub(crate) fn registered() -> Vec<(PathBuf, u64)> {
+    REGISTERED
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .clone()
+}
+
+/// Reads the registered files and appends them to the minidump, each as its
+/// own stream. Files are read up to their maximum length rather than by their
+/// size, as files in procfs report a size of 0.
+pub(crate) fn capture(minidump_path: &Path) {
+    // Another thread could have crashed while registering a file
+    let registered = match REGISTERED.try_lock() {
+        Ok(registered) => registered,
+        Err(_) => return,
+    };
+
+    let streams: Vec<_> = registered
+        .iter()
+        .filter_map(|(path, max_len)| {
+            let path_str = path.to_str()?;
+
+            let mut data = Vec::with_capacity(path_str.len() + 1);
+            data.extend_from_slice(path_str.as_bytes());
+            data.push(0);
+
+            match std::fs::File::open(path).and_then(|f| f.take(*max_len).read_to_end(&mut data)) {
+                Ok(_) => Some(NewStream {
+                    stream_type: StreamType::EmbeddedFile,
+                    data,
+                    relative_rvas: Vec::new(),
+                }),
+                Err(e) => {
+                    debug_print!("unable to read {} to embed: {}", path.display(), e);
+                    None
+                }
+            }
+        })
+        .collect();
+
+    if let Err(e) = crate::minidump::append_streams(minidump_path, &streams) {
+        debug_print!(
+            "unable to embed files in {}: {}",
+            minidump_path.display(),
+            e
+        );
+    }
+}
@@ -2,18 +2,22 @@ use std::fmt;
 
 #[derive(Debug)]
 pub enum Error {
+    #[cfg(feature = "handler")]
     Handler(breakpad_handler::Error),
     Io(std::io::Error),
     /// Paths in some cases are required to be utf-8 compatible
     NonUtf8Path(std::path::PathBuf),
+    /// The configuration could not be loaded
+    InvalidConfig(String),
 }
 
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
+            #[cfg(feature = "handler")]
             Self::Handler(e) => Some(e),
             Self::Io(e) => Some(e),
-            Self::NonUtf8Path(_) => None,
+            Self::NonUtf8Path(_) | Self::InvalidConfig(_) => None,
         }
     }
 }
@@ -21,13 +25,16 @@ impl std::error::Error for Error {
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            #[cfg(feature = "handler")]
             Self::Handler(e) => write!(f, "handler error: {}", e),
             Self::Io(e) => write!(f, "io error: {}", e),
             Self::NonUtf8Path(p) => write!(f, "{} is not a utf-8 path", p.display()),
+            Self::InvalidConfig(reason) => write!(f, "invalid configuration: {}", reason),
         }
     }
 }
 
+#[cfg(feature = "handler")]
 impl From<breakpad_handler::Error> for Error {
     fn from(e: breakpad_handler::Error) -> Self {
         Self::Handler(e)
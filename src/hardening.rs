@@ -0,0 +1,106 @@
+//! Security relevant state of the crashed process, which helps to tell if a
+//! crash that looks exploitable actually is, eg. a wild jump is a lot less
+//! interesting if the process was running with ASLR disabled under seccomp
+
+use sentry_core::protocol as proto;
+
+/// The name of the context the state is attached to the crash event as
+pub(crate) const CONTEXT_NAME: &str = "hardening";
+
+/// `ADDR_NO_RANDOMIZE` from `linux/personality.h`
+const ADDR_NO_RANDOMIZE: u32 = 0x004_0000;
+
+/// Files that container runtimes create in the root of the container
+const CONTAINER_MARKERS: [(&str, &str); 2] =
+    [("/.dockerenv", "docker"), ("/run/.containerenv", "podman")];
+
+/// Captures the hardening state of the current process. This reads a few
+/// small files from procfs, so it should only be called after the minidump
+/// has been written.
+pub(crate) fn capture() -> proto::Context {
+    let mut map = proto::Map::new();
+
+    // The personality can disable ASLR for a single process, eg. via
+    // `setarch -R`, while randomize_va_space controls it system wide
+    if let Some(personality) = std::fs::read_to_string("/proc/self/personality")
+        .ok()
+        .and_then(|p| u32::from_str_radix(p.trim(), 16).ok())
+    {
+        map.insert(
+            "aslr".to_owned(),
+            (personality & ADDR_NO_RANDOMIZE == 0).into(),
+        );
+    }
+
+    if let Some(randomize) = std::fs::read_to_string("/proc/sys/kernel/randomize_va_space")
+        .ok()
+        .and_then(|r| r.trim().parse::<u8>().ok())
+    {
+        map.insert("randomize_va_space".to_owned(), randomize.into());
+    }
+
+    if let Ok(status) = std::fs::read_to_string("/proc/self/status") {
+        for line in status.lines() {
+            let field = match line
+                .split_once(':')
+                .and_then(|(key, value)| Some((key, value.trim().parse::<u8>().ok()?)))
+            {
+                Some(kv) => kv,
+                None => continue,
+            };
+
+            match field {
+                ("Seccomp", mode) => {
+                    let mode = match mode {
+                        0 => "disabled",
+                        1 => "strict",
+                        2 => "filter",
+                        _ => "unknown",
+                    };
+                    map.insert("seccomp".to_owned(), mode.into());
+                }
+                ("NoNewPrivs", nnp) => {
+                    map.insert("no_new_privs".to_owned(), (nnp != 0).into());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // NX is always available on 64-bit ARM, but is a CPU feature on x86
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if let Ok(cpuinfo) = std::fs::read_to_string("/proc/cpuinfo") {
+        let nx = cpuinfo
+            .lines()
+            .find(|line| line.starts_with("flags"))
+            .is_some_and(|flags| flags.split_whitespace().any(|flag| flag == "nx"));
+        map.insert("nx".to_owned(), nx.into());
+    }
+
+    if let Some(container) = container() {
+        map.insert("container".to_owned(), container.into());
+    }
+
+    proto::Context::Other(map)
+}
+
+/// Detects if the process is running in a container, and which runtime
+fn container() -> Option<String> {
+    // Set by systemd-nspawn, lxc, and podman
+    if let Ok(container) = std::env::var("container") {
+        return Some(container);
+    }
+
+    if let Some((_, runtime)) = CONTAINER_MARKERS
+        .iter()
+        .find(|(marker, _)| std::path::Path::new(marker).exists())
+    {
+        return Some((*runtime).to_owned());
+    }
+
+    let cgroup = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+    ["docker", "kubepods", "lxc", "containerd"]
+        .iter()
+        .find(|runtime| cgroup.contains(*runtime))
+        .map(|runtime| (*runtime).to_owned())
+}
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "handler")]
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The name of the index file in the crash directory
+const INDEX_FILE: &str = "index.json";
+
+/// An entry in the index of minidumps in the crash directory, one is appended
+/// for every minidump when the crash occurs
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// The file stem of the minidump, ie. `<id>.dmp`
+    pub id: String,
+    /// When the crash occurred, in seconds since the Unix epoch
+    pub timestamp: u64,
+    /// The signal that caused the crash, only available on Linux/Android
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<i32>,
+    /// The release of the application that crashed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release: Option<String>,
+    /// The id of the boot the crash occurred in, only available on
+    /// Linux/Android
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boot_id: Option<String>,
+    /// The time since boot when the crash occurred, in milliseconds, which is
+    /// used to correct the timestamp if the system clock was wrong at the
+    /// time. Only available on Linux/Android
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uptime_ms: Option<u64>,
+}
+
+impl IndexEntry {
+    /// Gets the path of the minidump this entry is for
+    pub fn minidump_path(&self, crash_dir: &Path) -> PathBuf {
+        crash_dir.join(format!("{}.dmp", self.id))
+    }
+
+    /// Gets the reference to the monotonic clock at the time of the crash
+    pub(crate) fn boot_reference(&self) -> Option<crate::clock::BootReference> {
+        Some(crate::clock::BootReference {
+            boot_id: self.boot_id.clone()?,
+            uptime: std::time::Duration::from_millis(self.uptime_ms?),
+        })
+    }
+}
+
+/// Appends the entry to the index with a single write, as this is done in the
+/// crashing process
+#[cfg(feature = "handler")]
+pub(crate) fn append(crash_dir: &Path, entry: &IndexEntry) {
+    let index_path = crash_dir.join(INDEX_FILE);
+
+    let mut line = match serde_json::to_vec(entry) {
+        Ok(line) => line,
+        Err(e) => {
+            debug_print!("failed to serialize index entry: {}", e);
+            return;
+        }
+    };
+    line.push(b'\n');
+
+    let res = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&index_path)
+        .and_then(|mut f| f.write_all(&line));
+
+    if let Err(e) = res {
+        debug_print!("failed to append to {}: {}", index_path.display(), e);
+    }
+}
+
+/// Reads every entry in the index, skipping any lines that can't be parsed,
+/// eg. if the process was killed in the middle of appending to it
+pub(crate) fn read(crash_dir: &Path) -> Vec<IndexEntry> {
+    let index_path = crash_dir.join(INDEX_FILE);
+
+    let contents = match std::fs::read_to_string(&index_path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                debug_print!("unable to read {}: {}", index_path.display(), e);
+            }
+            return Vec::new();
+        }
+    };
+
+    contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                debug_print!("skipping invalid index entry '{}': {}", line, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Rewrites the index to only contain the entries whose minidump is still in
+/// the crash directory, ie. that haven't been uploaded yet. Entries whose
+/// minidump went missing without being uploaded by us are reported, as that
+/// means something else removed or tampered with the crash directory.
+pub(crate) fn prune(crash_dir: &Path, uploaded: &[String]) {
+    let index_path = crash_dir.join(INDEX_FILE);
+
+    let entries = read(crash_dir);
+    if entries.is_empty() {
+        return;
+    }
+
+    let mut contents = Vec::new();
+    for entry in entries {
+        if entry.minidump_path(crash_dir).exists() {
+            if serde_json::to_writer(&mut contents, &entry).is_ok() {
+                contents.push(b'\n');
+            }
+        } else if !uploaded.contains(&entry.id) {
+            debug_print!(
+                "minidump '{}' in the index is missing from '{}'",
+                entry.id,
+                crash_dir.display()
+            );
+        }
+    }
+
+    let res = if contents.is_empty() {
+        std::fs::remove_file(&index_path)
+    } else {
+        std::fs::write(&index_path, &contents)
+    };
+
+    if let Err(e) = res {
+        debug_print!("failed to update {}: {}", index_path.display(), e);
+    }
+}
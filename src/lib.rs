@@ -6,6 +6,21 @@
 //! 2. Initialize a Sentry [`Hub`](https://docs.rs/sentry-core/0.23.0/sentry_core/struct.Hub.html).
 //! 3. Create the [`BreakpadIntegration`] which will attach a crash handler and
 //! send any previous crashes that are in the crash directoy specified.
+//!
+//! With the `dump-watcher` feature enabled, a [`DumpWatcher`] can be used to be
+//! notified of minidumps written to the crash directory by other processes,
+//! and a [`CrashUploaderDaemon`] to upload the minidumps of other processes
+//! from a side process.
+//!
+//! With the `test-support` feature enabled, a [`CapturingTransport`] can be
+//! used to capture the envelopes that would be sent to Sentry in memory.
+//!
+//! Without the default `handler` feature, breakpad isn't built at all, and
+//! only the [`CrashStore`] is available to upload the minidumps that other
+//! processes wrote to a crash directory.
+//!
+//! The types from `breakpad-handler` are re-exported, and the most commonly
+//! used ones are available via `use sentry_contrib_breakpad::prelude::*`.
 
 macro_rules! debug_print {
     ($($arg:tt)*) => {
@@ -20,11 +35,95 @@ macro_rules! debug_print {
     }
 }
 
+mod attachments;
+#[cfg(feature = "handler")]
 mod breakpad_integration;
+mod clock;
+mod compact;
+#[cfg(feature = "handler")]
+mod config;
+mod consent;
+#[cfg(feature = "dump-watcher")]
+mod daemon;
+#[cfg(feature = "handler")]
+mod debug_meta;
+#[cfg(feature = "handler")]
+mod describe;
+#[cfg(feature = "handler")]
+mod embedded;
 mod error;
+#[cfg(all(feature = "handler", any(target_os = "linux", target_os = "android")))]
+mod hardening;
+mod index;
+#[cfg(all(feature = "handler", target_os = "macos"))]
+mod mach;
+pub mod minidump;
+#[cfg(feature = "handler")]
+mod panic;
+mod policy;
+mod queue;
+mod recurrence;
 mod shared;
+#[cfg(feature = "handler")]
+mod stats;
+mod store;
+#[cfg(feature = "handler")]
+mod task_names;
+mod telemetry;
+#[cfg(feature = "test-support")]
+mod test_support;
 mod transport;
+mod upload;
+#[cfg(feature = "dump-watcher")]
+mod watcher;
 
-pub use breakpad_integration::{BreakpadIntegration, InstallOptions};
+#[cfg(all(feature = "handler", any(target_os = "linux", target_os = "android")))]
+pub use breakpad_handler::AfterDump;
+#[cfg(feature = "handler")]
+pub use breakpad_handler::{
+    AccessKind, BreakpadHandler, CrashEvent, CrashFilter, CrashInfo, CrashReporter, DumpError,
+    FaultMapping, HandlerStats, HangMonitor, MachExceptionType, MemoryAccess, MinidumpInfo,
+    RateLimit, SignalMask, TaskNames,
+};
+#[cfg(feature = "async")]
+pub use breakpad_handler::{AsyncCrashEvent, CrashReceiver, CrashSender};
+#[cfg(feature = "handler")]
+pub use breakpad_integration::{BreakpadIntegration, InstallOptions, MinidumpSettings};
+#[cfg(feature = "handler")]
+pub use config::{BreakpadConfig, ConfigSource, DumpType};
+pub use consent::{ConsentPolicy, PendingCrash};
+#[cfg(feature = "dump-watcher")]
+pub use daemon::{CrashUploaderDaemon, RunningDaemon};
 pub use error::Error;
+pub use index::IndexEntry;
+#[cfg(feature = "handler")]
+pub use policy::CrashDirPolicy;
+pub use recurrence::RecurrencePolicy;
+pub use shared::{dump_metadata, CrashMetadata};
+#[cfg(feature = "handler")]
+pub use stats::CrashStats;
+pub use store::{CrashClaim, CrashStore, StoreStats, StoredCrash};
+#[cfg(feature = "handler")]
+pub use telemetry::TelemetryRing;
+#[cfg(feature = "test-support")]
+pub use test_support::{CapturingTransport, EnvelopeExt};
 pub use transport::{BreakpadTransportFactory, CrashSendStyle};
+pub use upload::UploadProgress;
+#[cfg(feature = "dump-watcher")]
+pub use watcher::DumpWatcher;
+
+/// The types needed by most applications, so that only this crate needs to be
+/// depended on, rather than also depending on `breakpad-handler` and risking
+/// a mismatch between the versions of the two
+pub mod prelude {
+    #[cfg(feature = "async")]
+    pub use crate::AsyncCrashEvent;
+    #[cfg(feature = "dump-watcher")]
+    pub use crate::DumpWatcher;
+    #[cfg(feature = "handler")]
+    pub use crate::{
+        BreakpadConfig, BreakpadHandler, BreakpadIntegration, ConfigSource, CrashEvent,
+        InstallOptions, MinidumpSettings,
+    };
+    pub use crate::{BreakpadTransportFactory, CrashSendStyle, CrashStore};
+}
@@ -0,0 +1,116 @@
+//! Translates the mach exceptions that breakpad handles on macOS to the BSD
+//! signals the kernel would have raised for them, the same as `ux_exception`
+
+const EXC_BAD_ACCESS: u32 = 1;
+const EXC_BAD_INSTRUCTION: u32 = 2;
+const EXC_ARITHMETIC: u32 = 3;
+const EXC_EMULATION: u32 = 4;
+const EXC_SOFTWARE: u32 = 5;
+const EXC_BREAKPOINT: u32 = 6;
+const EXC_CRASH: u32 = 10;
+const EXC_RESOURCE: u32 = 11;
+const EXC_GUARD: u32 = 12;
+
+const KERN_INVALID_ADDRESS: u32 = 1;
+const KERN_PROTECTION_FAILURE: u32 = 2;
+
+const EXC_UNIX_BAD_SYSCALL: u32 = 0x1_0000;
+const EXC_UNIX_BAD_PIPE: u32 = 0x1_0001;
+const EXC_UNIX_ABORT: u32 = 0x1_0002;
+const EXC_SOFT_SIGNAL: u32 = 0x1_0003;
+
+/// A mach exception as recorded by breakpad in the exception stream
+pub(crate) struct MachException {
+    /// The exception type, eg. `EXC_BAD_ACCESS`
+    pub(crate) kind: u32,
+    /// The exception code, eg. `KERN_INVALID_ADDRESS`
+    pub(crate) code: u32,
+    /// The exception subcode, only recorded for `EXC_BAD_ACCESS`, where it is
+    /// the faulting address
+    pub(crate) subcode: Option<u64>,
+}
+
+impl MachException {
+    /// Reads the exception from the minidump, breakpad records the exception
+    /// type as the exception code, the exception code as the flags, and the
+    /// subcode as the exception address for `EXC_BAD_ACCESS`
+    pub(crate) fn from_minidump(bytes: &[u8]) -> Option<Self> {
+        let record = crate::minidump::exception_record(bytes)?;
+
+        Some(Self {
+            kind: record.code,
+            code: record.flags,
+            subcode: (record.code == EXC_BAD_ACCESS).then_some(record.address),
+        })
+    }
+
+    /// Gets the name of the exception type
+    pub(crate) fn name(&self) -> &'static str {
+        match self.kind {
+            EXC_BAD_ACCESS => "EXC_BAD_ACCESS",
+            EXC_BAD_INSTRUCTION => "EXC_BAD_INSTRUCTION",
+            EXC_ARITHMETIC => "EXC_ARITHMETIC",
+            EXC_EMULATION => "EXC_EMULATION",
+            EXC_SOFTWARE => "EXC_SOFTWARE",
+            EXC_BREAKPOINT => "EXC_BREAKPOINT",
+            EXC_CRASH => "EXC_CRASH",
+            EXC_RESOURCE => "EXC_RESOURCE",
+            EXC_GUARD => "EXC_GUARD",
+            _ => "EXC_UNKNOWN",
+        }
+    }
+
+    /// Gets the name of the exception code, for the codes that have one
+    pub(crate) fn code_name(&self) -> Option<&'static str> {
+        Some(match (self.kind, self.code) {
+            (EXC_BAD_ACCESS, KERN_INVALID_ADDRESS) => "KERN_INVALID_ADDRESS",
+            (EXC_BAD_ACCESS, KERN_PROTECTION_FAILURE) => "KERN_PROTECTION_FAILURE",
+            (EXC_SOFTWARE, EXC_UNIX_BAD_SYSCALL) => "EXC_UNIX_BAD_SYSCALL",
+            (EXC_SOFTWARE, EXC_UNIX_BAD_PIPE) => "EXC_UNIX_BAD_PIPE",
+            (EXC_SOFTWARE, EXC_UNIX_ABORT) => "EXC_UNIX_ABORT",
+            (EXC_SOFTWARE, EXC_SOFT_SIGNAL) => "EXC_SOFT_SIGNAL",
+            _ => return None,
+        })
+    }
+
+    /// Gets the number and name of the signal the exception is equivalent to
+    pub(crate) fn signal(&self) -> Option<(i32, &'static str)> {
+        Some(match self.kind {
+            EXC_BAD_ACCESS if self.code == KERN_INVALID_ADDRESS => (11, "SIGSEGV"),
+            EXC_BAD_ACCESS => (10, "SIGBUS"),
+            EXC_BAD_INSTRUCTION => (4, "SIGILL"),
+            EXC_ARITHMETIC => (8, "SIGFPE"),
+            EXC_EMULATION => (7, "SIGEMT"),
+            EXC_SOFTWARE => match self.code {
+                EXC_UNIX_BAD_SYSCALL => (12, "SIGSYS"),
+                EXC_UNIX_BAD_PIPE => (13, "SIGPIPE"),
+                EXC_UNIX_ABORT => (6, "SIGABRT"),
+                EXC_SOFT_SIGNAL => (9, "SIGKILL"),
+                _ => return None,
+            },
+            EXC_BREAKPOINT => (5, "SIGTRAP"),
+            // The signal that terminated the process is encoded in the code
+            EXC_CRASH => {
+                let signal = ((self.code >> 24) & 0xff) as i32;
+                (signal, signal_name(signal)?)
+            }
+            _ => return None,
+        })
+    }
+}
+
+fn signal_name(signal: i32) -> Option<&'static str> {
+    Some(match signal {
+        4 => "SIGILL",
+        5 => "SIGTRAP",
+        6 => "SIGABRT",
+        7 => "SIGEMT",
+        8 => "SIGFPE",
+        9 => "SIGKILL",
+        10 => "SIGBUS",
+        11 => "SIGSEGV",
+        12 => "SIGSYS",
+        13 => "SIGPIPE",
+        _ => return None,
+    })
+}
@@ -0,0 +1,661 @@
+//! Sanity checks for minidumps, so that they can be verified before they are
+//! uploaded
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// `MDMP` in little endian
+const SIGNATURE: u32 = 0x504d_444d;
+/// The low 16 bits of the version, the high 16 bits are implementation specific
+const VERSION: u32 = 0xa793;
+
+const HEADER_SIZE: usize = 32;
+const DIRECTORY_ENTRY_SIZE: usize = 12;
+
+/// The streams that every minidump written by breakpad contains, and without
+/// which Sentry is unable to process the minidump
+const REQUIRED_STREAMS: [StreamType; 3] = [
+    StreamType::ThreadList,
+    StreamType::ModuleList,
+    StreamType::SystemInfo,
+];
+
+macro_rules! stream_types {
+    ($($(#[$doc:meta])* $name:ident = $value:literal,)+) => {
+        /// The type of a stream in the minidump directory, covering the
+        /// standard Windows streams as well as the Breakpad and Crashpad
+        /// extensions
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        pub enum StreamType {
+            $($(#[$doc])* $name,)+
+            /// A stream type that isn't known
+            Unknown(u32),
+        }
+
+        impl From<u32> for StreamType {
+            fn from(value: u32) -> Self {
+                match value {
+                    $($value => Self::$name,)+
+                    other => Self::Unknown(other),
+                }
+            }
+        }
+
+        impl From<StreamType> for u32 {
+            fn from(st: StreamType) -> Self {
+                match st {
+                    $(StreamType::$name => $value,)+
+                    StreamType::Unknown(other) => other,
+                }
+            }
+        }
+    };
+}
+
+stream_types! {
+    Unused = 0,
+    Reserved0 = 1,
+    Reserved1 = 2,
+    ThreadList = 3,
+    ModuleList = 4,
+    MemoryList = 5,
+    Exception = 6,
+    SystemInfo = 7,
+    ThreadExList = 8,
+    Memory64List = 9,
+    CommentA = 10,
+    CommentW = 11,
+    HandleData = 12,
+    FunctionTable = 13,
+    UnloadedModuleList = 14,
+    MiscInfo = 15,
+    MemoryInfoList = 16,
+    ThreadInfoList = 17,
+    HandleOperationList = 18,
+    Token = 19,
+    JavaScriptData = 20,
+    SystemMemoryInfo = 21,
+    ProcessVmCounters = 22,
+    IptTrace = 23,
+    ThreadNames = 24,
+    LastReserved = 0xffff,
+    /// Breakpad's `MDRawBreakpadInfo`
+    BreakpadInfo = 0x4767_0001,
+    /// Breakpad's `MDRawAssertionInfo`
+    AssertionInfo = 0x4767_0002,
+    /// The contents of `/proc/cpuinfo`
+    LinuxCpuInfo = 0x4767_0003,
+    /// The contents of `/proc/self/status`
+    LinuxProcStatus = 0x4767_0004,
+    /// The contents of `/etc/lsb-release`
+    LinuxLsbRelease = 0x4767_0005,
+    /// The contents of `/proc/self/cmdline`
+    LinuxCmdLine = 0x4767_0006,
+    /// The contents of `/proc/self/environ`
+    LinuxEnviron = 0x4767_0007,
+    /// The contents of `/proc/self/auxv`
+    LinuxAuxv = 0x4767_0008,
+    /// The contents of `/proc/self/maps`
+    LinuxMaps = 0x4767_0009,
+    /// The state of the dynamic linker, ie. `r_debug`
+    LinuxDsoDebug = 0x4767_000a,
+    /// Crashpad's `MinidumpCrashpadInfo`
+    CrashpadInfo = 0x4350_0001,
+    /// A file embedded with
+    /// [`BreakpadIntegration::embed_file`](crate::BreakpadIntegration::embed_file),
+    /// the nul terminated path of the file followed by its contents
+    EmbeddedFile = 0x4245_0001,
+    /// The labels registered with
+    /// [`breakpad_handler::TaskNames`](crate::TaskNames) for the tasks that
+    /// threads were running, each the thread id as a little endian `u32`
+    /// followed by the nul terminated label
+    TaskNames = 0x4245_0002,
+    /// A snapshot of a ring buffer registered with
+    /// [`BreakpadIntegration::register_telemetry_ring`](crate::BreakpadIntegration::register_telemetry_ring),
+    /// the nul terminated name of the ring, the cursor before and after the
+    /// snapshot was taken as little endian `u64`s, and the contents of the
+    /// ring, oldest first, see [`telemetry_snapshots`]
+    TelemetryRing = 0x4245_0003,
+}
+
+impl StreamType {
+    /// Whether this is one of the stream types known to this crate
+    #[inline]
+    pub fn is_known(self) -> bool {
+        !matches!(self, Self::Unknown(_))
+    }
+}
+
+/// An entry in the stream directory of a minidump
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Directory {
+    pub stream_type: StreamType,
+    /// The size of the stream's data
+    pub data_size: u32,
+    /// The offset of the stream's data from the start of the minidump
+    pub rva: u32,
+}
+
+/// A problem found by [`validate`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// The minidump is smaller than the header
+    TooSmall { len: usize },
+    /// The header doesn't start with `MDMP`
+    BadSignature(u32),
+    /// The header has an unknown version
+    BadVersion(u32),
+    /// The stream directory doesn't fit in the minidump
+    DirectoryOutOfBounds { rva: u32, count: u32 },
+    /// The data of a stream doesn't fit in the minidump
+    StreamOutOfBounds { index: u32, directory: Directory },
+    /// A stream that is required to process the minidump is not present
+    MissingStream(StreamType),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooSmall { len } => write!(
+                f,
+                "minidump is {} bytes, which is smaller than the {} byte header",
+                len, HEADER_SIZE
+            ),
+            Self::BadSignature(sig) => write!(f, "invalid signature {:#010x}", sig),
+            Self::BadVersion(version) => write!(f, "unknown version {:#010x}", version),
+            Self::DirectoryOutOfBounds { rva, count } => write!(
+                f,
+                "stream directory of {} entries at {:#x} is out of bounds",
+                count, rva
+            ),
+            Self::StreamOutOfBounds { index, directory } => write!(
+                f,
+                "stream {} ({:?}) of {} bytes at {:#x} is out of bounds",
+                index, directory.stream_type, directory.data_size, directory.rva
+            ),
+            Self::MissingStream(stream_type) => {
+                write!(f, "required stream {:?} is missing", stream_type)
+            }
+        }
+    }
+}
+
+/// The result of [`validate`]
+#[derive(Clone, Debug, Default)]
+pub struct ValidationReport {
+    /// Every stream in the directory, in order
+    pub streams: Vec<Directory>,
+    /// Everything that was found to be wrong with the minidump
+    pub problems: Vec<Problem>,
+}
+
+impl ValidationReport {
+    /// Whether no problems were found
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+#[inline]
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let b = bytes.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+#[inline]
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    let lo = read_u32(bytes, offset)?;
+    let hi = read_u32(bytes, offset.checked_add(4)?)?;
+    Some(u64::from(lo) | (u64::from(hi) << 32))
+}
+
+/// The exception record from the exception stream of a minidump, the meaning
+/// of the fields depends on the platform the minidump was written on
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ExceptionRecord {
+    /// The id of the thread that crashed
+    pub thread_id: u32,
+    /// The exception code on Windows, the signal on Linux/Android, or the
+    /// mach exception type on macOS
+    pub code: u32,
+    /// The `si_code` on Linux/Android, or the mach exception code on macOS
+    pub flags: u32,
+    /// The faulting address on Linux/Android, and for `EXC_BAD_ACCESS` on
+    /// macOS, otherwise the address of the instruction that crashed
+    pub address: u64,
+}
+
+/// Reads the exception record from the exception stream of the minidump, if
+/// it has one
+pub fn exception_record(bytes: &[u8]) -> Option<ExceptionRecord> {
+    let report = validate(bytes);
+    let stream = report
+        .streams
+        .iter()
+        .find(|d| d.stream_type == StreamType::Exception)?;
+
+    // MINIDUMP_EXCEPTION_STREAM is the thread id followed by the exception
+    // record, which is 8 byte aligned
+    let rva = stream.rva as usize;
+    if stream.data_size < 32 {
+        return None;
+    }
+
+    Some(ExceptionRecord {
+        thread_id: read_u32(bytes, rva)?,
+        code: read_u32(bytes, rva + 8)?,
+        flags: read_u32(bytes, rva + 12)?,
+        address: read_u64(bytes, rva + 24)?,
+    })
+}
+
+/// The size of a `MINIDUMP_THREAD`
+const THREAD_SIZE: usize = 48;
+
+/// The stack memory of a thread from the thread list stream of a minidump
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ThreadStack<'b> {
+    /// The address the stack memory starts at, ie. the stack pointer when the
+    /// minidump was written
+    pub start: u64,
+    /// The contents of the stack
+    pub memory: &'b [u8],
+}
+
+/// Reads the stack of the thread from the thread list stream of the minidump,
+/// if the thread and its stack memory are in it
+pub fn thread_stack(bytes: &[u8], thread_id: u32) -> Option<ThreadStack<'_>> {
+    let report = validate(bytes);
+    let stream = report
+        .streams
+        .iter()
+        .find(|d| d.stream_type == StreamType::ThreadList)?;
+
+    let rva = stream.rva as usize;
+    let count = read_u32(bytes, rva)? as usize;
+    let count = count.min((stream.data_size as usize).saturating_sub(4) / THREAD_SIZE);
+
+    // The stack is a MINIDUMP_MEMORY_DESCRIPTOR at offset 24
+    let thread = (0..count)
+        .map(|index| rva + 4 + index * THREAD_SIZE)
+        .find(|thread| read_u32(bytes, *thread) == Some(thread_id))?;
+    let start = read_u64(bytes, thread + 24)?;
+    let size = read_u32(bytes, thread + 32)? as usize;
+    let memory_rva = read_u32(bytes, thread + 36)? as usize;
+
+    Some(ThreadStack {
+        start,
+        memory: bytes.get(memory_rva..memory_rva.checked_add(size)?)?,
+    })
+}
+
+/// A snapshot of a telemetry ring from a [`StreamType::TelemetryRing`]
+/// stream of a minidump
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TelemetrySnapshot<'b> {
+    /// The name the ring was registered with
+    pub name: &'b str,
+    /// The total number of bytes that had been written to the ring when the
+    /// snapshot was taken
+    pub cursor: u64,
+    /// The number of bytes that were written to the ring while the snapshot
+    /// was taken, which may have replaced as many of the oldest bytes
+    pub overwritten: u64,
+    /// The contents of the ring, oldest first
+    pub data: &'b [u8],
+}
+
+impl<'b> TelemetrySnapshot<'b> {
+    /// The contents of the ring without the bytes that may have been
+    /// overwritten while the snapshot was taken
+    pub fn consistent_data(&self) -> &'b [u8] {
+        let overwritten = self.overwritten.min(self.data.len() as u64) as usize;
+        &self.data[overwritten..]
+    }
+}
+
+/// Reads the snapshots of the telemetry rings from the minidump, skipping any
+/// that are malformed
+pub fn telemetry_snapshots(bytes: &[u8]) -> Vec<TelemetrySnapshot<'_>> {
+    validate(bytes)
+        .streams
+        .iter()
+        .filter(|d| d.stream_type == StreamType::TelemetryRing)
+        .filter_map(|d| {
+            let rva = d.rva as usize;
+            let stream = bytes.get(rva..rva.checked_add(d.data_size as usize)?)?;
+
+            let name_len = stream.iter().position(|b| *b == 0)?;
+            let name = std::str::from_utf8(&stream[..name_len]).ok()?;
+            let cursor = read_u64(stream, name_len + 1)?;
+            let end_cursor = read_u64(stream, name_len + 9)?;
+
+            Some(TelemetrySnapshot {
+                name,
+                cursor,
+                overwritten: end_cursor.saturating_sub(cursor),
+                data: stream.get(name_len + 17..)?,
+            })
+        })
+        .collect()
+}
+
+/// The size of a `MINIDUMP_MODULE`
+const MODULE_SIZE: usize = 108;
+/// The signature of a valid `VS_FIXEDFILEINFO`
+const FIXED_FILE_INFO_SIGNATURE: u32 = 0xfeef_04bd;
+
+/// A module from the module list stream of a minidump
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Module {
+    /// The address the module was loaded at
+    pub base_of_image: u64,
+    /// The size of the module in memory
+    pub size_of_image: u32,
+    /// The `TimeDateStamp` from the PE header on Windows, 0 elsewhere
+    pub time_date_stamp: u32,
+    /// The full path of the module
+    pub name: String,
+    /// The file version, eg. `10.0.19041.1`, if the module has one
+    pub version: Option<String>,
+    /// The `CodeView` record that identifies the debug file of the module, eg.
+    /// a `RSDS` record with the GUID and age of the PDB, or a `BpEL` record
+    /// with the ELF build id
+    pub cv_record: Vec<u8>,
+}
+
+/// Reads a `MINIDUMP_STRING`, ie. a length prefixed UTF-16 string
+fn read_string(bytes: &[u8], rva: usize) -> Option<String> {
+    let len = read_u32(bytes, rva)? as usize;
+    let data = bytes.get(rva.checked_add(4)?..rva.checked_add(4 + len)?)?;
+    let utf16: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    Some(String::from_utf16_lossy(&utf16))
+}
+
+/// Reads the modules from the module list stream of the minidump, skipping
+/// any that are out of bounds
+pub fn module_list(bytes: &[u8]) -> Vec<Module> {
+    let report = validate(bytes);
+    let stream = match report
+        .streams
+        .iter()
+        .find(|d| d.stream_type == StreamType::ModuleList)
+    {
+        Some(stream) => stream,
+        None => return Vec::new(),
+    };
+
+    let rva = stream.rva as usize;
+    let count = read_u32(bytes, rva).unwrap_or_default() as usize;
+    let count = count.min((stream.data_size as usize).saturating_sub(4) / MODULE_SIZE);
+
+    (0..count)
+        .filter_map(|index| {
+            let module = rva + 4 + index * MODULE_SIZE;
+
+            // VS_FIXEDFILEINFO starts at offset 24, and is only filled in by
+            // breakpad on Windows and macOS
+            let version = if read_u32(bytes, module + 24)? == FIXED_FILE_INFO_SIGNATURE {
+                let ms = read_u32(bytes, module + 32)?;
+                let ls = read_u32(bytes, module + 36)?;
+                Some(format!(
+                    "{}.{}.{}.{}",
+                    ms >> 16,
+                    ms & 0xffff,
+                    ls >> 16,
+                    ls & 0xffff
+                ))
+            } else {
+                None
+            };
+
+            let cv_size = read_u32(bytes, module + 76)? as usize;
+            let cv_rva = read_u32(bytes, module + 80)? as usize;
+
+            Some(Module {
+                base_of_image: read_u64(bytes, module)?,
+                size_of_image: read_u32(bytes, module + 8)?,
+                time_date_stamp: read_u32(bytes, module + 16)?,
+                name: read_string(bytes, read_u32(bytes, module + 20)? as usize)?,
+                version,
+                cv_record: bytes
+                    .get(cv_rva..cv_rva.checked_add(cv_size)?)
+                    .map(<[u8]>::to_vec)
+                    .unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// Checks that the minidump has a valid header, that the stream directory and
+/// the data of every stream are within the bounds of the minidump, and that the
+/// streams required to process it are present.
+///
+/// This only checks the structure of the minidump, not the contents of the
+/// individual streams.
+pub fn validate(bytes: &[u8]) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    if bytes.len() < HEADER_SIZE {
+        report.problems.push(Problem::TooSmall { len: bytes.len() });
+        return report;
+    }
+
+    // The header is 32 bytes, so these reads can't fail
+    let signature = read_u32(bytes, 0).unwrap_or_default();
+    let version = read_u32(bytes, 4).unwrap_or_default();
+    let stream_count = read_u32(bytes, 8).unwrap_or_default();
+    let directory_rva = read_u32(bytes, 12).unwrap_or_default();
+
+    if signature != SIGNATURE {
+        // If the signature is wrong, it's probably not a minidump at all, so
+        // there's no point in checking anything else
+        report.problems.push(Problem::BadSignature(signature));
+        return report;
+    }
+
+    if version & 0xffff != VERSION {
+        report.problems.push(Problem::BadVersion(version));
+    }
+
+    let directory_end = (stream_count as usize)
+        .checked_mul(DIRECTORY_ENTRY_SIZE)
+        .and_then(|size| size.checked_add(directory_rva as usize));
+
+    if directory_end.is_none_or(|end| end > bytes.len()) {
+        report.problems.push(Problem::DirectoryOutOfBounds {
+            rva: directory_rva,
+            count: stream_count,
+        });
+        return report;
+    }
+
+    for index in 0..stream_count {
+        let entry = directory_rva as usize + index as usize * DIRECTORY_ENTRY_SIZE;
+
+        let directory = Directory {
+            stream_type: read_u32(bytes, entry).unwrap_or_default().into(),
+            data_size: read_u32(bytes, entry + 4).unwrap_or_default(),
+            rva: read_u32(bytes, entry + 8).unwrap_or_default(),
+        };
+
+        report.streams.push(directory);
+
+        if (directory.rva as usize).saturating_add(directory.data_size as usize) > bytes.len() {
+            report
+                .problems
+                .push(Problem::StreamOutOfBounds { index, directory });
+        }
+    }
+
+    for required in REQUIRED_STREAMS {
+        if !report.streams.iter().any(|d| d.stream_type == required) {
+            report.problems.push(Problem::MissingStream(required));
+        }
+    }
+
+    report
+}
+
+/// A stream to append to a minidump with [`append_streams`]
+#[cfg(feature = "handler")]
+pub(crate) struct NewStream {
+    pub(crate) stream_type: StreamType,
+    pub(crate) data: Vec<u8>,
+    /// The offsets in `data` of 64-bit RVAs that point into `data` itself, and
+    /// so are relative to the start of the stream until it is appended
+    pub(crate) relative_rvas: Vec<usize>,
+}
+
+/// Appends streams to a minidump on disk. The streams and a new directory that
+/// includes them are written to the end of the file, the old directory is left
+/// in place, unused, rather than moving the streams that follow it.
+#[cfg(feature = "handler")]
+pub(crate) fn append_streams(
+    minidump_path: &std::path::Path,
+    streams: &[NewStream],
+) -> std::io::Result<()> {
+    use std::convert::TryFrom;
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    if streams.is_empty() {
+        return Ok(());
+    }
+
+    let invalid = |msg: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_owned());
+
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(minidump_path)?;
+
+    let mut header = [0u8; HEADER_SIZE];
+    file.read_exact(&mut header)?;
+
+    if read_u32(&header, 0) != Some(SIGNATURE) {
+        return Err(invalid("not a minidump"));
+    }
+
+    // The header is 32 bytes, so these reads can't fail
+    let stream_count = read_u32(&header, 8).unwrap_or_default();
+    let directory_rva = read_u32(&header, 12).unwrap_or_default();
+
+    let mut directory = vec![0u8; stream_count as usize * DIRECTORY_ENTRY_SIZE];
+    file.seek(SeekFrom::Start(directory_rva.into()))?;
+    file.read_exact(&mut directory)?;
+
+    let mut offset = file.seek(SeekFrom::End(0))?;
+    let mut appended = Vec::new();
+
+    // Every stream, and the directory, is 4 byte aligned
+    let align = |appended: &mut Vec<u8>, offset: &mut u64| {
+        let padding = (4 - *offset % 4) % 4;
+        appended.resize(appended.len() + padding as usize, 0);
+        *offset += padding;
+    };
+
+    for stream in streams {
+        align(&mut appended, &mut offset);
+
+        let rva = u32::try_from(offset).map_err(|_e| invalid("minidump is too large"))?;
+        let data_size =
+            u32::try_from(stream.data.len()).map_err(|_e| invalid("stream is too large"))?;
+
+        directory.extend_from_slice(&u32::from(stream.stream_type).to_le_bytes());
+        directory.extend_from_slice(&data_size.to_le_bytes());
+        directory.extend_from_slice(&rva.to_le_bytes());
+
+        let start = appended.len();
+        appended.extend_from_slice(&stream.data);
+
+        for &pos in &stream.relative_rvas {
+            let field = appended
+                .get_mut(start + pos..start + pos + 8)
+                .ok_or_else(|| invalid("relative rva is out of bounds"))?;
+            let mut relative = [0u8; 8];
+            relative.copy_from_slice(field);
+            field.copy_from_slice(&(u64::from_le_bytes(relative) + offset).to_le_bytes());
+        }
+
+        offset += stream.data.len() as u64;
+    }
+
+    align(&mut appended, &mut offset);
+    let directory_rva = u32::try_from(offset).map_err(|_e| invalid("minidump is too large"))?;
+    appended.extend_from_slice(&directory);
+
+    file.write_all(&appended)?;
+
+    // Only point the header at the new directory once it has been written, so
+    // that the minidump stays valid if we fail midway
+    let mut counts = Vec::with_capacity(8);
+    counts.extend_from_slice(&(stream_count + streams.len() as u32).to_le_bytes());
+    counts.extend_from_slice(&directory_rva.to_le_bytes());
+    file.seek(SeekFrom::Start(8))?;
+    file.write_all(&counts)
+}
+
+/// Converts a core dump of a crashed process into a minidump written to
+/// `minidump_path`, so that crashes collected via the kernel's `core_pattern`
+/// can be sent to Sentry the same way as the ones captured by the integration,
+/// eg. by writing the minidump to the crash directory and calling
+/// [`BreakpadIntegration::upload_pending`](crate::BreakpadIntegration::upload_pending).
+///
+/// `proc_snapshot` is a directory with copies of the `auxv`, `cmdline`,
+/// `environ`, `maps`, and `status` files from `/proc/<pid>` of the crashed
+/// process, which the kernel doesn't include in the core dump, taken before
+/// the process exited, eg. by the `core_pattern` handler.
+#[cfg(all(feature = "handler", any(target_os = "linux", target_os = "android")))]
+pub fn from_core(
+    core_path: impl AsRef<std::path::Path>,
+    proc_snapshot: impl AsRef<std::path::Path>,
+    minidump_path: impl AsRef<std::path::Path>,
+) -> Result<(), crate::Error> {
+    breakpad_handler::write_minidump_from_core(
+        core_path.as_ref(),
+        proc_snapshot.as_ref(),
+        minidump_path.as_ref(),
+    )?;
+    Ok(())
+}
+
+/// Validates the minidump on disk, returning the problems that were found, if
+/// any, so that they can be attached to the crash event
+#[cfg(all(feature = "handler", debug_assertions))]
+pub(crate) fn validate_file(
+    minidump_path: &std::path::Path,
+) -> Option<sentry_core::protocol::Value> {
+    let bytes = match std::fs::read(minidump_path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            debug_print!(
+                "unable to read minidump '{}' for validation: {}",
+                minidump_path.display(),
+                e
+            );
+            return None;
+        }
+    };
+
+    let report = validate(&bytes);
+    if report.is_valid() {
+        return None;
+    }
+
+    for problem in &report.problems {
+        debug_print!("minidump '{}': {}", minidump_path.display(), problem);
+    }
+
+    Some(
+        report
+            .problems
+            .iter()
+            .map(|problem| problem.to_string().into())
+            .collect::<Vec<sentry_core::protocol::Value>>()
+            .into(),
+    )
+}
@@ -0,0 +1,65 @@
+//! Minidumps for Rust panics, so that they are reported the same as crashes,
+//! see [`BreakpadIntegration::hook_panics`](crate::BreakpadIntegration::hook_panics)
+
+use std::sync::{Mutex, PoisonError};
+
+/// A panic that a minidump is being written for
+pub(crate) struct Panic {
+    /// The message the panic was raised with
+    pub(crate) message: String,
+    /// Where the panic was raised, eg. `src/main.rs:10:5`
+    pub(crate) location: Option<String>,
+    /// The name of the thread that panicked
+    pub(crate) thread: Option<String>,
+    /// Whether the minidump was written by the hook, in which case the
+    /// process keeps running, rather than for the abort that follows the panic
+    pub(crate) on_demand: bool,
+}
+
+static PENDING: Mutex<Option<Panic>> = Mutex::new(None);
+
+/// Installs the panic hook, which runs the one that was installed before it
+/// first, eg. the default one that prints the panic
+pub(crate) fn hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+
+        let payload = info.payload();
+        let message = match payload.downcast_ref::<&str>() {
+            Some(s) => (*s).to_owned(),
+            None => match payload.downcast_ref::<String>() {
+                Some(s) => s.clone(),
+                None => "Box<dyn Any>".to_owned(),
+            },
+        };
+
+        // The process aborts with SIGABRT right after the hook, which the
+        // handler catches on Linux/Android, so the panic is only recorded for
+        // that crash rather than writing two minidumps
+        let on_demand = !cfg!(all(
+            panic = "abort",
+            any(target_os = "linux", target_os = "android")
+        ));
+
+        *PENDING.lock().unwrap_or_else(PoisonError::into_inner) = Some(Panic {
+            message,
+            location: info.location().map(|location| location.to_string()),
+            thread: std::thread::current().name().map(str::to_owned),
+            on_demand,
+        });
+
+        if on_demand {
+            if let Err(e) = breakpad_handler::generate_current_minidump() {
+                debug_print!("unable to write a minidump for the panic: {}", e);
+                let _ = take();
+            }
+        }
+    }));
+}
+
+/// Takes the panic that the minidump that was just written is for, if any
+pub(crate) fn take() -> Option<Panic> {
+    // Another thread could have crashed while a panic was being recorded
+    PENDING.try_lock().ok()?.take()
+}
@@ -0,0 +1,262 @@
+use std::path::Path;
+#[cfg(feature = "handler")]
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+/// The window of [`CrashDirPolicy::max_dumps_per_hour`]
+#[cfg(feature = "handler")]
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60 * 60);
+/// The file in the crash directory with the times the minidumps in the rate
+/// limit window were written, in seconds since the Unix epoch, one per line
+#[cfg(feature = "handler")]
+const RATE_LIMIT_FILE: &str = "rate_limit";
+
+/// Limits on the minidumps that are kept in the crash directory until they
+/// are uploaded, so that an application that gets into a crash loop doesn't
+/// fill up the disk before the next successful startup. The limits are
+/// enforced when a crash occurs, after the minidump has been written.
+#[cfg(feature = "handler")]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CrashDirPolicy {
+    /// The maximum number of minidumps kept, the oldest ones are deleted first
+    pub max_dumps: Option<usize>,
+    /// The maximum total size of the minidumps kept, in bytes, the oldest ones
+    /// are deleted first. A single minidump that is larger than this is kept.
+    pub max_total_size: Option<u64>,
+    /// The maximum number of minidumps written in the last hour. The times they
+    /// were written are recorded in the crash directory, so minidumps that have
+    /// since been uploaded or removed still count. New minidumps over the limit
+    /// are deleted, and the crash event is sent without one.
+    pub max_dumps_per_hour: Option<u32>,
+}
+
+#[cfg(feature = "handler")]
+impl CrashDirPolicy {
+    /// Keeps at most `max_dumps` minidumps
+    pub fn with_max_dumps(mut self, max_dumps: usize) -> Self {
+        self.max_dumps = Some(max_dumps);
+        self
+    }
+
+    /// Keeps at most `max_total_size` bytes of minidumps
+    pub fn with_max_total_size(mut self, max_total_size: u64) -> Self {
+        self.max_total_size = Some(max_total_size);
+        self
+    }
+
+    /// Keeps at most `max_dumps_per_hour` minidumps written in the last hour
+    pub fn with_max_dumps_per_hour(mut self, max_dumps_per_hour: u32) -> Self {
+        self.max_dumps_per_hour = Some(max_dumps_per_hour);
+        self
+    }
+
+    /// Enforces the policy after `minidump_path` was written to the crash
+    /// directory, returning `false` if it was over the rate limit and has been
+    /// deleted
+    pub(crate) fn apply(&self, crash_dir: &Path, minidump_path: &Path) -> bool {
+        if *self == Self::default() {
+            return true;
+        }
+
+        let new_len = match std::fs::metadata(minidump_path) {
+            Ok(md) => md.len(),
+            Err(_) => return true,
+        };
+
+        let mut dumps: Vec<_> = list_dumps(crash_dir)
+            .into_iter()
+            .filter(|(path, ..)| path != minidump_path)
+            .collect();
+
+        if let Some(max_dumps_per_hour) = self.max_dumps_per_hour {
+            if !record_written(crash_dir, max_dumps_per_hour, SystemTime::now()) {
+                remove(crash_dir, minidump_path);
+                return false;
+            }
+        }
+
+        dumps.sort_by_key(|(_, _, modified)| *modified);
+
+        let mut count = dumps.len() + 1;
+        let mut total_size = dumps.iter().map(|(_, len, _)| len).sum::<u64>() + new_len;
+
+        let mut removed = Vec::new();
+        for (path, len, _) in dumps {
+            if count <= self.max_dumps.unwrap_or(usize::MAX)
+                && total_size <= self.max_total_size.unwrap_or(u64::MAX)
+            {
+                break;
+            }
+
+            if let Some(id) = remove(crash_dir, &path) {
+                removed.push(id);
+            }
+
+            count -= 1;
+            total_size -= len;
+        }
+
+        // The minidumps are removed on purpose, so they aren't reported as
+        // missing from the index
+        if !removed.is_empty() {
+            crate::index::prune(crash_dir, &removed);
+        }
+
+        true
+    }
+
+    /// Explains why a minidump that was over the rate limit was discarded
+    pub(crate) fn rate_limit_reason(&self) -> String {
+        format!(
+            "minidump was discarded, as {} were already written in the last {}",
+            self.max_dumps_per_hour.unwrap_or_default(),
+            describe_window(RATE_LIMIT_WINDOW)
+        )
+    }
+}
+
+/// Records that a minidump was written at `now`, unless `max_dumps` were
+/// already written within the rate limit window, in which case it returns
+/// `false`. Only the times within the window are kept, so the file stays small.
+#[cfg(feature = "handler")]
+fn record_written(crash_dir: &Path, max_dumps: u32, now: SystemTime) -> bool {
+    let path = crash_dir.join(RATE_LIMIT_FILE);
+    let now_secs = now
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let mut written: Vec<u64> = std::fs::read_to_string(&path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .filter(|&secs: &u64| now_secs.saturating_sub(secs) < RATE_LIMIT_WINDOW.as_secs())
+        .collect();
+
+    if written.len() >= max_dumps as usize {
+        return false;
+    }
+
+    written.push(now_secs);
+    let contents: String = written.iter().map(|secs| format!("{}\n", secs)).collect();
+    if let Err(e) = std::fs::write(&path, contents) {
+        debug_print!("failed to record minidump in {}: {}", path.display(), e);
+    }
+
+    true
+}
+
+/// Formats the window in the largest unit it is a whole number of, eg.
+/// `hour` or `90 minutes`, to follow "in the last"
+#[cfg(feature = "handler")]
+fn describe_window(window: Duration) -> String {
+    let secs = window.as_secs();
+    let (count, unit) = if secs != 0 && secs.is_multiple_of(60 * 60) {
+        (secs / (60 * 60), "hour")
+    } else if secs != 0 && secs.is_multiple_of(60) {
+        (secs / 60, "minute")
+    } else {
+        (secs, "second")
+    };
+
+    if count == 1 {
+        unit.to_owned()
+    } else {
+        format!("{} {}s", count, unit)
+    }
+}
+
+/// Gets the path, size, and modification time of every minidump in the
+/// directory
+#[cfg(feature = "handler")]
+fn list_dumps(crash_dir: &Path) -> Vec<(PathBuf, u64, SystemTime)> {
+    crate::CrashStore::new(crash_dir)
+        .list()
+        .into_iter()
+        .map(|crash| (crash.minidump_path, crash.size, crash.modified))
+        .collect()
+}
+
+/// Removes a minidump that won't be uploaded, along with the metadata and
+/// attachments written for it, returning its id. The index is left for the
+/// caller to prune.
+pub(crate) fn remove(crash_dir: &Path, minidump_path: &Path) -> Option<String> {
+    if let Err(e) = std::fs::remove_file(minidump_path) {
+        debug_print!("failed to remove {}: {}", minidump_path.display(), e);
+        return None;
+    }
+
+    let _ = std::fs::remove_file(minidump_path.with_extension("metadata"));
+
+    let id = minidump_path.file_stem().and_then(|fs| fs.to_str())?;
+    crate::attachments::remove(crash_dir, id);
+    Some(id.to_owned())
+}
+
+#[cfg(all(test, feature = "handler"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_windows() {
+        assert_eq!(describe_window(Duration::from_secs(60 * 60)), "hour");
+        assert_eq!(describe_window(Duration::from_secs(3 * 60 * 60)), "3 hours");
+        assert_eq!(describe_window(Duration::from_secs(90 * 60)), "90 minutes");
+        assert_eq!(describe_window(Duration::from_secs(1)), "second");
+        assert_eq!(describe_window(Duration::ZERO), "0 seconds");
+    }
+
+    #[test]
+    fn rate_limit_outlives_removed_minidumps() {
+        let crash_dir = std::env::temp_dir().join(format!(
+            "sentry-contrib-breakpad-rate-limit-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&crash_dir);
+        std::fs::create_dir_all(&crash_dir).unwrap();
+
+        let policy = CrashDirPolicy::default().with_max_dumps_per_hour(2);
+        let write = |id: &str| {
+            let path = crash_dir.join(format!("{}.dmp", id));
+            std::fs::write(&path, b"MDMP").unwrap();
+            path
+        };
+
+        // Uploading the minidumps doesn't reset the limit
+        for id in ["a", "b"] {
+            let path = write(id);
+            assert!(policy.apply(&crash_dir, &path));
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        let path = write("c");
+        assert!(!policy.apply(&crash_dir, &path));
+        assert!(!path.exists());
+
+        // Times outside of the window are forgotten
+        let hour_ago = SystemTime::now() - RATE_LIMIT_WINDOW - Duration::from_secs(1);
+        let secs = hour_ago
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        std::fs::write(
+            crash_dir.join(RATE_LIMIT_FILE),
+            format!("{}\n{}\n", secs, secs),
+        )
+        .unwrap();
+        assert!(policy.apply(&crash_dir, &write("d")));
+
+        let _ = std::fs::remove_dir_all(&crash_dir);
+    }
+
+    #[test]
+    fn rate_limit_reason_names_the_limit() {
+        let policy = CrashDirPolicy::default().with_max_dumps_per_hour(3);
+        assert_eq!(
+            policy.rate_limit_reason(),
+            "minidump was discarded, as 3 were already written in the last hour"
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use sentry_core::{protocol as proto, Envelope, Transport};
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// How long the worker waits for the wrapped transport to make room for, and
+/// then deliver, a crash envelope
+const CRASH_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long dropping the queue waits for the worker to forward what is still
+/// queued, after which the worker is left to finish on its own
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// An envelope waiting to be forwarded to the wrapped transport
+pub(crate) struct Queued {
+    pub(crate) envelope: Envelope,
+    /// The minidump the envelope was assembled from. It is kept on disk along
+    /// with its metadata until the envelope has been delivered, so that it can
+    /// be sent in the next session if delivery fails
+    pub(crate) spooled: Option<PathBuf>,
+}
+
+impl Queued {
+    fn is_crash(&self) -> bool {
+        self.envelope.items().any(|ei| {
+            matches!(
+                ei,
+                proto::EnvelopeItem::Attachment(proto::Attachment {
+                    ty: Some(proto::AttachmentType::Minidump),
+                    ..
+                })
+            )
+        })
+    }
+}
+
+#[derive(Default)]
+struct Lanes {
+    crashes: VecDeque<Queued>,
+    telemetry: VecDeque<Queued>,
+    /// Set while the worker is forwarding an envelope it has already popped
+    busy: bool,
+    shutdown: bool,
+}
+
+impl Lanes {
+    fn is_idle(&self) -> bool {
+        self.crashes.is_empty() && self.telemetry.is_empty() && !self.busy
+    }
+}
+
+struct Shared {
+    lanes: Mutex<Lanes>,
+    cvar: Condvar,
+}
+
+/// Queue in front of the wrapped transport, where crash envelopes always take
+/// priority over other telemetry and are never dropped, while other telemetry
+/// is bounded, dropping the oldest envelopes first when the wrapped transport
+/// can't keep up
+pub(crate) struct EnvelopeQueue {
+    shared: Arc<Shared>,
+    capacity: usize,
+    /// Set if the worker couldn't be spawned, in which case envelopes are just
+    /// forwarded directly
+    fallback: Option<Arc<dyn Transport>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl EnvelopeQueue {
+    pub(crate) fn new(inner: Arc<dyn Transport>, capacity: usize) -> Self {
+        let shared = Arc::new(Shared {
+            lanes: Mutex::new(Lanes::default()),
+            cvar: Condvar::new(),
+        });
+
+        let worker = shared.clone();
+        let worker_inner = inner.clone();
+        let (worker, fallback) = match std::thread::Builder::new()
+            .name("breakpad-envelope-queue".to_owned())
+            .spawn(move || Self::forward(&worker, worker_inner.as_ref()))
+        {
+            Ok(worker) => (Some(worker), None),
+            Err(e) => {
+                debug_print!("failed to spawn envelope queue worker: {}", e);
+                (None, Some(inner))
+            }
+        };
+
+        Self {
+            shared,
+            capacity,
+            fallback,
+            worker,
+        }
+    }
+
+    pub(crate) fn push(&self, queued: Queued) {
+        if let Some(inner) = &self.fallback {
+            inner.send_envelope(queued.envelope);
+            return;
+        }
+
+        let mut lanes = self
+            .shared
+            .lanes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        if queued.is_crash() {
+            lanes.crashes.push_back(queued);
+        } else {
+            if lanes.telemetry.len() >= self.capacity.max(1) {
+                debug_print!("envelope queue is full, dropping oldest envelope");
+                lanes.telemetry.pop_front();
+            }
+
+            lanes.telemetry.push_back(queued);
+        }
+
+        self.shared.cvar.notify_all();
+    }
+
+    /// Waits until every queued envelope has been forwarded to the wrapped
+    /// transport, returning false if that doesn't happen before the timeout
+    pub(crate) fn drain(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut lanes = self
+            .shared
+            .lanes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        while !lanes.is_idle() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return false;
+            }
+
+            lanes = self
+                .shared
+                .cvar
+                .wait_timeout(lanes, remaining)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+
+        true
+    }
+
+    fn forward(shared: &Shared, inner: &dyn Transport) {
+        loop {
+            let (queued, is_crash) = {
+                let mut lanes = shared.lanes.lock().unwrap_or_else(PoisonError::into_inner);
+
+                loop {
+                    if let Some(queued) = lanes.crashes.pop_front() {
+                        lanes.busy = true;
+                        break (queued, true);
+                    }
+
+                    if let Some(queued) = lanes.telemetry.pop_front() {
+                        lanes.busy = true;
+                        break (queued, false);
+                    }
+
+                    if lanes.shutdown {
+                        return;
+                    }
+
+                    lanes = shared
+                        .cvar
+                        .wait(lanes)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            };
+
+            if is_crash {
+                // Give the wrapped transport a chance to get rid of whatever
+                // it already has queued, so that it doesn't drop the crash
+                // because it is saturated
+                inner.flush(CRASH_FLUSH_TIMEOUT);
+                inner.send_envelope(queued.envelope);
+
+                if let Some(minidump_path) = queued.spooled {
+                    if inner.flush(CRASH_FLUSH_TIMEOUT) {
+                        if let Some(crash_dir) = minidump_path.parent() {
+                            if let Some(id) = crate::policy::remove(crash_dir, &minidump_path) {
+                                crate::index::prune(crash_dir, &[id]);
+                            }
+                        }
+                    } else {
+                        debug_print!(
+                            "unable to confirm delivery of {}, it will be sent next session",
+                            minidump_path.display()
+                        );
+                    }
+                }
+            } else {
+                inner.send_envelope(queued.envelope);
+            }
+
+            let mut lanes = shared.lanes.lock().unwrap_or_else(PoisonError::into_inner);
+            lanes.busy = false;
+            shared.cvar.notify_all();
+        }
+    }
+}
+
+impl Drop for EnvelopeQueue {
+    fn drop(&mut self) {
+        // The worker forwards anything that is still queued before exiting
+        {
+            let mut lanes = self
+                .shared
+                .lanes
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            lanes.shutdown = true;
+            self.shared.cvar.notify_all();
+        }
+
+        // Joining can't time out, so the worker is only joined once it is
+        // idle, and would exit right away, rather than block on a wrapped
+        // transport that is stuck
+        if let Some(worker) = self.worker.take() {
+            if self.drain(SHUTDOWN_TIMEOUT) {
+                let _ = worker.join();
+            } else {
+                debug_print!("envelope queue worker didn't finish in time, detaching it");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sentry_core::types::Uuid;
+    use std::sync::mpsc;
+
+    /// Records the envelopes it is sent, holding up the first one until the
+    /// test releases it, so that the queue can be filled up in the meantime
+    struct Recording {
+        sent: Mutex<Vec<Uuid>>,
+        entered: Mutex<mpsc::Sender<()>>,
+        release: Mutex<Option<mpsc::Receiver<()>>>,
+    }
+
+    impl Transport for Recording {
+        fn send_envelope(&self, envelope: Envelope) {
+            let _ = self.entered.lock().unwrap().send(());
+            if let Some(release) = self.release.lock().unwrap().take() {
+                let _ = release.recv();
+            }
+
+            let id = envelope.event().map(|eve| eve.event_id).unwrap();
+            self.sent.lock().unwrap().push(id);
+        }
+    }
+
+    fn envelope(id: u128, crash: bool) -> Queued {
+        let mut envelope = Envelope::new();
+        envelope.add_item(proto::Event {
+            event_id: Uuid::from_u128(id),
+            ..Default::default()
+        });
+        if crash {
+            envelope.add_item(proto::Attachment {
+                buffer: vec![0; 4],
+                filename: "minidump.dmp".to_owned(),
+                ty: Some(proto::AttachmentType::Minidump),
+                ..Default::default()
+            });
+        }
+
+        Queued {
+            envelope,
+            spooled: None,
+        }
+    }
+
+    #[test]
+    fn prioritizes_crashes_and_evicts_oldest_telemetry() {
+        let (entered_tx, entered) = mpsc::channel();
+        let (release, release_rx) = mpsc::channel();
+        let transport = Arc::new(Recording {
+            sent: Mutex::new(Vec::new()),
+            entered: Mutex::new(entered_tx),
+            release: Mutex::new(Some(release_rx)),
+        });
+
+        let queue = EnvelopeQueue::new(transport.clone(), 2);
+
+        // Holds up the worker, so the rest pile up in the queue
+        queue.push(envelope(1, false));
+        entered.recv().unwrap();
+
+        queue.push(envelope(2, false));
+        queue.push(envelope(3, false));
+        queue.push(envelope(4, true));
+        // The queue is full, so the oldest telemetry is dropped, but crashes
+        // don't count against the capacity
+        queue.push(envelope(5, false));
+        queue.push(envelope(6, true));
+
+        release.send(()).unwrap();
+        assert!(queue.drain(Duration::from_secs(10)));
+
+        let sent: Vec<_> = transport.sent.lock().unwrap().clone();
+        assert_eq!(
+            sent,
+            [1, 4, 6, 3, 5].map(Uuid::from_u128).to_vec(),
+            "crashes first, then the telemetry that wasn't evicted"
+        );
+    }
+
+    #[test]
+    fn forwards_queued_envelopes_on_drop() {
+        let (entered_tx, _entered) = mpsc::channel();
+        let transport = Arc::new(Recording {
+            sent: Mutex::new(Vec::new()),
+            entered: Mutex::new(entered_tx),
+            release: Mutex::new(None),
+        });
+
+        let queue = EnvelopeQueue::new(transport.clone(), 10);
+        for id in 0..5 {
+            queue.push(envelope(id, id == 3));
+        }
+        drop(queue);
+
+        assert_eq!(transport.sent.lock().unwrap().len(), 5);
+    }
+}
@@ -0,0 +1,167 @@
+//! Detection of crashes that are the same as the previously uploaded one, see
+//! [`BreakpadIntegration::set_recurrence_policy`](crate::BreakpadIntegration::set_recurrence_policy)
+
+use serde::{Deserialize, Serialize};
+use std::{
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+/// The name of the file the last uploaded crash is persisted to in the crash
+/// directory
+const LAST_UPLOAD_FILE: &str = "last_upload.json";
+
+/// The maximum number of return addresses from the stack of the crashing
+/// thread that are included in the fingerprint
+const MAX_FRAMES: usize = 16;
+
+/// How crashes that are the same as the previously uploaded one are handled,
+/// eg. when an application crashes on every startup
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RecurrencePolicy {
+    /// How long after the previous crash the same crash is considered a
+    /// recurrence of it
+    pub window: Duration,
+    /// Whether the minidump of a recurrence is left out of the upload, as it
+    /// is usually identical to the one that was already uploaded
+    pub skip_minidump: bool,
+}
+
+impl RecurrencePolicy {
+    /// Considers the same crash within `window` of the previous one a
+    /// recurrence of it
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            skip_minidump: false,
+        }
+    }
+
+    /// Leaves the minidump of a recurrence out of the upload
+    pub fn with_skip_minidump(mut self, skip_minidump: bool) -> Self {
+        self.skip_minidump = skip_minidump;
+        self
+    }
+}
+
+static POLICY: std::sync::Mutex<Option<RecurrencePolicy>> = std::sync::Mutex::new(None);
+
+#[cfg(feature = "handler")]
+pub(crate) fn set(policy: Option<RecurrencePolicy>) {
+    *POLICY
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = policy;
+}
+
+pub(crate) fn current() -> Option<RecurrencePolicy> {
+    *POLICY
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// The on-disk format of the last uploaded crash
+#[derive(Serialize, Deserialize)]
+struct LastUpload {
+    fingerprint: String,
+    /// Seconds since the Unix epoch
+    timestamp: u64,
+}
+
+/// Computes the fingerprint of the crash from where it occurred, ie. the
+/// exception and the return addresses on the stack of the crashing thread,
+/// relative to the modules they are in so that it is the same across runs.
+/// Returns `None` if none of the addresses are in a module.
+pub(crate) fn fingerprint(bytes: &[u8]) -> Option<String> {
+    let exception = crate::minidump::exception_record(bytes)?;
+    let modules = crate::minidump::module_list(bytes);
+
+    let locate = |address: u64| {
+        modules.iter().find_map(|module| {
+            let offset = address.checked_sub(module.base_of_image)?;
+            if offset >= u64::from(module.size_of_image) {
+                return None;
+            }
+
+            let file_name = module.name.rsplit(['/', '\\']).next()?;
+            Some((file_name, offset))
+        })
+    };
+
+    // The stack is scanned for anything that looks like a return address, as
+    // the stack isn't walked, which is stable enough for the same crash
+    let mut frames: Vec<_> = locate(exception.address).into_iter().collect();
+    if let Some(stack) = crate::minidump::thread_stack(bytes, exception.thread_id) {
+        frames.extend(
+            stack
+                .memory
+                .chunks_exact(std::mem::size_of::<usize>())
+                .filter_map(|word| {
+                    let mut address = [0u8; 8];
+                    address[..word.len()].copy_from_slice(word);
+                    locate(u64::from_le_bytes(address))
+                })
+                .take(MAX_FRAMES),
+        );
+    }
+
+    if frames.is_empty() {
+        return None;
+    }
+
+    // FNV-1a, as the hash must not change between versions of the application
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut update = |bytes: &[u8]| {
+        for b in bytes {
+            hash ^= u64::from(*b);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    update(&exception.code.to_le_bytes());
+    update(&exception.flags.to_le_bytes());
+    for (file_name, offset) in frames {
+        update(file_name.as_bytes());
+        update(&offset.to_le_bytes());
+    }
+
+    Some(format!("{:016x}", hash))
+}
+
+/// Persists the crash as the last uploaded one, returning whether it is a
+/// recurrence of the previous one
+pub(crate) fn record(
+    crash_dir: &Path,
+    fingerprint: &str,
+    timestamp: SystemTime,
+    window: Duration,
+) -> bool {
+    let path = crash_dir.join(LAST_UPLOAD_FILE);
+    let timestamp = timestamp
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+
+    let recurrence = std::fs::read(&path)
+        .ok()
+        .and_then(|contents| serde_json::from_slice::<LastUpload>(&contents).ok())
+        .is_some_and(|last| {
+            last.fingerprint == fingerprint
+                && timestamp >= last.timestamp
+                && timestamp - last.timestamp <= window.as_secs()
+        });
+
+    let last = LastUpload {
+        fingerprint: fingerprint.to_owned(),
+        timestamp,
+    };
+    match serde_json::to_vec(&last) {
+        Ok(contents) => {
+            if let Err(e) = std::fs::write(&path, contents) {
+                debug_print!("unable to write {}: {}", path.display(), e);
+            }
+        }
+        Err(e) => {
+            debug_print!("unable to serialize the last upload: {}", e);
+        }
+    }
+
+    recurrence
+}
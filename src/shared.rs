@@ -1,7 +1,12 @@
 use sentry_core::{protocol as proto, types};
 use std::{path::Path, time::SystemTime};
 
-pub(crate) fn assemble_envelope(md: CrashMetadata, minidump_path: &Path) -> proto::Envelope {
+/// Assembles the envelope for the crash, along with the error reading the
+/// minidump if it couldn't be attached
+pub(crate) fn assemble_envelope(
+    md: CrashMetadata,
+    minidump_path: &Path,
+) -> (proto::Envelope, Option<std::io::Error>) {
     let mut envelope = proto::Envelope::new();
 
     let timestamp = md
@@ -18,17 +23,19 @@ pub(crate) fn assemble_envelope(md: CrashMetadata, minidump_path: &Path) -> prot
 
     // An event_id is required, so if we were unable to get one from the .metadata
     // we just use the guid in the filename of the minidump
-    envelope.add_item(md.event.unwrap_or_else(|| {
-        proto::Event {
-            event_id: minidump_path
-                .file_stem()
-                .and_then(|fname| fname.to_str().and_then(|fs| fs.parse::<types::Uuid>().ok()))
-                .unwrap_or_else(types::random_uuid),
-            level: proto::Level::Fatal,
-            timestamp,
-            ..Default::default()
-        }
-    }));
+    let mut event = md.event.unwrap_or_else(|| proto::Event {
+        event_id: minidump_path
+            .file_stem()
+            .and_then(|fname| fname.to_str().and_then(|fs| fs.parse::<types::Uuid>().ok()))
+            .unwrap_or_else(types::random_uuid),
+        level: proto::Level::Fatal,
+        timestamp,
+        ..Default::default()
+    });
+    stamp_producer(&mut event);
+
+    let event_id = event.event_id;
+    envelope.add_item(event);
 
     // Unfortunately we can't really synthesize this with the current API as,
     // among other things, the session id is not exposed anywhere :-/
@@ -36,61 +43,166 @@ pub(crate) fn assemble_envelope(md: CrashMetadata, minidump_path: &Path) -> prot
         envelope.add_item(su);
     }
 
-    match std::fs::read(minidump_path) {
+    let read_error = match std::fs::read(minidump_path) {
         Err(e) => {
             debug_print!(
                 "unable to read minidump from '{}': {}",
                 minidump_path.display(),
                 e
             );
+            Some(e)
         }
         Ok(minidump) => {
             envelope.add_item(proto::EnvelopeItem::Attachment(proto::Attachment {
                 buffer: minidump,
-                filename: minidump_path.file_name().unwrap().to_string_lossy().into(),
+                // Minidumps that were written to memory don't have a
+                // meaningful filename
+                filename: if minidump_path.extension().is_some_and(|ext| ext == "dmp") {
+                    minidump_path.file_name().unwrap().to_string_lossy().into()
+                } else {
+                    format!("{}.dmp", event_id)
+                },
                 content_type: Some("application/octet-stream".to_owned()),
                 ty: Some(proto::AttachmentType::Minidump),
             }));
+            None
         }
+    };
+
+    (envelope, read_error)
+}
+
+/// Parses only the fields of the event that are most relevant to a crash, if
+/// the event as a whole can't be deserialized, eg. because the metadata was
+/// written by a version of this crate that used a different version of
+/// `sentry-core` whose protocol types have changed since. This way the crash
+/// is still sent, rather than only the minidump with an empty event.
+fn parse_minimal_event(line: &str) -> Option<proto::Event<'static>> {
+    let value: serde_json::Value = serde_json::from_str(line).ok()?;
+    let obj = value.as_object()?;
+
+    let str_field = |name: &str| obj.get(name).and_then(|v| v.as_str());
+
+    let mut event = proto::Event {
+        event_id: str_field("event_id")?.parse::<types::Uuid>().ok()?,
+        level: str_field("level")
+            .and_then(|level| level.parse().ok())
+            .unwrap_or(proto::Level::Fatal),
+        release: str_field("release").map(|r| r.to_owned().into()),
+        environment: str_field("environment").map(|e| e.to_owned().into()),
+        ..Default::default()
+    };
+
+    if let Some(timestamp) = obj
+        .get("timestamp")
+        .and_then(|ts| ts.as_f64())
+        .and_then(|ts| std::time::Duration::try_from_secs_f64(ts).ok())
+    {
+        event.timestamp = SystemTime::UNIX_EPOCH + timestamp;
     }
 
-    envelope
+    if let Some(tags) = obj.get("tags").and_then(|tags| tags.as_object()) {
+        event.tags = tags
+            .iter()
+            .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_owned())))
+            .collect();
+    }
+
+    if let Some(extra) = obj.get("extra").and_then(|extra| extra.as_object()) {
+        event.extra = extra.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    }
+
+    debug_print!("recovered the minimal event {}", event.event_id);
+    Some(event)
+}
+
+/// Records the code that produced the minidump in the event, so that minidumps
+/// Sentry is unable to process can be traced back to it
+fn stamp_producer(event: &mut proto::Event<'static>) {
+    let sdk = event
+        .sdk
+        .get_or_insert_with(|| {
+            std::borrow::Cow::Owned(proto::ClientSdkInfo {
+                name: env!("CARGO_PKG_NAME").to_owned(),
+                version: env!("CARGO_PKG_VERSION").to_owned(),
+                integrations: Vec::new(),
+                packages: Vec::new(),
+            })
+        })
+        .to_mut();
+
+    if !sdk.integrations.iter().any(|i| i == "breakpad") {
+        sdk.integrations.push("breakpad".to_owned());
+    }
+
+    let mut producer = proto::Map::new();
+    producer.insert("version".to_owned(), env!("CARGO_PKG_VERSION").into());
+    // The minidump is always written by breakpad's C++ writer
+    producer.insert("backend".to_owned(), "cpp".into());
+    producer.insert("writer".to_owned(), "breakpad".into());
+    #[cfg(feature = "handler")]
+    producer.insert(
+        "writer_version".to_owned(),
+        breakpad_handler::VERSION.into(),
+    );
+
+    event
+        .contexts
+        .insert("breakpad".to_owned(), proto::Context::Other(producer));
 }
 
-pub(crate) struct CrashMetadata {
-    pub(crate) event: Option<proto::Event<'static>>,
-    pub(crate) session_update: Option<proto::SessionUpdate<'static>>,
+/// The start of the header line of metadata files, followed by the version
+pub(crate) const METADATA_HEADER: &str = "breakpad-metadata ";
+/// The version of the metadata format that is written, newer versions can't
+/// be parsed
+pub(crate) const METADATA_VERSION: u32 = 1;
+
+/// The metadata for a crash that is written next to the minidump, with a
+/// `.metadata` extension, and sent along with it the next time the
+/// integration is initialized.
+///
+/// The file starts with a header line with the version of its format, eg.
+/// `breakpad-metadata 1`, followed by 2 lines, the first is the
+/// [`Event`](proto::Event) serialized as JSON and the second is the
+/// [`SessionUpdate`](proto::SessionUpdate) serialized as JSON, either of which
+/// can be empty if it wasn't captured. Files written by older versions of this
+/// crate have no header line.
+#[derive(Debug, Default)]
+pub struct CrashMetadata {
+    /// The crash event, with the scope applied to it
+    pub event: Option<proto::Event<'static>>,
+    /// The update for the session that crashed
+    pub session_update: Option<proto::SessionUpdate<'static>>,
 }
 
 impl CrashMetadata {
-    pub(crate) fn deserialize(path: &Path) -> Self {
-        if !path.exists() {
-            return Self {
-                event: None,
-                session_update: None,
-            };
-        }
+    /// Reads the metadata file, eg. to inspect the metadata of a pending
+    /// crash. Unlike the integration, this doesn't remove the file.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, crate::Error> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(Self::parse(&contents))
+    }
 
-        let contents = match std::fs::read_to_string(path) {
-            Ok(contents) => {
-                // Immediately remove the file so we don't try to do this again
-                let _ = std::fs::remove_file(path);
-                contents
-            }
-            Err(e) => {
-                debug_print!(
-                    "unable to read crash metadata from '{}': {}",
-                    path.display(),
-                    e
-                );
-                return Self {
-                    event: None,
-                    session_update: None,
-                };
-            }
-        };
+    /// Parses the contents of a metadata file, lines that can't be parsed are
+    /// skipped. Files in a newer format than this version of the crate writes
+    /// are skipped entirely.
+    pub fn parse(contents: &str) -> Self {
+        let mut lines = contents.lines().peekable();
 
-        let mut lines = contents.lines();
+        if let Some(version) = lines
+            .peek()
+            .and_then(|line| line.strip_prefix(METADATA_HEADER))
+        {
+            match version.parse::<u32>() {
+                Ok(version) if version <= METADATA_VERSION => {
+                    lines.next();
+                }
+                _ => {
+                    debug_print!("unsupported crash metadata version '{}'", version);
+                    return Self::default();
+                }
+            }
+        }
 
         let event = lines.next().and_then(|eve| {
             if !eve.is_empty() {
@@ -98,7 +210,7 @@ impl CrashMetadata {
                     Ok(event) => Some(event),
                     Err(e) => {
                         debug_print!("unable to deserialize Event: {}", e);
-                        None
+                        parse_minimal_event(eve)
                     }
                 }
             } else {
@@ -126,13 +238,49 @@ impl CrashMetadata {
         }
     }
 
-    pub(crate) fn serialize(self) -> Vec<u8> {
+    /// Reads and removes the metadata file, so that it is only sent once
+    pub(crate) fn deserialize(path: &Path) -> Self {
+        if !path.exists() {
+            return Self::default();
+        }
+
+        match std::fs::read_to_string(path) {
+            Ok(contents) => {
+                // Immediately remove the file so we don't try to do this again
+                let _ = std::fs::remove_file(path);
+                Self::parse(&contents)
+            }
+            Err(e) => {
+                debug_print!(
+                    "unable to read crash metadata from '{}': {}",
+                    path.display(),
+                    e
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Formats the metadata as a single pretty printed JSON object with
+    /// `event` and `session_update` keys, for inspecting it with support
+    /// tooling
+    pub fn to_pretty_json(&self) -> String {
+        serde_json::to_string_pretty(&serde_json::json!({
+            "event": self.event,
+            "session_update": self.session_update,
+        }))
+        .unwrap_or_default()
+    }
+
+    pub(crate) fn serialize(&self) -> Vec<u8> {
         use std::io::Write;
 
         let mut md = Vec::with_capacity(2048);
 
+        let _ = writeln!(&mut md, "{}{}", METADATA_HEADER, METADATA_VERSION);
+
         // Serialize the envelope then the session update to their own JSON line
-        if let Some(eve) = self.event {
+        if let Some(eve) = &self.event {
             debug_print!("serializing event to metadata");
             if let Err(e) = serde_json::to_writer(&mut md, &eve) {
                 debug_print!("failed to serialize event to crash metadata: {}", e);
@@ -141,7 +289,7 @@ impl CrashMetadata {
 
         let _ = writeln!(&mut md);
 
-        if let Some(su) = self.session_update {
+        if let Some(su) = &self.session_update {
             debug_print!("serializing session update to metadata");
             if let Err(e) = serde_json::to_writer(&mut md, &su) {
                 debug_print!(
@@ -154,4 +302,73 @@ impl CrashMetadata {
         let _ = writeln!(&mut md);
         md
     }
+
+    /// Serializes only the subset of the metadata that is most relevant to a
+    /// crash, without using `serde_json`
+    pub(crate) fn serialize_compact(&self) -> Vec<u8> {
+        debug_print!("serializing compact metadata");
+        crate::compact::serialize(self.event.as_ref(), self.session_update.as_ref())
+    }
+}
+
+/// Reads the metadata file of a pending crash and formats it as pretty
+/// printed JSON, see [`CrashMetadata::to_pretty_json`]
+pub fn dump_metadata(path: impl AsRef<Path>) -> Result<String, crate::Error> {
+    Ok(CrashMetadata::read(path)?.to_pretty_json())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENT_ID: types::Uuid = types::Uuid::from_u128(0x1234);
+
+    fn metadata() -> CrashMetadata {
+        CrashMetadata {
+            event: Some(proto::Event {
+                event_id: EVENT_ID,
+                release: Some("app@1.0.0".into()),
+                ..Default::default()
+            }),
+            session_update: None,
+        }
+    }
+
+    #[test]
+    fn parses_versioned_files() {
+        let serialized = metadata().serialize();
+        let contents = std::str::from_utf8(&serialized).unwrap();
+        assert!(contents.starts_with("breakpad-metadata 1\n"));
+
+        let md = CrashMetadata::parse(contents);
+        assert_eq!(md.event.map(|eve| eve.event_id), Some(EVENT_ID));
+        assert!(md.session_update.is_none());
+
+        let compact = metadata().serialize_compact();
+        let md = CrashMetadata::parse(std::str::from_utf8(&compact).unwrap());
+        assert_eq!(md.event.map(|eve| eve.event_id), Some(EVENT_ID));
+    }
+
+    #[test]
+    fn parses_files_without_header() {
+        let event = serde_json::to_string(&metadata().event).unwrap();
+
+        let md = CrashMetadata::parse(&format!("{}\n\n", event));
+        assert_eq!(md.event.map(|eve| eve.event_id), Some(EVENT_ID));
+        assert!(md.session_update.is_none());
+
+        let md = CrashMetadata::parse("\n\n");
+        assert!(md.event.is_none());
+    }
+
+    #[test]
+    fn skips_newer_versions() {
+        let event = serde_json::to_string(&metadata().event).unwrap();
+
+        let md = CrashMetadata::parse(&format!("breakpad-metadata 2\n{}\n\n", event));
+        assert!(md.event.is_none());
+
+        let md = CrashMetadata::parse(&format!("breakpad-metadata v1\n{}\n\n", event));
+        assert!(md.event.is_none());
+    }
 }
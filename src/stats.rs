@@ -0,0 +1,145 @@
+use serde::Deserialize;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::atomic,
+    time::{Duration, SystemTime},
+};
+
+/// The name of the file the stats are persisted to in the crash directory
+const STATS_FILE: &str = "crash_stats.json";
+
+/// Crash counters for the application, persisted in the crash directory so
+/// that they are kept across runs
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CrashStats {
+    /// The total number of crashes that have been captured
+    pub total_crashes: u64,
+    /// When the last crash occurred
+    pub last_crash: Option<SystemTime>,
+    /// The signal that caused the last crash, only available on Linux/Android
+    pub last_signal: Option<i32>,
+}
+
+/// The on-disk format of the stats file
+#[derive(Deserialize)]
+struct StatsFile {
+    total_crashes: u64,
+    /// Seconds since the Unix epoch
+    last_crash: Option<u64>,
+    last_signal: Option<i32>,
+}
+
+/// The stats are kept in atomics so that they can be updated from the crash
+/// callback without any locking
+pub(crate) struct PersistentStats {
+    path: PathBuf,
+    total_crashes: atomic::AtomicU64,
+    /// Seconds since the Unix epoch, 0 if there hasn't been a crash
+    last_crash: atomic::AtomicU64,
+    /// 0 if unknown
+    last_signal: atomic::AtomicI32,
+}
+
+impl PersistentStats {
+    /// Loads the stats from the crash directory, starting from scratch if
+    /// they haven't been persisted yet or can't be read
+    pub(crate) fn load(crash_dir: &Path) -> Self {
+        let path = crash_dir.join(STATS_FILE);
+
+        let stats = match std::fs::read(&path) {
+            Ok(contents) => match serde_json::from_slice::<StatsFile>(&contents) {
+                Ok(sf) => Some(sf),
+                Err(e) => {
+                    debug_print!("unable to parse {}: {}", path.display(), e);
+                    None
+                }
+            },
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    debug_print!("unable to read {}: {}", path.display(), e);
+                }
+                None
+            }
+        };
+
+        let (total_crashes, last_crash, last_signal) = stats.map_or((0, 0, 0), |sf| {
+            (
+                sf.total_crashes,
+                sf.last_crash.unwrap_or_default(),
+                sf.last_signal.unwrap_or_default(),
+            )
+        });
+
+        Self {
+            path,
+            total_crashes: atomic::AtomicU64::new(total_crashes),
+            last_crash: atomic::AtomicU64::new(last_crash),
+            last_signal: atomic::AtomicI32::new(last_signal),
+        }
+    }
+
+    /// Records a crash and persists the updated stats, returning them
+    pub(crate) fn record_crash(&self, signal: Option<i32>) -> CrashStats {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        self.total_crashes.fetch_add(1, atomic::Ordering::Relaxed);
+        self.last_crash.store(now, atomic::Ordering::Relaxed);
+        self.last_signal
+            .store(signal.unwrap_or_default(), atomic::Ordering::Relaxed);
+
+        let stats = self.get();
+        self.persist(&stats);
+        stats
+    }
+
+    pub(crate) fn get(&self) -> CrashStats {
+        let last_crash = self.last_crash.load(atomic::Ordering::Relaxed);
+        let last_signal = self.last_signal.load(atomic::Ordering::Relaxed);
+
+        CrashStats {
+            total_crashes: self.total_crashes.load(atomic::Ordering::Relaxed),
+            last_crash: (last_crash != 0)
+                .then(|| SystemTime::UNIX_EPOCH + Duration::from_secs(last_crash)),
+            last_signal: (last_signal != 0).then_some(last_signal),
+        }
+    }
+
+    /// Writes the stats with a single small write, as this is done in the
+    /// crashing process
+    fn persist(&self, stats: &CrashStats) {
+        let mut buf = [0u8; 128];
+        let len = {
+            let mut cursor = std::io::Cursor::new(&mut buf[..]);
+
+            let mut write = || -> std::io::Result<()> {
+                write!(cursor, "{{\"total_crashes\":{}", stats.total_crashes)?;
+                if let Some(lc) = stats.last_crash {
+                    let secs = lc
+                        .duration_since(SystemTime::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs();
+                    write!(cursor, ",\"last_crash\":{}", secs)?;
+                }
+                if let Some(ls) = stats.last_signal {
+                    write!(cursor, ",\"last_signal\":{}", ls)?;
+                }
+                cursor.write_all(b"}")
+            };
+
+            if let Err(e) = write() {
+                debug_print!("failed to format crash stats: {}", e);
+                return;
+            }
+
+            cursor.position() as usize
+        };
+
+        if let Err(e) = std::fs::write(&self.path, &buf[..len]) {
+            debug_print!("failed to write {}: {}", self.path.display(), e);
+        }
+    }
+}
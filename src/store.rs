@@ -0,0 +1,425 @@
+//! The minidumps in a crash directory, and everything that is written along
+//! with them, shared by the integration, the daemon and the consent API
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// How long a claim is honored, after which the process that made it is
+/// assumed to have died before it could release it
+const CLAIM_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// A minidump in a crash directory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredCrash {
+    /// The file stem of the minidump
+    pub id: String,
+    /// The path of the minidump
+    pub minidump_path: PathBuf,
+    /// The size of the minidump in bytes
+    pub size: u64,
+    /// When the minidump was last modified
+    pub modified: SystemTime,
+    /// Whether the metadata of the crash event was written next to the
+    /// minidump
+    pub has_metadata: bool,
+}
+
+impl StoredCrash {
+    /// The path of the metadata of the crash event, which may not exist
+    pub fn metadata_path(&self) -> PathBuf {
+        self.minidump_path.with_extension("metadata")
+    }
+}
+
+/// Summary of the contents of a crash directory, see [`CrashStore::stats`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StoreStats {
+    /// The number of minidumps
+    pub dumps: usize,
+    /// The total size of the minidumps in bytes
+    pub total_size: u64,
+    /// The number of metadata files without a minidump
+    pub orphans: usize,
+}
+
+/// The minidumps in a crash directory that haven't been uploaded yet, ie. the
+/// `<id>.dmp` files, along with the `<id>.metadata` of their crash event and
+/// the attachments captured for them
+#[derive(Clone, Debug)]
+pub struct CrashStore {
+    crash_dir: PathBuf,
+}
+
+impl CrashStore {
+    /// Creates the store for the crash directory, which is treated as empty
+    /// if it doesn't exist
+    pub fn new(crash_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            crash_dir: crash_dir.into(),
+        }
+    }
+
+    /// The crash directory of the store
+    pub fn crash_dir(&self) -> &Path {
+        &self.crash_dir
+    }
+
+    /// Gets the minidumps in the crash directory, in no particular order
+    pub fn list(&self) -> Vec<StoredCrash> {
+        self.read_dir()
+            .into_iter()
+            .filter_map(|entry| {
+                let minidump_path = entry.path();
+                if minidump_path.extension().and_then(|ext| ext.to_str()) != Some("dmp") {
+                    return None;
+                }
+
+                let md = entry.metadata().ok()?;
+                Some(StoredCrash {
+                    id: minidump_path.file_stem()?.to_str()?.to_owned(),
+                    size: md.len(),
+                    modified: md.modified().ok()?,
+                    has_metadata: minidump_path.with_extension("metadata").is_file(),
+                    minidump_path,
+                })
+            })
+            .collect()
+    }
+
+    /// Gets the metadata files whose minidump is missing, eg. because it was
+    /// removed by hand, which are never uploaded
+    pub fn orphans(&self) -> Vec<PathBuf> {
+        self.read_dir()
+            .into_iter()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension().and_then(|ext| ext.to_str()) == Some("metadata")
+                    && !path.with_extension("dmp").exists()
+            })
+            .collect()
+    }
+
+    /// Gets the number and size of the minidumps, and the number of orphans
+    pub fn stats(&self) -> StoreStats {
+        let dumps = self.list();
+        StoreStats {
+            dumps: dumps.len(),
+            total_size: dumps.iter().map(|crash| crash.size).sum(),
+            orphans: self.orphans().len(),
+        }
+    }
+
+    /// Claims the crash for this process, so that other processes that share
+    /// the crash directory, eg. a daemon that uploads the minidumps of a
+    /// fleet of applications, don't upload it as well. Returns `None` if another process has
+    /// already claimed it. The claim is released when it is dropped.
+    pub fn claim(&self, crash: &StoredCrash) -> Option<CrashClaim> {
+        let path = crash.minidump_path.with_extension("claim");
+
+        for _ in 0..2 {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(_) => return Some(CrashClaim { path }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let stale = std::fs::metadata(&path)
+                        .and_then(|md| md.modified())
+                        .ok()
+                        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                        .is_some_and(|age| age > CLAIM_TIMEOUT);
+                    if !stale {
+                        return None;
+                    }
+
+                    debug_print!("taking over stale claim '{}'", path.display());
+                    let _ = std::fs::remove_file(&path);
+                }
+                Err(e) => {
+                    debug_print!("unable to claim '{}': {}", path.display(), e);
+                    return None;
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Reads the metadata of the crash event, which is empty if it wasn't
+    /// written or can't be read
+    pub fn load(&self, crash: &StoredCrash) -> crate::CrashMetadata {
+        // Unlike uploading, loading leaves the metadata in place so that it
+        // is still sent with the minidump
+        crate::CrashMetadata::read(crash.metadata_path()).unwrap_or_default()
+    }
+
+    /// Sends the minidumps to Sentry via the client of the hub, along with the
+    /// metadata and attachments written for them, and removes them. Minidumps
+    /// are uploaded or discarded according to the
+    /// [`ConsentPolicy`](crate::ConsentPolicy), and the ones that another
+    /// process has claimed are skipped.
+    pub fn upload_pending(&self, hub: &sentry_core::Hub) {
+        crate::upload::upload_consented(&self.crash_dir, hub);
+    }
+
+    /// Same as [`CrashStore::upload_pending`], but on a background worker, so
+    /// the caller isn't blocked while the minidumps are read and sent. The
+    /// minidumps are sent one at a time, waiting for each to be flushed by the
+    /// transport before reading the next, and `on_progress` is invoked on the
+    /// worker after each of them. The worker stops once the hub is dropped.
+    /// With [`ConsentPolicy::NeverSend`](crate::ConsentPolicy::NeverSend),
+    /// progress is reported for each minidump as it is discarded, and with
+    /// [`ConsentPolicy::AskBeforeSend`](crate::ConsentPolicy::AskBeforeSend),
+    /// the minidumps are left for the user to approve and no progress is
+    /// reported.
+    pub fn upload_pending_async<F>(
+        &self,
+        hub: &std::sync::Arc<sentry_core::Hub>,
+        on_progress: F,
+    ) -> Result<std::thread::JoinHandle<()>, crate::Error>
+    where
+        F: Fn(crate::UploadProgress) + Send + 'static,
+    {
+        Ok(crate::upload::upload_pending_async(
+            self.crash_dir.clone(),
+            std::sync::Arc::downgrade(hub),
+            on_progress,
+        )?)
+    }
+
+    /// Sends a single crash, regardless of the
+    /// [`ConsentPolicy`](crate::ConsentPolicy), eg. once the user approved
+    /// it, and removes it. Returns `false` if the hub has no client, or
+    /// another process has claimed the crash.
+    pub fn upload(&self, crash: &StoredCrash, hub: &sentry_core::Hub) -> bool {
+        let client = match hub.client() {
+            Some(c) => c,
+            None => return false,
+        };
+        let _claim = match self.claim(crash) {
+            Some(claim) => claim,
+            None => return false,
+        };
+
+        let index = crate::index::read(&self.crash_dir);
+        match crate::upload::upload_minidump(
+            &self.crash_dir,
+            &index,
+            &client,
+            crash.minidump_path.clone(),
+        ) {
+            Some(id) => {
+                crate::index::prune(&self.crash_dir, &[id]);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the minidump along with its metadata and attachments, and its
+    /// entry in the index
+    pub fn delete(&self, crash: &StoredCrash) {
+        if let Some(id) = crate::policy::remove(&self.crash_dir, &crash.minidump_path) {
+            crate::index::prune(&self.crash_dir, &[id]);
+        }
+    }
+
+    fn read_dir(&self) -> Vec<std::fs::DirEntry> {
+        match std::fs::read_dir(&self.crash_dir) {
+            Ok(rd) => rd.filter_map(|e| e.ok()).collect(),
+            Err(e) => {
+                debug_print!(
+                    "Unable to read crash directory '{}': {}",
+                    self.crash_dir.display(),
+                    e
+                );
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// A crash claimed with [`CrashStore::claim`], which is released when dropped
+#[derive(Debug)]
+pub struct CrashClaim {
+    path: PathBuf,
+}
+
+impl Drop for CrashClaim {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            debug_print!("failed to release claim '{}': {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates an empty crash directory for a test
+    fn fixture(name: &str) -> CrashStore {
+        let crash_dir = std::env::temp_dir().join(format!(
+            "sentry-contrib-breakpad-store-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&crash_dir);
+        std::fs::create_dir_all(&crash_dir).unwrap();
+        CrashStore::new(crash_dir)
+    }
+
+    fn write(store: &CrashStore, file_name: &str, contents: &[u8]) {
+        std::fs::write(store.crash_dir().join(file_name), contents).unwrap();
+    }
+
+    fn crash(store: &CrashStore, id: &str) -> StoredCrash {
+        store
+            .list()
+            .into_iter()
+            .find(|crash| crash.id == id)
+            .unwrap()
+    }
+
+    #[test]
+    fn lists_minidumps() {
+        let store = fixture("list");
+        write(&store, "a.dmp", b"MDMP1234");
+        write(&store, "a.metadata", b"");
+        write(&store, "b.dmp", b"MDMP");
+        write(&store, "c.metadata", b"");
+        write(&store, "index.json", b"[]");
+
+        let mut crashes = store.list();
+        crashes.sort_by(|a, b| a.id.cmp(&b.id));
+
+        assert_eq!(crashes.len(), 2);
+        assert_eq!(crashes[0].id, "a");
+        assert_eq!(crashes[0].size, 8);
+        assert!(crashes[0].has_metadata);
+        assert_eq!(crashes[1].id, "b");
+        assert_eq!(crashes[1].size, 4);
+        assert!(!crashes[1].has_metadata);
+
+        assert_eq!(store.orphans(), vec![store.crash_dir().join("c.metadata")]);
+        assert_eq!(
+            store.stats(),
+            StoreStats {
+                dumps: 2,
+                total_size: 12,
+                orphans: 1,
+            }
+        );
+
+        let _ = std::fs::remove_dir_all(store.crash_dir());
+    }
+
+    #[test]
+    fn missing_directory_is_empty() {
+        let store = fixture("missing");
+        std::fs::remove_dir_all(store.crash_dir()).unwrap();
+
+        assert!(store.list().is_empty());
+        assert_eq!(store.stats(), StoreStats::default());
+    }
+
+    #[test]
+    fn claims_are_exclusive() {
+        let store = fixture("claim");
+        write(&store, "a.dmp", b"MDMP");
+        let crash = crash(&store, "a");
+
+        let claim = store.claim(&crash).unwrap();
+        assert!(store.claim(&crash).is_none());
+
+        drop(claim);
+        assert!(!crash.minidump_path.with_extension("claim").exists());
+        assert!(store.claim(&crash).is_some());
+
+        let _ = std::fs::remove_dir_all(store.crash_dir());
+    }
+
+    #[test]
+    fn takes_over_stale_claims() {
+        let store = fixture("stale");
+        write(&store, "a.dmp", b"MDMP");
+        write(&store, "a.claim", b"");
+        let crash = crash(&store, "a");
+
+        // A claim that is still fresh belongs to another process
+        assert!(store.claim(&crash).is_none());
+
+        // Left behind by a process that died while uploading
+        std::fs::File::options()
+            .write(true)
+            .open(store.crash_dir().join("a.claim"))
+            .unwrap()
+            .set_modified(SystemTime::now() - CLAIM_TIMEOUT - Duration::from_secs(1))
+            .unwrap();
+        assert!(store.claim(&crash).is_some());
+
+        let _ = std::fs::remove_dir_all(store.crash_dir());
+    }
+
+    #[test]
+    fn load_keeps_metadata() {
+        let store = fixture("load");
+        write(&store, "a.dmp", b"MDMP");
+        let event_id = sentry_core::types::Uuid::from_u128(0x1234);
+        let md = crate::CrashMetadata {
+            event: Some(sentry_core::protocol::Event {
+                event_id,
+                ..Default::default()
+            }),
+            session_update: None,
+        };
+        write(&store, "a.metadata", &md.serialize());
+        let crash = crash(&store, "a");
+
+        for _ in 0..2 {
+            let loaded = store.load(&crash);
+            assert_eq!(loaded.event.map(|event| event.event_id), Some(event_id));
+        }
+        assert!(crash.metadata_path().exists());
+
+        let _ = std::fs::remove_dir_all(store.crash_dir());
+    }
+
+    #[test]
+    fn load_without_metadata_is_empty() {
+        let store = fixture("load-empty");
+        write(&store, "a.dmp", b"MDMP");
+
+        let loaded = store.load(&crash(&store, "a"));
+        assert!(loaded.event.is_none());
+        assert!(loaded.session_update.is_none());
+
+        let _ = std::fs::remove_dir_all(store.crash_dir());
+    }
+
+    #[test]
+    fn delete_removes_everything() {
+        let store = fixture("delete");
+        write(&store, "a.dmp", b"MDMP");
+        write(&store, "a.metadata", b"");
+        write(&store, "b.dmp", b"MDMP");
+
+        store.delete(&crash(&store, "a"));
+
+        assert!(!store.crash_dir().join("a.dmp").exists());
+        assert!(!store.crash_dir().join("a.metadata").exists());
+        assert_eq!(
+            store
+                .list()
+                .into_iter()
+                .map(|crash| crash.id)
+                .collect::<Vec<_>>(),
+            vec!["b".to_owned()]
+        );
+
+        let _ = std::fs::remove_dir_all(store.crash_dir());
+    }
+}
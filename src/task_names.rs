@@ -0,0 +1,114 @@
+//! The names of the threads, and the labels of the tasks that they were
+//! running when the crash occurred, see [`TaskNames`](crate::TaskNames)
+
+use crate::minidump::{NewStream, StreamType};
+use std::{collections::BTreeMap, path::Path};
+
+/// Appends the labels registered with [`TaskNames`](crate::TaskNames) to the
+/// minidump as a [`StreamType::TaskNames`] stream, and the names of the threads
+/// as a [`StreamType::ThreadNames`] stream, which processors show instead of
+/// the bare thread ids. Threads that are running a task are named after both,
+/// eg. `tokio-worker-3: handle_checkout`.
+pub(crate) fn capture(minidump_path: &Path) {
+    let task_names = breakpad_handler::task_names();
+    let mut thread_names = thread_names();
+
+    for (thread_id, label) in &task_names {
+        let name = match thread_names.get(thread_id) {
+            Some(thread_name) => format!("{}: {}", thread_name, label),
+            None => label.clone(),
+        };
+        thread_names.insert(*thread_id, name);
+    }
+
+    if thread_names.is_empty() {
+        return;
+    }
+
+    let mut streams = Vec::with_capacity(2);
+
+    if !task_names.is_empty() {
+        let mut labels = Vec::new();
+        for (thread_id, label) in &task_names {
+            labels.extend_from_slice(&thread_id.to_le_bytes());
+            labels.extend_from_slice(label.as_bytes());
+            labels.push(0);
+        }
+
+        streams.push(NewStream {
+            stream_type: StreamType::TaskNames,
+            data: labels,
+            relative_rvas: Vec::new(),
+        });
+    }
+
+    // A MINIDUMP_THREAD_NAME_LIST, followed by the MINIDUMP_STRINGs its
+    // entries point to
+    let mut names = Vec::new();
+    let mut relative_rvas = Vec::with_capacity(thread_names.len());
+    names.extend_from_slice(&(thread_names.len() as u32).to_le_bytes());
+
+    let mut string_offset = 4 + thread_names.len() * 12;
+    let mut strings = Vec::new();
+    for (thread_id, name) in &thread_names {
+        let utf16: Vec<u16> = name.encode_utf16().collect();
+
+        names.extend_from_slice(&thread_id.to_le_bytes());
+        relative_rvas.push(names.len());
+        names.extend_from_slice(&(string_offset as u64).to_le_bytes());
+
+        // The length excludes the nul terminator
+        strings.extend_from_slice(&(utf16.len() as u32 * 2).to_le_bytes());
+        for c in utf16.iter().chain(std::iter::once(&0)) {
+            strings.extend_from_slice(&c.to_le_bytes());
+        }
+        strings.resize((strings.len() + 3) & !3, 0);
+
+        string_offset = 4 + thread_names.len() * 12 + strings.len();
+    }
+    names.extend_from_slice(&strings);
+
+    streams.push(NewStream {
+        stream_type: StreamType::ThreadNames,
+        data: names,
+        relative_rvas,
+    });
+
+    if let Err(e) = crate::minidump::append_streams(minidump_path, &streams) {
+        debug_print!(
+            "unable to add thread names to {}: {}",
+            minidump_path.display(),
+            e
+        );
+    }
+}
+
+/// Gets the names of the threads of the process, eg. `RenderThread`, as set
+/// with `pthread_setname_np` or by the executor for its workers, which
+/// breakpad doesn't record in the minidump
+fn thread_names() -> BTreeMap<u32, String> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let tasks = match std::fs::read_dir("/proc/self/task") {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                debug_print!("unable to list the threads of the process: {}", e);
+                return BTreeMap::new();
+            }
+        };
+
+        tasks
+            .filter_map(|task| {
+                let task = task.ok()?;
+                let thread_id = task.file_name().to_str()?.parse().ok()?;
+                let comm = std::fs::read_to_string(task.path().join("comm")).ok()?;
+                let name = comm.trim_end();
+                (!name.is_empty()).then(|| (thread_id, name.to_owned()))
+            })
+            .collect()
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        BTreeMap::new()
+    }
+}
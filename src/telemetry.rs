@@ -0,0 +1,176 @@
+//! Ring buffers of in-memory telemetry, eg. high frequency logs, that are
+//! snapshotted into the minidump when a crash occurs, see
+//! [`BreakpadIntegration::register_telemetry_ring`](crate::BreakpadIntegration::register_telemetry_ring)
+
+use crate::minidump::TelemetrySnapshot;
+use sentry_core::protocol as proto;
+use std::path::Path;
+#[cfg(feature = "handler")]
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+/// A ring buffer that the application writes telemetry to, eg. in shared
+/// memory mapped by several processes
+///
+/// The cursor is the total number of bytes that have been written to the
+/// ring, so the next byte is written at `cursor % buffer.len()`, and is
+/// advanced after the bytes have been written.
+#[cfg(feature = "handler")]
+#[derive(Copy, Clone, Debug)]
+pub struct TelemetryRing {
+    name: &'static str,
+    buffer: &'static [AtomicU8],
+    cursor: &'static AtomicU64,
+}
+
+#[cfg(feature = "handler")]
+impl TelemetryRing {
+    /// Creates the ring, the name identifies its snapshots, eg. to pick the
+    /// decoder registered with
+    /// [`BreakpadIntegration::register_telemetry_decoder`](crate::BreakpadIntegration::register_telemetry_decoder)
+    pub fn new(
+        name: &'static str,
+        buffer: &'static [AtomicU8],
+        cursor: &'static AtomicU64,
+    ) -> Self {
+        Self {
+            name,
+            buffer,
+            cursor,
+        }
+    }
+
+    /// The name of the ring
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The size of the ring in bytes
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Whether the ring has no room for any bytes
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Copies the contents of the ring, oldest first, into a
+    /// [`StreamType::TelemetryRing`](crate::minidump::StreamType::TelemetryRing)
+    /// stream. The ring keeps being written to while it is copied, so the
+    /// cursor is recorded before and after the copy, which tells the oldest
+    /// bytes that may have been overwritten apart from the rest.
+    fn snapshot(&self) -> Vec<u8> {
+        let len = self.buffer.len() as u64;
+        let cursor = self.cursor.load(Ordering::Acquire);
+        let (oldest, count) = if cursor < len {
+            (0, cursor)
+        } else {
+            (cursor % len, len)
+        };
+
+        let mut data = Vec::with_capacity(self.name.len() + 17 + count as usize);
+        data.extend_from_slice(self.name.as_bytes());
+        data.push(0);
+        data.extend_from_slice(&cursor.to_le_bytes());
+        data.extend_from_slice(&[0; 8]);
+        data.extend(
+            (0..count).map(|i| self.buffer[((oldest + i) % len) as usize].load(Ordering::Relaxed)),
+        );
+
+        let end_cursor = self.cursor.load(Ordering::Acquire);
+        let end = self.name.len() + 9;
+        data[end..end + 8].copy_from_slice(&end_cursor.to_le_bytes());
+        data
+    }
+}
+
+/// Callback that decodes a snapshot of a telemetry ring into attachments, see
+/// [`BreakpadIntegration::register_telemetry_decoder`](crate::BreakpadIntegration::register_telemetry_decoder)
+type Decoder = dyn Fn(&TelemetrySnapshot<'_>) -> Vec<proto::Attachment> + Send + Sync;
+
+#[cfg(feature = "handler")]
+static RINGS: std::sync::Mutex<Vec<TelemetryRing>> = std::sync::Mutex::new(Vec::new());
+
+pub(crate) static DECODERS: std::sync::Mutex<Vec<(String, Box<Decoder>)>> =
+    std::sync::Mutex::new(Vec::new());
+
+#[cfg(feature = "handler")]
+pub(crate) fn register(ring: TelemetryRing) {
+    RINGS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .push(ring);
+}
+
+/// Gets the names and sizes of the rings that have been registered so far
+#[cfg(feature = "handler")]
+pub(crate) fn registered() -> Vec<(&'static str, usize)> {
+    RINGS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .iter()
+        .map(|ring| (ring.name, ring.len()))
+        .collect()
+}
+
+/// Appends a snapshot of each of the registered rings to the minidump
+#[cfg(feature = "handler")]
+pub(crate) fn capture(minidump_path: &Path) {
+    // Another thread could have crashed while registering a ring
+    let rings = match RINGS.try_lock() {
+        Ok(rings) => rings,
+        Err(_) => return,
+    };
+
+    let streams: Vec<_> = rings
+        .iter()
+        .filter(|ring| !ring.is_empty())
+        .map(|ring| crate::minidump::NewStream {
+            stream_type: crate::minidump::StreamType::TelemetryRing,
+            data: ring.snapshot(),
+            relative_rvas: Vec::new(),
+        })
+        .collect();
+
+    if streams.is_empty() {
+        return;
+    }
+
+    if let Err(e) = crate::minidump::append_streams(minidump_path, &streams) {
+        debug_print!(
+            "unable to snapshot telemetry rings in {}: {}",
+            minidump_path.display(),
+            e
+        );
+    }
+}
+
+/// Decodes the snapshots in the minidump with the decoders registered for
+/// them, snapshots without a decoder are only left in the minidump
+pub(crate) fn decode(minidump_path: &Path) -> Vec<proto::Attachment> {
+    let decoders = DECODERS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    if decoders.is_empty() {
+        return Vec::new();
+    }
+
+    let bytes = match std::fs::read(minidump_path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            debug_print!("unable to read {}: {}", minidump_path.display(), e);
+            return Vec::new();
+        }
+    };
+
+    crate::minidump::telemetry_snapshots(&bytes)
+        .iter()
+        .flat_map(|snapshot| {
+            decoders
+                .iter()
+                .filter(|(name, _)| name.as_str() == snapshot.name)
+                .flat_map(|(_, decode)| decode(snapshot))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
@@ -0,0 +1,83 @@
+//! Helpers for testing crash handling without a network, eg. in CI
+
+use sentry_core::protocol as proto;
+use std::sync::{Arc, Mutex};
+
+/// A transport that captures the envelopes sent with it in memory rather than
+/// sending them to Sentry. It can be used as the inner transport of a
+/// [`BreakpadTransportFactory`](crate::BreakpadTransportFactory), eg.
+/// `BreakpadTransportFactory::new(CrashSendStyle::SendNextSession, Arc::new(transport.clone()))`,
+/// to verify the envelopes sent for crashes from previous sessions.
+#[derive(Default)]
+pub struct CapturingTransport {
+    envelopes: Mutex<Vec<proto::Envelope>>,
+}
+
+impl CapturingTransport {
+    /// Creates a new transport, the `Arc` of which is a transport factory
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Gets a copy of the envelopes that have been captured so far
+    pub fn envelopes(&self) -> Vec<proto::Envelope> {
+        self.envelopes
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Takes the envelopes that have been captured so far
+    pub fn take_envelopes(&self) -> Vec<proto::Envelope> {
+        std::mem::take(
+            &mut *self
+                .envelopes
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner),
+        )
+    }
+}
+
+impl sentry_core::Transport for CapturingTransport {
+    fn send_envelope(&self, envelope: proto::Envelope) {
+        self.envelopes
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push(envelope);
+    }
+}
+
+/// Assertion helpers for the envelopes captured by a [`CapturingTransport`]
+pub trait EnvelopeExt {
+    /// Whether the envelope has a minidump attachment
+    fn has_minidump_attachment(&self) -> bool;
+    /// Gets the level of the event in the envelope, if it has one
+    fn event_level(&self) -> Option<proto::Level>;
+    /// Gets the status of the session update in the envelope, if it has one
+    fn session_status(&self) -> Option<proto::SessionStatus>;
+}
+
+impl EnvelopeExt for proto::Envelope {
+    fn has_minidump_attachment(&self) -> bool {
+        self.items().any(|item| {
+            matches!(
+                item,
+                proto::EnvelopeItem::Attachment(proto::Attachment {
+                    ty: Some(proto::AttachmentType::Minidump),
+                    ..
+                })
+            )
+        })
+    }
+
+    fn event_level(&self) -> Option<proto::Level> {
+        self.event().map(|eve| eve.level)
+    }
+
+    fn session_status(&self) -> Option<proto::SessionStatus> {
+        self.items().find_map(|item| match item {
+            proto::EnvelopeItem::SessionUpdate(su) => Some(su.status),
+            _ => None,
+        })
+    }
+}
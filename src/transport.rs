@@ -1,8 +1,17 @@
+use crate::queue::{EnvelopeQueue, Queued};
 use sentry_core::{ClientOptions, Envelope, Transport, TransportFactory};
-use std::{sync::Arc, time::Duration};
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, Instant, SystemTime},
+};
 
 /// Determines how crashes are sent to Sentry after they have been captured.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
+#[non_exhaustive]
 pub enum CrashSendStyle {
     /// Attempts to send crash envelopes immediately, in the same session that
     /// crashed, which may be unreliable depending on the overall state of the
@@ -12,6 +21,118 @@ pub enum CrashSendStyle {
     /// [`Transport`], initializing the BreakpadIntegration with the same path
     /// for crashes will send any existing crashes from previous sessions.
     SendNextSession,
+    /// Same as [`CrashSendStyle::SendNextSession`], but every envelope that is
+    /// sent after the crash, eg. the session update and breadcrumbs flushed
+    /// while the client is closed, is also serialized to the crash directory
+    /// instead of being forwarded to the final [`Transport`], which would
+    /// likely lose them as the process dies. They are sent along with the
+    /// crashes from previous sessions.
+    StoreAllNextSession,
+}
+
+/// The budget of the transport that was created last in milliseconds, or
+/// [`u64::MAX`] if it has none, for the integration to enforce while it
+/// captures the crash event. It is read in the crashing process, so it is an
+/// atomic rather than a lock that a dead thread may hold
+static METADATA_BUDGET_MS: AtomicU64 = AtomicU64::new(u64::MAX);
+
+#[cfg(feature = "handler")]
+pub(crate) fn metadata_budget() -> Option<Duration> {
+    match METADATA_BUDGET_MS.load(Ordering::Relaxed) {
+        u64::MAX => None,
+        ms => Some(Duration::from_millis(ms)),
+    }
+}
+
+/// How much of the crash event is captured, see
+/// [`BreakpadTransportFactory::with_metadata_budget`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum MetadataLevel {
+    /// The entire event, with everything that was applied from the scope
+    Full,
+    /// Only the tags, the basic attributes such as the release and environment,
+    /// and the extra added by this crate
+    TagsOnly,
+    /// Only the event id, level and timestamp
+    EventIdOnly,
+}
+
+impl MetadataLevel {
+    /// Selects the level based on how much of the budget has already been
+    /// spent since the crash
+    #[cfg(feature = "handler")]
+    pub(crate) fn select(crashed_at: Instant, budget: Duration) -> Self {
+        let elapsed = crashed_at.elapsed();
+
+        if elapsed >= budget {
+            Self::EventIdOnly
+        } else if elapsed >= budget / 2 {
+            Self::TagsOnly
+        } else {
+            Self::Full
+        }
+    }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::TagsOnly => "tags_only",
+            Self::EventIdOnly => "event_id_only",
+        }
+    }
+
+    fn from_str(level: &str) -> Option<Self> {
+        match level {
+            "full" => Some(Self::Full),
+            "tags_only" => Some(Self::TagsOnly),
+            "event_id_only" => Some(Self::EventIdOnly),
+            _ => None,
+        }
+    }
+
+    /// Copies the parts of the event for this level
+    fn capture(
+        self,
+        eve: &sentry_core::protocol::Event<'static>,
+    ) -> sentry_core::protocol::Event<'static> {
+        use sentry_core::protocol as proto;
+
+        if self == Self::Full {
+            return eve.clone();
+        }
+
+        let mut event = proto::Event {
+            event_id: eve.event_id,
+            level: eve.level,
+            timestamp: eve.timestamp,
+            extra: eve
+                .extra
+                .iter()
+                .filter(|(k, _)| {
+                    k.starts_with("__breakpad")
+                        || (self == Self::TagsOnly && k.starts_with("breakpad."))
+                })
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            ..Default::default()
+        };
+
+        if self == Self::TagsOnly {
+            event.platform = eve.platform.clone();
+            event.release = eve.release.clone();
+            event.dist = eve.dist.clone();
+            event.environment = eve.environment.clone();
+            event.server_name = eve.server_name.clone();
+            event.tags = eve.tags.clone();
+        }
+
+        event.tags.insert(
+            "breakpad.metadata_level".to_owned(),
+            self.as_str().to_owned(),
+        );
+
+        event
+    }
 }
 
 /// The [`TransportFactory`](https://docs.rs/sentry-core/0.23.0/sentry_core/trait.TransportFactory.html) implementation that must be used in concert with
@@ -20,6 +141,9 @@ pub enum CrashSendStyle {
 pub struct BreakpadTransportFactory {
     inner: Arc<dyn TransportFactory>,
     style: CrashSendStyle,
+    queue_capacity: Option<usize>,
+    compact_metadata: bool,
+    metadata_budget: Option<Duration>,
 }
 
 impl BreakpadTransportFactory {
@@ -27,15 +151,71 @@ impl BreakpadTransportFactory {
         Self {
             style,
             inner: transport,
+            queue_capacity: None,
+            compact_metadata: false,
+            metadata_budget: None,
         }
     }
+
+    /// Forwards envelopes to the wrapped transport from a background queue
+    /// where crash envelopes always take priority over other telemetry, and
+    /// are never dropped when the wrapped transport is saturated. Other
+    /// telemetry is limited to `capacity` queued envelopes, dropping the oldest
+    /// ones first.
+    ///
+    /// With [`CrashSendStyle::SendImmediately`], the minidump and its metadata
+    /// are also kept on disk until the wrapped transport confirms the crash
+    /// was delivered, so that it is sent in the next session otherwise.
+    pub fn with_priority_queue(mut self, capacity: usize) -> Self {
+        self.queue_capacity = Some(capacity);
+        self
+    }
+
+    /// Serializes crash metadata with a small hand-rolled JSON writer instead
+    /// of `serde_json`, which allocates far less in the crashing process, but
+    /// only captures the basic attributes, user, fingerprint, exceptions
+    /// without their stack traces, sdk, tags, extra, debug images and
+    /// breadcrumbs of the event, and the session update. Of the contexts, only
+    /// `os`, `runtime`, and the untyped ones such as those added by the
+    /// integration are captured, notably `device` and `app` are not.
+    pub fn with_compact_metadata(mut self) -> Self {
+        self.compact_metadata = true;
+        self
+    }
+
+    /// Limits how long capturing the crash metadata may take in the crashing
+    /// process, so that a pathologically large scope can't stall it. The time
+    /// is measured from when the integration is notified of the crash until
+    /// the scope is about to be applied to the crash event. If that has taken
+    /// more than half of the budget, the breadcrumbs of the scope are left out
+    /// and only the tags and basic attributes of the event are captured, and
+    /// if it has taken the entire budget, the scope isn't applied at all and
+    /// only the event id is captured. Degraded events are tagged with
+    /// `breakpad.metadata_level`.
+    pub fn with_metadata_budget(mut self, budget: Duration) -> Self {
+        self.metadata_budget = Some(budget);
+        self
+    }
 }
 
 impl TransportFactory for BreakpadTransportFactory {
     fn create_transport(&self, options: &ClientOptions) -> Arc<dyn Transport> {
+        let budget_ms = self.metadata_budget.map_or(u64::MAX, |budget| {
+            budget.as_millis().min(u128::from(u64::MAX - 1)) as u64
+        });
+        METADATA_BUDGET_MS.store(budget_ms, Ordering::Relaxed);
+
+        let inner = self.inner.create_transport(options);
+        let queue = self
+            .queue_capacity
+            .map(|capacity| EnvelopeQueue::new(inner.clone(), capacity));
+
         Arc::new(BreakpadTransport {
-            inner: self.inner.create_transport(options),
+            inner,
             style: self.style,
+            queue,
+            compact_metadata: self.compact_metadata,
+            store_dir: Mutex::new(None),
         })
     }
 }
@@ -43,24 +223,46 @@ impl TransportFactory for BreakpadTransportFactory {
 struct BreakpadTransport {
     inner: Arc<dyn Transport>,
     style: CrashSendStyle,
+    queue: Option<EnvelopeQueue>,
+    compact_metadata: bool,
+    /// The crash directory that every envelope is stored in once a crash has
+    /// been captured with [`CrashSendStyle::StoreAllNextSession`]
+    store_dir: Mutex<Option<PathBuf>>,
 }
 
 impl BreakpadTransport {
-    fn process(&self, envelope: Envelope) -> Option<Envelope> {
+    fn process(&self, envelope: Envelope) -> Option<Queued> {
         use sentry_core::protocol as proto;
 
+        let passthrough = |envelope| {
+            Some(Queued {
+                envelope,
+                spooled: None,
+            })
+        };
+
         match envelope.event() {
             // Check if this is actually a crash event
-            Some(eve) if !eve.extra.contains_key("__breakpad_minidump_path") => Some(envelope),
-            None => Some(envelope),
+            Some(eve) if !eve.extra.contains_key("__breakpad_minidump_path") => {
+                passthrough(envelope)
+            }
+            None => passthrough(envelope),
             Some(eve) => {
-                let mut event = eve.clone();
+                // Selected by the integration before the scope was applied
+                let level = eve
+                    .extra
+                    .get("__breakpad_metadata_level")
+                    .and_then(|level| level.as_str())
+                    .and_then(MetadataLevel::from_str)
+                    .unwrap_or(MetadataLevel::Full);
+                let mut event = level.capture(eve);
+                event.extra.remove("__breakpad_metadata_level");
 
                 // Clear the exceptions array, Sentry will automatically fill this
                 // in for the event due to it having a minidump attachment
                 event.exception.values.clear();
 
-                let mut minidump_path = match event.extra.remove("__breakpad_minidump_path") {
+                let minidump_path = match event.extra.remove("__breakpad_minidump_path") {
                     Some(sentry_core::protocol::Value::String(s)) => std::path::PathBuf::from(s),
                     other => unreachable!(
                         "__breakpad_minidump_path should be a String, but was {:?}",
@@ -68,6 +270,19 @@ impl BreakpadTransport {
                     ),
                 };
 
+                // Set by the integration if the minidump can't be persisted
+                // until the next session
+                let send_immediately = event.extra.remove("__breakpad_send_immediately").is_some();
+                let style = if send_immediately {
+                    CrashSendStyle::SendImmediately
+                } else {
+                    self.style
+                };
+                // Set by the integration if the process keeps running after
+                // the crash event, eg. for a hang
+                let process_continues =
+                    event.extra.remove("__breakpad_process_continues").is_some();
+
                 let session_update = envelope.items().find_map(|ei| match ei {
                     proto::EnvelopeItem::SessionUpdate(su) => {
                         let mut su = su.clone();
@@ -83,32 +298,46 @@ impl BreakpadTransport {
                     session_update,
                 };
 
-                match self.style {
+                match style {
                     CrashSendStyle::SendImmediately => {
-                        let envelope = crate::shared::assemble_envelope(md, &minidump_path);
-
-                        if let Err(e) = std::fs::remove_file(&minidump_path) {
-                            debug_print!(
-                                "failed to remove crashdump {}: {}",
-                                minidump_path.display(),
-                                e
-                            );
+                        // If we're queueing, spool the crash to disk until it's
+                        // actually been delivered, unless it's not on disk in
+                        // the first place
+                        let spooled = self.queue.is_some() && !send_immediately;
+                        if spooled {
+                            write_metadata(&md, &minidump_path, self.compact_metadata);
+                        }
+
+                        let (envelope, _) = crate::shared::assemble_envelope(md, &minidump_path);
+
+                        if !spooled && !send_immediately {
+                            if let Err(e) = std::fs::remove_file(&minidump_path) {
+                                debug_print!(
+                                    "failed to remove crashdump {}: {}",
+                                    minidump_path.display(),
+                                    e
+                                );
+                            }
                         }
 
-                        Some(envelope)
+                        Some(Queued {
+                            envelope,
+                            spooled: spooled.then_some(minidump_path),
+                        })
                     }
                     CrashSendStyle::SendNextSession => {
-                        let serialized = md.serialize();
-
-                        minidump_path.set_extension("metadata");
-                        if let Err(e) = std::fs::write(&minidump_path, serialized) {
-                            debug_print!(
-                                "failed to write crash metadata {}: {}",
-                                minidump_path.display(),
-                                e
-                            );
+                        write_metadata(&md, &minidump_path, self.compact_metadata);
+                        None
+                    }
+                    CrashSendStyle::StoreAllNextSession => {
+                        write_metadata(&md, &minidump_path, self.compact_metadata);
+                        if !process_continues {
+                            *self
+                                .store_dir
+                                .lock()
+                                .unwrap_or_else(PoisonError::into_inner) =
+                                minidump_path.parent().map(Path::to_owned);
                         }
-
                         None
                     }
                 }
@@ -117,18 +346,80 @@ impl BreakpadTransport {
     }
 }
 
+/// Serializes an envelope to the crash directory, where it is picked up along
+/// with the crashes in the next session
+fn store_envelope(envelope: &Envelope, store_dir: &Path) {
+    // Unique across the processes sharing the crash directory, and the
+    // envelopes stored by this one
+    static STORED: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    let path = store_dir.join(format!(
+        "{}-{}-{}.envelope",
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+        std::process::id(),
+        STORED.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
+    ));
+
+    let mut serialized = Vec::new();
+    if let Err(e) = envelope
+        .to_writer(&mut serialized)
+        .and_then(|()| std::fs::write(&path, serialized))
+    {
+        debug_print!("failed to store envelope {}: {}", path.display(), e);
+    }
+}
+
+/// Writes the metadata for a crash next to its minidump
+fn write_metadata(md: &crate::shared::CrashMetadata, minidump_path: &Path, compact: bool) {
+    let serialized = if compact {
+        md.serialize_compact()
+    } else {
+        md.serialize()
+    };
+
+    let metadata_path = minidump_path.with_extension("metadata");
+    if let Err(e) = std::fs::write(&metadata_path, serialized) {
+        debug_print!(
+            "failed to write crash metadata {}: {}",
+            metadata_path.display(),
+            e
+        );
+    }
+}
+
 impl Transport for BreakpadTransport {
     fn send_envelope(&self, envelope: Envelope) {
-        if let Some(envelope) = self.process(envelope) {
-            self.inner.send_envelope(envelope);
+        // The process is going down after a crash, so the envelope is kept
+        // until the next session rather than being lost
+        if let Some(store_dir) = self
+            .store_dir
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_deref()
+        {
+            store_envelope(&envelope, store_dir);
+            return;
+        }
+
+        if let Some(queued) = self.process(envelope) {
+            match &self.queue {
+                Some(queue) => queue.push(queued),
+                None => self.inner.send_envelope(queued.envelope),
+            }
         }
     }
 
     fn flush(&self, timeout: Duration) -> bool {
-        self.inner.flush(timeout)
+        let start = Instant::now();
+        let drained = self.queue.as_ref().is_none_or(|queue| queue.drain(timeout));
+        self.inner.flush(timeout.saturating_sub(start.elapsed())) && drained
     }
 
     fn shutdown(&self, timeout: Duration) -> bool {
-        self.inner.shutdown(timeout)
+        let start = Instant::now();
+        let drained = self.queue.as_ref().is_none_or(|queue| queue.drain(timeout));
+        self.inner.shutdown(timeout.saturating_sub(start.elapsed())) && drained
     }
 }
@@ -0,0 +1,333 @@
+//! Uploading of the minidumps in a crash directory, along with the metadata
+//! and attachments written for them, shared by the integration, the daemon
+//! and [`CrashStore`](crate::CrashStore)
+
+use sentry_core::protocol as proto;
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Callback that captures an additional artifact for a crash, eg. a screenshot
+/// or UI state file, see
+/// [`BreakpadIntegration::register_companion_artifact`](crate::BreakpadIntegration::register_companion_artifact)
+type CompanionArtifact = dyn Fn(&Path) -> Vec<proto::Attachment> + Send + Sync;
+
+pub(crate) static COMPANION_ARTIFACTS: std::sync::Mutex<Vec<Box<CompanionArtifact>>> =
+    std::sync::Mutex::new(Vec::new());
+
+/// How long [`CrashStore::upload_pending_async`](crate::CrashStore::upload_pending_async)
+/// waits for each minidump to be flushed by the transport
+const UPLOAD_FLUSH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// The progress of [`CrashStore::upload_pending_async`](crate::CrashStore::upload_pending_async),
+/// reported after each minidump
+#[derive(Clone, Debug)]
+pub struct UploadProgress {
+    /// The minidump that was just handled, which has been removed from the
+    /// crash directory
+    pub minidump_path: PathBuf,
+    /// How many of the minidumps have been handled so far, including this one
+    pub completed: usize,
+    /// How many minidumps were pending when the upload started
+    pub total: usize,
+    /// Whether the transport finished sending the minidump in time, which is
+    /// always `false` for minidumps that were discarded because of
+    /// [`ConsentPolicy::NeverSend`](crate::ConsentPolicy::NeverSend)
+    pub sent: bool,
+}
+
+/// Uploads the minidumps in the crash directory one at a time on a background
+/// worker, waiting for each to be flushed by the transport before reading the
+/// next, until they have all been sent or the hub goes away
+pub(crate) fn upload_pending_async<F>(
+    crash_dir: PathBuf,
+    hub: std::sync::Weak<sentry_core::Hub>,
+    on_progress: F,
+) -> std::io::Result<std::thread::JoinHandle<()>>
+where
+    F: Fn(UploadProgress) + Send + 'static,
+{
+    std::thread::Builder::new()
+        .name("breakpad-uploader".to_owned())
+        .spawn(move || {
+            if let Some(client) = hub.upgrade().and_then(|hub| hub.client()) {
+                send_stored_envelopes(&crash_dir, &client);
+            }
+
+            // The minidumps are left in place until the user decides on each
+            // of them, so there is no progress to report
+            let consent = crate::consent::current();
+            if consent == crate::ConsentPolicy::AskBeforeSend {
+                return;
+            }
+
+            let store = crate::CrashStore::new(&crash_dir);
+            let pending = store.list();
+
+            let mut handled = Vec::new();
+            let index = crate::index::read(&crash_dir);
+            let total = pending.len();
+
+            for (i, crash) in pending.into_iter().enumerate() {
+                // Stop if the hub went away in the meantime
+                let client = match hub.upgrade().and_then(|hub| hub.client()) {
+                    Some(c) => c,
+                    None => break,
+                };
+
+                // Skipped if another process sharing the crash directory
+                // is already uploading it
+                let (id, sent) = if consent == crate::ConsentPolicy::NeverSend {
+                    let id = store
+                        .claim(&crash)
+                        .and_then(|_claim| crate::policy::remove(&crash_dir, &crash.minidump_path));
+                    (id, false)
+                } else {
+                    let id = store.claim(&crash).and_then(|_claim| {
+                        upload_minidump(&crash_dir, &index, &client, crash.minidump_path.clone())
+                    });
+                    let sent = id.is_some() && client.flush(Some(UPLOAD_FLUSH_TIMEOUT));
+                    (id, sent)
+                };
+                handled.extend(id);
+
+                on_progress(UploadProgress {
+                    minidump_path: crash.minidump_path,
+                    completed: i + 1,
+                    total,
+                    sent,
+                });
+            }
+
+            crate::index::prune(&crash_dir, &handled);
+        })
+}
+
+/// Uploads or discards the minidumps in the crash directory according to
+/// the current [`ConsentPolicy`](crate::ConsentPolicy)
+pub(crate) fn upload_consented(crash_dir: &Path, hub: &sentry_core::Hub) {
+    if let Some(client) = hub.client() {
+        send_stored_envelopes(crash_dir, &client);
+    }
+
+    match crate::consent::current() {
+        crate::ConsentPolicy::AlwaysSend => {
+            upload_minidumps(crash_dir, hub, &mut |_| true);
+        }
+        crate::ConsentPolicy::AskBeforeSend => {}
+        crate::ConsentPolicy::NeverSend => {
+            let discarded: Vec<_> = crate::consent::pending(crash_dir)
+                .into_iter()
+                .filter_map(|pc| crate::policy::remove(crash_dir, &pc.minidump_path))
+                .collect();
+            crate::index::prune(crash_dir, &discarded);
+        }
+    }
+}
+
+/// Sends the envelopes that were stored in the crash directory after a crash
+/// with [`CrashSendStyle::StoreAllNextSession`](crate::CrashSendStyle::StoreAllNextSession),
+/// and removes them. They aren't crash reports, so they are sent regardless of
+/// the [`ConsentPolicy`](crate::ConsentPolicy).
+fn send_stored_envelopes(crash_dir: &Path, client: &sentry_core::Client) {
+    let entries = match std::fs::read_dir(crash_dir) {
+        Ok(rd) => rd,
+        Err(_) => return,
+    };
+
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        if path.extension().and_then(|ext| ext.to_str()) != Some("envelope") {
+            continue;
+        }
+
+        let envelope = match sentry_core::Envelope::from_path(&path) {
+            Ok(envelope) => Some(envelope),
+            Err(e) => {
+                debug_print!("unable to read envelope {}: {}", path.display(), e);
+                None
+            }
+        };
+
+        // Removing it first means another process sharing the crash directory
+        // can't send it as well, and an unreadable one isn't retried forever
+        if let Err(e) = std::fs::remove_file(&path) {
+            debug_print!("failed to remove {}: {}", path.display(), e);
+            continue;
+        }
+
+        if let Some(envelope) = envelope {
+            client.send_envelope(envelope);
+        }
+    }
+}
+
+/// Called during startup to send any minidumps + metadata that have been
+/// captured in previous sessions but (seem to) have not been sent yet.
+/// Minidumps that `should_upload` returns `false` for are left in place, it is
+/// only asked about the minidumps that this process managed to claim.
+pub(crate) fn upload_minidumps(
+    crash_dir: &Path,
+    hub: &sentry_core::Hub,
+    should_upload: &mut dyn FnMut(&Path) -> bool,
+) {
+    let client = match hub.client() {
+        Some(c) => c,
+        None => return,
+    };
+
+    // Scan the directory the integration was initialized with to find any
+    // envelopes that have been serialized to disk and send + delete them
+    let store = crate::CrashStore::new(crash_dir);
+    let mut uploaded = Vec::new();
+    let index = crate::index::read(crash_dir);
+
+    // The minidumps are what we care about the most, but of course, the
+    // metadata that we (hopefully) were able to capture along with the crash
+    for crash in store.list() {
+        // Skipped if another process sharing the crash directory is
+        // already uploading it
+        let _claim = match store.claim(&crash) {
+            Some(claim) => claim,
+            None => continue,
+        };
+
+        if !should_upload(&crash.minidump_path) {
+            continue;
+        }
+
+        if let Some(id) = upload_minidump(crash_dir, &index, &client, crash.minidump_path) {
+            uploaded.push(id);
+        }
+    }
+
+    crate::index::prune(crash_dir, &uploaded);
+}
+
+/// Sends a single minidump + metadata, removing them from the crash
+/// directory, and returns the id of the minidump
+pub(crate) fn upload_minidump(
+    crash_dir: &Path,
+    index: &[crate::IndexEntry],
+    client: &sentry_core::Client,
+    mut minidump_path: PathBuf,
+) -> Option<String> {
+    minidump_path.set_extension("metadata");
+
+    let mut md = crate::shared::CrashMetadata::deserialize(&minidump_path);
+    if let Err(e) = std::fs::remove_file(&minidump_path) {
+        debug_print!("failed to remove {}: {}", minidump_path.display(), e);
+    }
+
+    minidump_path.set_extension("dmp");
+
+    // If the clock was wrong when the crash occurred, recompute the
+    // timestamp from the monotonic clock, keeping the original one
+    if let (Some(event), Some(boot_ref)) = (
+        md.event.as_mut(),
+        minidump_path
+            .file_stem()
+            .and_then(|fs| fs.to_str())
+            .and_then(|id| index.iter().find(|entry| entry.id == id))
+            .and_then(|entry| entry.boot_reference()),
+    ) {
+        if let Some(corrected) = crate::clock::corrected_timestamp(event.timestamp, &boot_ref) {
+            let original = event
+                .timestamp
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            event
+                .extra
+                .insert("breakpad.original_timestamp".to_owned(), original.into());
+            event.timestamp = corrected;
+        }
+    }
+
+    // Tag crashes that are the same as the previously uploaded one, eg.
+    // from crashing on every startup, rather than sending the same
+    // minidump over and over
+    if let (Some(event), Some(policy)) = (md.event.as_mut(), crate::recurrence::current()) {
+        let fingerprint = std::fs::read(&minidump_path)
+            .ok()
+            .and_then(|bytes| crate::recurrence::fingerprint(&bytes));
+        if let Some(fingerprint) = fingerprint {
+            if crate::recurrence::record(crash_dir, &fingerprint, event.timestamp, policy.window) {
+                event
+                    .tags
+                    .insert("breakpad.recurrence".to_owned(), "true".to_owned());
+
+                if policy.skip_minidump {
+                    event.extra.insert(
+                        "breakpad.minidump_unavailable".to_owned(),
+                        "minidump was not attached, as the crash is the same as the previous one"
+                            .into(),
+                    );
+                    if let Err(e) = std::fs::remove_file(&minidump_path) {
+                        debug_print!("failed to remove {}: {}", minidump_path.display(), e);
+                    }
+                }
+            }
+        }
+    }
+
+    let size = std::fs::metadata(&minidump_path).map_or(0, |md| md.len());
+    let (mut envelope, read_error) = crate::shared::assemble_envelope(md, &minidump_path);
+
+    // The minidump is still removed, as it's unlikely to become readable,
+    // but the loss is reported so that a broken crash pipeline doesn't
+    // just look like an absence of crashes
+    if let Some(e) = read_error.filter(|e| e.kind() != std::io::ErrorKind::NotFound) {
+        let mut extra = std::collections::BTreeMap::new();
+        extra.insert(
+            "breakpad.minidump_path".to_owned(),
+            minidump_path.display().to_string().into(),
+        );
+        extra.insert("breakpad.minidump_size".to_owned(), size.into());
+        extra.insert("breakpad.io_error".to_owned(), e.to_string().into());
+
+        client.capture_event(
+            proto::Event {
+                level: proto::Level::Warning,
+                message: Some(format!(
+                    "Unable to read minidump '{}', the crash was sent without it: {}",
+                    minidump_path.display(),
+                    e
+                )),
+                extra,
+                ..Default::default()
+            },
+            None,
+        );
+    }
+
+    if let Some(id) = minidump_path.file_stem().and_then(|fs| fs.to_str()) {
+        for attachment in crate::attachments::collect(crash_dir, id) {
+            envelope.add_item(attachment);
+        }
+    }
+
+    for capture in COMPANION_ARTIFACTS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .iter()
+    {
+        for attachment in capture(&minidump_path) {
+            envelope.add_item(attachment);
+        }
+    }
+
+    for attachment in crate::telemetry::decode(&minidump_path) {
+        envelope.add_item(attachment);
+    }
+
+    if let Err(e) = std::fs::remove_file(&minidump_path) {
+        debug_print!("failed to remove {}: {}", minidump_path.display(), e);
+    }
+
+    client.send_envelope(envelope);
+
+    minidump_path
+        .file_stem()
+        .and_then(|fs| fs.to_str())
+        .map(|id| id.to_owned())
+}
@@ -0,0 +1,363 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{atomic, Arc},
+    time::Duration,
+};
+
+/// How long the watcher waits for a change notification before rescanning
+/// the directory anyway, this is also the upper bound on how long it takes to
+/// drop the watcher
+const RESCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Watches a crash directory and notifies the application when new minidumps
+/// land in it, eg. dumps written by an external WER or launchd handler, so that
+/// uploads can be triggered without polling or restarting the application.
+///
+/// Change notifications come from inotify on Linux/Android, kqueue on macOS,
+/// and directory change notifications on Windows. Only files with a `.dmp`
+/// extension are reported, and only once their size has stopped changing
+/// between two scans of the directory, so that dumps that are still in the
+/// process of being written are not reported prematurely. Dumps that already
+/// exist when the watcher is created are not reported, as those are sent by
+/// [`BreakpadIntegration::new`](crate::BreakpadIntegration::new).
+pub struct DumpWatcher {
+    stop: Arc<atomic::AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+enum DumpState {
+    /// The dump has been seen with the specified size, but not reported yet
+    Pending(u64),
+    /// The dump has been reported, or was present when the watcher started
+    Reported,
+}
+
+impl DumpWatcher {
+    /// Starts watching the specified directory on a background thread, calling
+    /// `on_dump` with the full path of each new minidump.
+    pub fn new<F>(crash_dir: impl AsRef<Path>, on_dump: F) -> Result<Self, crate::Error>
+    where
+        F: Fn(PathBuf) + Send + 'static,
+    {
+        let crash_dir = crash_dir.as_ref().to_owned();
+        std::fs::create_dir_all(&crash_dir)?;
+
+        let mut notifier = sys::Notifier::new(&crash_dir)?;
+
+        let stop = Arc::new(atomic::AtomicBool::new(false));
+        let thread_stop = stop.clone();
+
+        let thread = std::thread::Builder::new()
+            .name("breakpad-dump-watcher".to_owned())
+            .spawn(move || {
+                let mut dumps: HashMap<_, _> = list_dumps(&crash_dir)
+                    .into_iter()
+                    .map(|(path, _)| (path, DumpState::Reported))
+                    .collect();
+
+                while !thread_stop.load(atomic::Ordering::Relaxed) {
+                    if let Err(e) = notifier.wait(RESCAN_INTERVAL) {
+                        // Fall back to just rescanning the directory periodically
+                        debug_print!(
+                            "failed to wait for changes in '{}': {}",
+                            crash_dir.display(),
+                            e
+                        );
+                        std::thread::sleep(RESCAN_INTERVAL);
+                    }
+
+                    let current = list_dumps(&crash_dir);
+                    let present: HashSet<_> =
+                        current.iter().map(|(path, _)| path.clone()).collect();
+
+                    for (path, len) in current {
+                        match dumps.get(&path) {
+                            Some(DumpState::Reported) => {}
+                            Some(DumpState::Pending(prev)) if *prev == len && len > 0 => {
+                                dumps.insert(path.clone(), DumpState::Reported);
+                                on_dump(path);
+                            }
+                            _ => {
+                                dumps.insert(path, DumpState::Pending(len));
+                            }
+                        }
+                    }
+
+                    dumps.retain(|path, _| present.contains(path));
+                }
+            })?;
+
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for DumpWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, atomic::Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Gets the path and current size of every minidump in the directory
+fn list_dumps(crash_dir: &Path) -> Vec<(PathBuf, u64)> {
+    crate::CrashStore::new(crash_dir)
+        .list()
+        .into_iter()
+        .map(|crash| (crash.minidump_path, crash.size))
+        .collect()
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[allow(unsafe_code)]
+mod sys {
+    use std::{io, path::Path, time::Duration};
+
+    pub(super) struct Notifier {
+        fd: libc::c_int,
+    }
+
+    impl Notifier {
+        pub(super) fn new(dir: &Path) -> io::Result<Self> {
+            use std::os::unix::ffi::OsStrExt;
+
+            let path = std::ffi::CString::new(dir.as_os_str().as_bytes())?;
+
+            // SAFETY: syscalls, the path is a valid nul terminated string
+            unsafe {
+                let fd = libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC);
+                if fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                // We only care about files that have been completely written,
+                // either directly or by being moved into the directory
+                if libc::inotify_add_watch(
+                    fd,
+                    path.as_ptr(),
+                    libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO,
+                ) < 0
+                {
+                    let err = io::Error::last_os_error();
+                    libc::close(fd);
+                    return Err(err);
+                }
+
+                Ok(Self { fd })
+            }
+        }
+
+        pub(super) fn wait(&mut self, timeout: Duration) -> io::Result<()> {
+            let mut pfd = libc::pollfd {
+                fd: self.fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+
+            // SAFETY: syscall, pfd is valid for the duration of the call
+            if unsafe { libc::poll(&mut pfd, 1, timeout.as_millis() as libc::c_int) } < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+
+            // Drain the queued events, we rescan the directory rather than
+            // keeping track of the individual files in the events
+            let mut buf = [0u8; 4096];
+            // SAFETY: syscall, the buffer is valid for its entire length
+            while unsafe { libc::read(self.fd, buf.as_mut_ptr().cast(), buf.len()) } > 0 {}
+
+            Ok(())
+        }
+    }
+
+    impl Drop for Notifier {
+        fn drop(&mut self) {
+            // SAFETY: syscall, we own the fd
+            unsafe {
+                libc::close(self.fd);
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+#[allow(unsafe_code)]
+mod sys {
+    use std::{io, path::Path, time::Duration};
+
+    pub(super) struct Notifier {
+        kq: libc::c_int,
+        dir: libc::c_int,
+    }
+
+    impl Notifier {
+        pub(super) fn new(dir: &Path) -> io::Result<Self> {
+            use std::os::unix::ffi::OsStrExt;
+
+            let path = std::ffi::CString::new(dir.as_os_str().as_bytes())?;
+
+            // SAFETY: syscalls, the path is a valid nul terminated string
+            unsafe {
+                let dir = libc::open(path.as_ptr(), libc::O_EVTONLY | libc::O_CLOEXEC);
+                if dir < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                let kq = libc::kqueue();
+                if kq < 0 {
+                    let err = io::Error::last_os_error();
+                    libc::close(dir);
+                    return Err(err);
+                }
+
+                // A write to the directory vnode means an entry was added,
+                // removed, or renamed
+                let change = libc::kevent {
+                    ident: dir as usize,
+                    filter: libc::EVFILT_VNODE,
+                    flags: libc::EV_ADD | libc::EV_CLEAR,
+                    fflags: libc::NOTE_WRITE,
+                    data: 0,
+                    udata: std::ptr::null_mut(),
+                };
+
+                if libc::kevent(kq, &change, 1, std::ptr::null_mut(), 0, std::ptr::null()) < 0 {
+                    let err = io::Error::last_os_error();
+                    libc::close(kq);
+                    libc::close(dir);
+                    return Err(err);
+                }
+
+                Ok(Self { kq, dir })
+            }
+        }
+
+        pub(super) fn wait(&mut self, timeout: Duration) -> io::Result<()> {
+            let ts = libc::timespec {
+                tv_sec: timeout.as_secs() as libc::time_t,
+                tv_nsec: timeout.subsec_nanos() as libc::c_long,
+            };
+
+            // SAFETY: syscall, the event and timeout are valid for the duration
+            // of the call
+            unsafe {
+                let mut event: libc::kevent = std::mem::zeroed();
+                if libc::kevent(self.kq, std::ptr::null(), 0, &mut event, 1, &ts) < 0 {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    impl Drop for Notifier {
+        fn drop(&mut self) {
+            // SAFETY: syscalls, we own both fds
+            unsafe {
+                libc::close(self.kq);
+                libc::close(self.dir);
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+#[allow(unsafe_code)]
+mod sys {
+    use std::{io, path::Path, time::Duration};
+
+    type Handle = *mut std::ffi::c_void;
+
+    const INVALID_HANDLE_VALUE: Handle = -1isize as Handle;
+    const FILE_NOTIFY_CHANGE_FILE_NAME: u32 = 0x1;
+    const FILE_NOTIFY_CHANGE_SIZE: u32 = 0x8;
+    const FILE_NOTIFY_CHANGE_LAST_WRITE: u32 = 0x10;
+    const WAIT_OBJECT_0: u32 = 0x0;
+    const WAIT_FAILED: u32 = 0xffff_ffff;
+
+    extern "system" {
+        fn FindFirstChangeNotificationW(
+            path_name: *const u16,
+            watch_subtree: i32,
+            notify_filter: u32,
+        ) -> Handle;
+        fn FindNextChangeNotification(handle: Handle) -> i32;
+        fn FindCloseChangeNotification(handle: Handle) -> i32;
+        fn WaitForSingleObject(handle: Handle, milliseconds: u32) -> u32;
+    }
+
+    pub(super) struct Notifier {
+        handle: Handle,
+    }
+
+    // SAFETY: the change notification handle is only ever used by the thread
+    // that owns the watcher
+    unsafe impl Send for Notifier {}
+
+    impl Notifier {
+        pub(super) fn new(dir: &Path) -> io::Result<Self> {
+            use std::os::windows::ffi::OsStrExt;
+
+            let path: Vec<u16> = dir
+                .as_os_str()
+                .encode_wide()
+                .chain(std::iter::once(0))
+                .collect();
+
+            // SAFETY: calling into Win32, the path is a valid nul terminated string
+            let handle = unsafe {
+                FindFirstChangeNotificationW(
+                    path.as_ptr(),
+                    0,
+                    FILE_NOTIFY_CHANGE_FILE_NAME
+                        | FILE_NOTIFY_CHANGE_SIZE
+                        | FILE_NOTIFY_CHANGE_LAST_WRITE,
+                )
+            };
+
+            if handle == INVALID_HANDLE_VALUE {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(Self { handle })
+        }
+
+        pub(super) fn wait(&mut self, timeout: Duration) -> io::Result<()> {
+            // SAFETY: calling into Win32, we own the handle
+            unsafe {
+                match WaitForSingleObject(self.handle, timeout.as_millis() as u32) {
+                    WAIT_FAILED => Err(io::Error::last_os_error()),
+                    WAIT_OBJECT_0 => {
+                        // Rearm the notification for the next wait
+                        if FindNextChangeNotification(self.handle) == 0 {
+                            Err(io::Error::last_os_error())
+                        } else {
+                            Ok(())
+                        }
+                    }
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+
+    impl Drop for Notifier {
+        fn drop(&mut self) {
+            // SAFETY: calling into Win32, we own the handle
+            unsafe {
+                FindCloseChangeNotification(self.handle);
+            }
+        }
+    }
+}